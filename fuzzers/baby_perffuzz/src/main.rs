@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use libafl::{
+    bolts::{current_nanos, rands::StdRand, tuples::tuple_list, AsSlice},
+    corpus::{Corpus, InMemoryCorpus, OnDiskCorpus},
+    events::SimpleEventManager,
+    executors::{inprocess::InProcessExecutor, ExitKind},
+    feedbacks::{MaxHitCountFeedback, TotalHitCountFeedback},
+    fuzzer::{Fuzzer, StdFuzzer},
+    generators::RandPrintablesGenerator,
+    inputs::{BytesInput, HasTargetBytes},
+    monitors::SimpleMonitor,
+    mutators::scheduled::{havoc_mutations, StdScheduledMutator},
+    observers::StdMapObserver,
+    schedulers::{MaxHitCountScheduler, QueueScheduler},
+    stages::mutational::StdMutationalStage,
+    state::{HasMaxSize, HasSolutions, StdState},
+    Error,
+};
+
+/// The maximum input size
+const MAX_SIZE: usize = 256;
+
+/// The total hit count above which an input is considered a slow input.
+/// The sort of `MAX_SIZE` bytes hits at most `1 + 255 + 255 * 256 / 2 = 32_896` edges.
+const THRESHOLD: u64 = 20_000;
+
+/// Hit counts map with explicit increments due to the lack of instrumentation
+static mut HITS: [u32; 4] = [0; 4];
+
+/// Count a hit of the given edge
+fn hit(idx: usize) {
+    unsafe { HITS[idx] = HITS[idx].saturating_add(1) };
+}
+
+/// An insertion sort, quadratic in the worst case
+fn insertion_sort(buf: &mut [u8]) {
+    hit(0);
+    for i in 1..buf.len() {
+        hit(1);
+        let mut j = i;
+        while j > 0 && buf[j - 1] > buf[j] {
+            hit(2);
+            buf.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+#[allow(clippy::similar_names)]
+pub fn main() -> Result<(), Error> {
+    // The closure that we want to fuzz
+    let mut harness = |input: &BytesInput| {
+        let target = input.target_bytes();
+        let mut buf = target.as_slice().to_vec();
+        insertion_sort(&mut buf);
+        ExitKind::Ok
+    };
+
+    // Create an observation channel using the hit counts map.
+    // The counts must not be classified into buckets, so no `HitcountsMapObserver` here.
+    let observer = unsafe { StdMapObserver::new_from_ptr("hits", HITS.as_mut_ptr(), HITS.len()) };
+
+    // Keep the inputs that hit an edge more often than all inputs before
+    let mut feedback = MaxHitCountFeedback::new(&observer);
+
+    // An input is a solution if it executes too many edges
+    let mut objective = TotalHitCountFeedback::new(&observer, THRESHOLD);
+
+    // create a State from scratch
+    let mut state = StdState::new(
+        // RNG
+        StdRand::with_seed(current_nanos()),
+        // Corpus that will be evolved, we keep it in memory for performance
+        InMemoryCorpus::new(),
+        // Corpus in which we store the slow inputs
+        OnDiskCorpus::new(PathBuf::from("./slow_inputs")).unwrap(),
+        // States of the feedbacks, here the maximum hit count of each edge
+        &mut feedback,
+        // Same for objective feedbacks
+        &mut objective,
+    )?;
+    state.set_max_size(MAX_SIZE);
+
+    // The Monitor trait define how the fuzzer stats are displayed to the user
+    let mon = SimpleMonitor::new(|s| println!("{}", s));
+
+    // The event manager handle the various events generated during the fuzzing loop
+    let mut mgr = SimpleEventManager::new(mon);
+
+    // Favor the inputs holding the maximum hit count of an edge
+    let scheduler = MaxHitCountScheduler::new(QueueScheduler::new());
+
+    // A fuzzer with feedbacks and a corpus scheduler
+    let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+
+    // Create the executor for an in-process function with just one observer
+    let mut executor = InProcessExecutor::new(
+        &mut harness,
+        tuple_list!(observer),
+        &mut fuzzer,
+        &mut state,
+        &mut mgr,
+    )?;
+
+    // Generator of printable bytearrays of max size 32
+    let mut generator = RandPrintablesGenerator::new(32);
+
+    // Generate 8 initial inputs
+    state.generate_initial_inputs(&mut fuzzer, &mut executor, &mut generator, &mut mgr, 8)?;
+
+    // Setup a mutational stage with a basic bytes mutator
+    let mutator = StdScheduledMutator::new(havoc_mutations());
+    let mut stages = tuple_list!(StdMutationalStage::new(mutator));
+
+    while state.solutions().count() == 0 {
+        fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)?;
+    }
+    println!("Found a slow input, see ./slow_inputs");
+    Ok(())
+}
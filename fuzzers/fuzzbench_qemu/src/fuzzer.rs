@@ -21,15 +21,14 @@ use libafl::{
         rands::StdRand,
         shmem::{ShMemProvider, StdShMemProvider},
         tuples::{tuple_list, Merge},
-        AsSlice,
     },
-    corpus::{Corpus, OnDiskCorpus},
-    events::SimpleRestartingEventManager,
-    executors::{ExitKind, ShadowExecutor, TimeoutExecutor},
+    corpus::{Corpus, InMemoryCorpus, OnDiskCorpus},
+    events::{SimpleEventManager, SimpleRestartingEventManager},
+    executors::{ShadowExecutor, TimeoutExecutor},
     feedback_or,
     feedbacks::{CrashFeedback, MaxMapFeedback, TimeFeedback},
     fuzzer::{Fuzzer, StdFuzzer},
-    inputs::{BytesInput, HasTargetBytes},
+    inputs::{BytesInput, Input},
     monitors::SimpleMonitor,
     mutators::{
         scheduled::havoc_mutations, token_mutations::I2SRandReplace, tokens_mutations,
@@ -38,6 +37,7 @@ use libafl::{
     observers::{HitcountsMapObserver, TimeObserver, VariableMapObserver},
     schedulers::{
         powersched::PowerSchedule, IndexesLenTimeMinimizerScheduler, PowerQueueScheduler,
+        QueueScheduler,
     },
     stages::{
         calibrate::CalibrationStage, power::StdPowerMutationalStage, ShadowTracingStage,
@@ -52,12 +52,14 @@ use libafl_qemu::{
     cmplog::{CmpLogObserver, QemuCmpLogHelper},
     edges,
     edges::QemuEdgeCoverageHelper,
-    elf::EasyElf,
     emu::Emulator,
     filter_qemu_args,
     hooks::QemuHooks,
-    //snapshot::QemuSnapshotHelper,
-    MmapPerms,
+    persistent::{
+        persistent_harness, QemuPersistentHelper, QemuPersistentHelperBuilder, QemuPersistentInput,
+    },
+    snapshot::QemuSnapshotHelper,
+    trace::{QemuTraceHelper, QemuTraceObserver, QemuTraceReader},
     QemuExecutor,
     Regs,
 };
@@ -76,14 +78,14 @@ pub fn main() {
             Arg::new("out")
                 .help("The directory to place finds in ('corpus')")
                 .long("libafl-out")
-                .required(true)
+                .required_unless_present("replay")
                 .takes_value(true),
         )
         .arg(
             Arg::new("in")
                 .help("The directory to read initial inputs from ('seeds')")
                 .long("libafl-in")
-                .required(true)
+                .required_unless_present("replay")
                 .takes_value(true),
         )
         .arg(
@@ -104,6 +106,18 @@ pub fn main() {
                 .help("Timeout for each individual execution, in milliseconds")
                 .default_value("1000"),
         )
+        .arg(
+            Arg::new("replay")
+                .long("libafl-replay")
+                .help("Replays this input instead of fuzzing, recording its execution to the trace file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("trace")
+                .long("libafl-trace")
+                .help("The trace file written when replaying an input")
+                .default_value("trace.bin"),
+        )
         .try_get_matches_from(filter_qemu_args())
     {
         Ok(res) => res,
@@ -124,6 +138,21 @@ pub fn main() {
         env::current_dir().unwrap().to_string_lossy().to_string()
     );
 
+    let timeout = Duration::from_millis(
+        res.value_of("timeout")
+            .unwrap()
+            .to_string()
+            .parse()
+            .expect("Could not parse timeout in milliseconds"),
+    );
+
+    if let Some(input_file) = res.value_of("replay") {
+        let trace_file = PathBuf::from(res.value_of("trace").unwrap().to_string());
+        replay(PathBuf::from(input_file), trace_file, timeout)
+            .expect("An error occurred while replaying");
+        return;
+    }
+
     // For fuzzbench, crashes and finds are inside the same `corpus` directory, in the "queue" and "crashes" subdir.
     let mut out_dir = PathBuf::from(res.value_of("out").unwrap().to_string());
     if fs::create_dir(&out_dir).is_err() {
@@ -147,18 +176,106 @@ pub fn main() {
 
     let logfile = PathBuf::from(res.value_of("logfile").unwrap().to_string());
 
-    let timeout = Duration::from_millis(
-        res.value_of("timeout")
-            .unwrap()
-            .to_string()
-            .parse()
-            .expect("Could not parse timeout in milliseconds"),
-    );
-
     fuzz(out_dir, crashes, in_dir, tokens, logfile, timeout)
         .expect("An error occurred while fuzzing");
 }
 
+/// The number of blocks printed after a replay
+const REPLAY_LAST_BLOCKS: usize = 16;
+
+/// Builds the persistent helper, stopping each run when `LLVMFuzzerTestOneInput` returns
+fn persistent_helper(emu: &Emulator) -> Result<QemuPersistentHelper, Error> {
+    let persistent = QemuPersistentHelperBuilder::new()
+        .entry_symbol("LLVMFuzzerTestOneInput")
+        .input(QemuPersistentInput::Registers {
+            buf_reg: Regs::Rdi,
+            len_reg: Regs::Rsi,
+        })
+        .build(emu)?;
+
+    println!("LLVMFuzzerTestOneInput @ {:#x}", persistent.entry());
+    println!("Return address = {:#x}", persistent.exit());
+    println!("Placing input at {:#x}", persistent.input_addr());
+    Ok(persistent)
+}
+
+/// Replays an input with the helpers of the fuzzer, and the trace helper recording the execution.
+/// If the input crashes, the process exits after writing the trace, load it with a `QemuTraceReader`.
+fn replay(input_file: PathBuf, trace_file: PathBuf, timeout: Duration) -> Result<(), Error> {
+    env::remove_var("LD_LIBRARY_PATH");
+
+    let args: Vec<String> = env::args().collect();
+    let env: Vec<(String, String)> = env::vars().collect();
+    let emu = Emulator::new(&args, &env);
+
+    let persistent = persistent_helper(&emu)?;
+
+    let edges = unsafe { &mut edges::EDGES_MAP };
+    let edges_counter = unsafe { &mut edges::MAX_EDGES_NUM };
+    let edges_observer =
+        HitcountsMapObserver::new(VariableMapObserver::new("edges", edges, edges_counter));
+
+    // Writes the trace at the end of the run, also when it crashes
+    let trace_observer = QemuTraceObserver::new("trace", &trace_file);
+
+    let mut feedback = ();
+    let mut objective = CrashFeedback::new();
+
+    let mut state = StdState::new(
+        StdRand::with_seed(current_nanos()),
+        InMemoryCorpus::new(),
+        InMemoryCorpus::new(),
+        &mut feedback,
+        &mut objective,
+    )?;
+
+    let mut mgr = SimpleEventManager::new(SimpleMonitor::new(|s| println!("{}", s)));
+
+    let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+
+    let mut harness = persistent_harness::<BytesInput>(&emu);
+
+    let hooks = QemuHooks::new(
+        &emu,
+        tuple_list!(
+            QemuEdgeCoverageHelper::default(),
+            QemuCmpLogHelper::default(),
+            persistent,
+            QemuTraceHelper::new().regs([
+                Regs::Rax,
+                Regs::Rbx,
+                Regs::Rcx,
+                Regs::Rdx,
+                Regs::Rsi,
+                Regs::Rdi,
+                Regs::Rbp,
+                Regs::Rsp,
+            ]),
+        ),
+    );
+
+    let executor = QemuExecutor::new(
+        hooks,
+        &mut harness,
+        tuple_list!(edges_observer, trace_observer),
+        &mut fuzzer,
+        &mut state,
+        &mut mgr,
+    )?;
+    let mut executor = TimeoutExecutor::new(executor, timeout);
+
+    let input = BytesInput::from_file(&input_file)?;
+    let exit_kind = fuzzer.execute_input(&mut state, &mut executor, &mut mgr, &input)?;
+    println!("Replayed {:?}: {:?}", input_file, exit_kind);
+
+    let trace = QemuTraceReader::from_file(&trace_file)?;
+    println!("Last blocks, trace written to {:?}:", trace_file);
+    for block in trace.last_blocks(REPLAY_LAST_BLOCKS) {
+        println!("{:#x} {:x?}", block.pc, block.regs);
+    }
+    Ok(())
+}
+
 /// The actual fuzzer
 fn fuzz(
     corpus_dir: PathBuf,
@@ -174,32 +291,8 @@ fn fuzz(
     let env: Vec<(String, String)> = env::vars().collect();
     let emu = Emulator::new(&args, &env);
 
-    let mut elf_buffer = Vec::new();
-    let elf = EasyElf::from_file(emu.binary_path(), &mut elf_buffer)?;
-
-    let test_one_input_ptr = elf
-        .resolve_symbol("LLVMFuzzerTestOneInput", emu.load_addr())
-        .expect("Symbol LLVMFuzzerTestOneInput not found");
-    println!("LLVMFuzzerTestOneInput @ {:#x}", test_one_input_ptr);
-
-    emu.set_breakpoint(test_one_input_ptr); // LLVMFuzzerTestOneInput
-    unsafe { emu.run() };
-
-    println!("Break at {:#x}", emu.read_reg::<_, u64>(Regs::Rip).unwrap());
-
-    let stack_ptr: u64 = emu.read_reg(Regs::Rsp).unwrap();
-    let mut ret_addr = [0; 8];
-    unsafe { emu.read_mem(stack_ptr, &mut ret_addr) };
-    let ret_addr = u64::from_le_bytes(ret_addr);
-
-    println!("Stack pointer = {:#x}", stack_ptr);
-    println!("Return address = {:#x}", ret_addr);
-
-    emu.remove_breakpoint(test_one_input_ptr); // LLVMFuzzerTestOneInput
-    emu.set_breakpoint(ret_addr); // LLVMFuzzerTestOneInput ret addr
-
-    let input_addr = emu.map_private(0, 4096, MmapPerms::ReadWrite).unwrap();
-    println!("Placing input at {:#x}", input_addr);
+    // Each run starts at LLVMFuzzerTestOneInput and stops when it returns
+    let persistent = persistent_helper(&emu)?;
 
     let log = RefCell::new(
         OpenOptions::new()
@@ -308,29 +401,9 @@ fn fuzz(
     // A fuzzer with feedbacks and a corpus scheduler
     let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
 
-    // The wrapped harness function, calling out to the LLVM-style harness
-    let mut harness = |input: &BytesInput| {
-        let target = input.target_bytes();
-        let mut buf = target.as_slice();
-        let mut len = buf.len();
-        if len > 4096 {
-            buf = &buf[0..4096];
-            len = 4096;
-        }
-
-        unsafe {
-            emu.write_mem(input_addr, buf);
-
-            emu.write_reg(Regs::Rdi, input_addr).unwrap();
-            emu.write_reg(Regs::Rsi, len).unwrap();
-            emu.write_reg(Regs::Rip, test_one_input_ptr).unwrap();
-            emu.write_reg(Regs::Rsp, stack_ptr).unwrap();
-
-            emu.run();
-        }
-
-        ExitKind::Ok
-    };
+    // The harness resumes the emulator, the persistent helper restores the registers and writes the input.
+    // The snapshot helper comes first, to restore the memory of the persistent entry before the input is written.
+    let mut harness = persistent_harness::<BytesInput>(&emu);
 
     let hooks = QemuHooks::new(
         &emu,
@@ -338,7 +411,8 @@ fn fuzz(
             QemuEdgeCoverageHelper::default(),
             QemuCmpLogHelper::default(),
             //QemuAsanHelper::new(),
-            //QemuSnapshotHelper::new()
+            QemuSnapshotHelper::new(),
+            persistent,
         ),
     );
 
@@ -0,0 +1,156 @@
+//! Persistent on-disk checkpoints of the fuzzer state.
+//! Unlike the [`crate::bolts::staterestore::StateRestorer`], which only hands the state to the next client via shared memory,
+//! a [`StateCheckpoint`] survives host reboots and broker crashes, so long-running campaigns can be resumed.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// The file extension used for checkpoint files
+pub const CHECKPOINT_EXTENSION: &str = "libafl_checkpoint";
+
+/// A checkpoint file on disk, containing the complete serialized state of one fuzzer client.
+/// Checkpoints are written atomically: a crash while saving leaves the previous checkpoint intact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateCheckpoint {
+    path: PathBuf,
+}
+
+impl StateCheckpoint {
+    /// Create a new [`StateCheckpoint`], stored in the file at `path`.
+    pub fn new<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The [`StateCheckpoint`] of the client with the given id (usually the core id) in the checkpoint directory `dir`.
+    pub fn for_client<P>(dir: P, client_id: usize) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(
+            dir.as_ref()
+                .join(format!("client_{}.{}", client_id, CHECKPOINT_EXTENSION)),
+        )
+    }
+
+    /// The path of the checkpoint file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// If a checkpoint has been written to disk already
+    #[must_use]
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Saves the state to disk, replacing the previous checkpoint.
+    /// The state is written to a tmpfile first, synced, and then moved over the old checkpoint.
+    pub fn save<S>(&self, state: &S) -> Result<(), Error>
+    where
+        S: Serialize,
+    {
+        let serialized = postcard::to_allocvec(state)?;
+
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(dir)?;
+
+        let filename = self.path.file_name().ok_or_else(|| {
+            Error::illegal_argument(format!("Invalid checkpoint path {:?}", self.path))
+        })?;
+        let tmpfile_name = dir.join(format!(".{}.tmp", filename.to_string_lossy()));
+
+        // A leftover tmpfile from an interrupted save will simply be overwritten
+        let mut tmpfile = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmpfile_name)?;
+        tmpfile.write_all(&serialized)?;
+        tmpfile.sync_all()?;
+        drop(tmpfile);
+
+        fs::rename(&tmpfile_name, &self.path)?;
+
+        // Make sure the rename itself hits the disk
+        #[cfg(unix)]
+        File::open(dir)?.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Loads the state from disk.
+    /// Returns `None` if no checkpoint has been written yet.
+    pub fn load<S>(&self) -> Result<Option<S>, Error>
+    where
+        S: DeserializeOwned,
+    {
+        if !self.exists() {
+            return Ok(None);
+        }
+
+        let mut serialized = vec![];
+        File::open(&self.path)?.read_to_end(&mut serialized)?;
+        let state = postcard::from_bytes(&serialized).map_err(|e| {
+            Error::illegal_state(format!(
+                "Could not deserialize the checkpoint {:?} (corrupted, or written by a different fuzzer?): {}",
+                self.path, e
+            ))
+        })?;
+        Ok(Some(state))
+    }
+
+    /// Removes the checkpoint from disk, if it exists.
+    pub fn remove(&self) -> Result<(), Error> {
+        if self.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        string::{String, ToString},
+        vec::Vec,
+    };
+    use std::env::temp_dir;
+
+    use crate::bolts::checkpoint::StateCheckpoint;
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let checkpoint = StateCheckpoint::for_client(
+            temp_dir().join(format!("libafl_checkpoint_test_{}", std::process::id())),
+            0,
+        );
+        assert_eq!(checkpoint.load::<Vec<String>>().unwrap(), None);
+
+        let state = vec!["hello".to_string(), "world".to_string()];
+        checkpoint.save(&state).unwrap();
+        assert_eq!(checkpoint.load::<Vec<String>>().unwrap(), Some(state));
+
+        let state = vec!["resumed".to_string()];
+        checkpoint.save(&state).unwrap();
+        assert_eq!(checkpoint.load::<Vec<String>>().unwrap(), Some(state));
+
+        checkpoint.remove().unwrap();
+        assert!(!checkpoint.exists());
+        std::fs::remove_dir(checkpoint.path().parent().unwrap()).unwrap();
+    }
+}
@@ -16,7 +16,7 @@ use crate::bolts::os::startable_self;
 use crate::bolts::os::{dup2, fork, ForkResult};
 #[cfg(feature = "std")]
 use crate::{
-    bolts::{core_affinity::Cores, shmem::ShMemProvider},
+    bolts::{checkpoint::StateCheckpoint, core_affinity::Cores, shmem::ShMemProvider},
     events::{EventConfig, LlmpRestartingEventManager, ManagerKind, RestartingMgr},
     inputs::Input,
     monitors::Monitor,
@@ -33,13 +33,13 @@ use core::fmt::{self, Debug, Formatter};
 use core::marker::PhantomData;
 #[cfg(feature = "std")]
 use serde::de::DeserializeOwned;
-#[cfg(feature = "std")]
-use std::net::SocketAddr;
 #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
 use std::process::Stdio;
 #[cfg(all(unix, feature = "std", feature = "fork"))]
 use std::{fs::File, os::unix::io::AsRawFd};
 #[cfg(feature = "std")]
+use std::{net::SocketAddr, path::PathBuf};
+#[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
 
 /// The (internal) `env` that indicates we're running as client.
@@ -84,6 +84,11 @@ where
     /// Then, clients launched by this [`Launcher`] can connect to the original `broker`.
     #[builder(default = true)]
     spawn_broker: bool,
+    /// A directory containing a [`StateCheckpoint`] for each client, named after the core id.
+    /// Clients resume from their checkpoint, if it exists, instead of starting with a fresh state.
+    /// Checkpoints can be written using the [`crate::stages::CheckpointStage`].
+    #[builder(default = None)]
+    checkpoint_dir: Option<PathBuf>,
     #[builder(setter(skip), default = PhantomData)]
     phantom_data: PhantomData<(&'a I, &'a OT, &'a S, &'a SP)>,
 }
@@ -105,6 +110,7 @@ where
             .field("spawn_broker", &self.spawn_broker)
             .field("remote_broker_addr", &self.remote_broker_addr)
             .field("stdout_file", &self.stdout_file)
+            .field("checkpoint_dir", &self.checkpoint_dir)
             .finish_non_exhaustive()
     }
 }
@@ -180,6 +186,11 @@ where
                             .kind(ManagerKind::Client {
                                 cpu_core: Some(*bind_to),
                             })
+                            .checkpoint(
+                                self.checkpoint_dir
+                                    .as_ref()
+                                    .map(|dir| StateCheckpoint::for_client(dir, bind_to.id)),
+                            )
                             .configuration(self.configuration)
                             .build()
                             .launch()?;
@@ -250,6 +261,11 @@ where
                     .kind(ManagerKind::Client {
                         cpu_core: Some(CoreId { id: core_id }),
                     })
+                    .checkpoint(
+                        self.checkpoint_dir
+                            .as_ref()
+                            .map(|dir| StateCheckpoint::for_client(dir, core_id)),
+                    )
                     .configuration(self.configuration)
                     .build()
                     .launch()?;
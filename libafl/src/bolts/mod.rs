@@ -3,6 +3,8 @@
 pub mod anymap;
 #[cfg(feature = "std")]
 pub mod build_id;
+#[cfg(feature = "std")]
+pub mod checkpoint;
 #[cfg(all(
     any(feature = "cli", feature = "frida_cli", feature = "qemu_cli"),
     feature = "std"
@@ -25,6 +27,7 @@ pub mod ownedref;
 pub mod rands;
 pub mod serdeany;
 pub mod shmem;
+pub mod simd;
 #[cfg(feature = "std")]
 pub mod staterestore;
 pub mod tuples;
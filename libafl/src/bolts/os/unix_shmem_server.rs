@@ -60,6 +60,17 @@ const UNIX_SERVER_NAME: &str = "./libafl_unix_shmem_server";
 /// Env variable. If set, we won't try to spawn the service
 const AFL_SHMEM_SERVICE_STARTED: &str = "AFL_SHMEM_SERVICE_STARTED";
 
+/// The fd of a map of the inner [`ShMemProvider`] of the server.
+/// The id of the map is either just the fd, or `<pid>/<fd>`, as used by the `MemfdShMemProvider`.
+fn shmem_fd(id: &ShMemId) -> i32 {
+    id.as_str()
+        .rsplit('/')
+        .next()
+        .unwrap()
+        .parse()
+        .expect("The id of a served map must end with its fd")
+}
+
 /// Hands out served shared maps, as used on Android.
 #[derive(Debug)]
 pub struct ServedShMemProvider<SP>
@@ -536,7 +547,7 @@ where
                 let description = new_shmem.description();
                 let new_rc = Rc::new(RefCell::new(new_shmem));
                 self.all_shmems
-                    .insert(description.id.into(), Rc::downgrade(&new_rc));
+                    .insert(shmem_fd(&description.id), Rc::downgrade(&new_rc));
                 Ok(ServedShMemResponse::Mapping(new_rc))
             }
             ServedShMemRequest::ExistingMap(description) => {
@@ -610,11 +621,11 @@ where
         match response {
             ServedShMemResponse::Mapping(mapping) => {
                 let id = mapping.as_ref().borrow().id();
-                let server_fd: i32 = id.to_string().parse().unwrap();
+                let server_fd = shmem_fd(&id);
                 let client = self.clients.get_mut(&client_id).unwrap();
                 client
                     .stream
-                    .send_fds(id.to_string().as_bytes(), &[server_fd])?;
+                    .send_fds(server_fd.to_string().as_bytes(), &[server_fd])?;
                 client.maps.entry(server_fd).or_default().push(mapping);
             }
             ServedShMemResponse::Id(id) => {
@@ -27,6 +27,9 @@ pub use unix_shmem::{MmapShMem, MmapShMemProvider};
 #[cfg(all(feature = "std", unix))]
 pub use unix_shmem::{UnixShMem, UnixShMemProvider};
 
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use unix_shmem::memfd::{MemfdShMem, MemfdShMemProvider};
+
 #[cfg(all(windows, feature = "std"))]
 pub use win32_shmem::{Win32ShMem, Win32ShMemProvider};
 
@@ -1140,6 +1143,206 @@ pub mod unix_shmem {
             }
         }
     }
+
+    /// Module containing `memfd_create` based shared memory support for Linux.
+    /// It does not depend on `SysV` limits such as `kernel.shmmax`, and maps get freed by the kernel as soon as the last holder closed them.
+    #[cfg(all(target_os = "linux", feature = "std"))]
+    pub mod memfd {
+        use alloc::string::ToString;
+        use core::{ptr, slice};
+        use libc::{
+            c_int, close, dup, fstat, ftruncate, getpid, memfd_create, mmap, munmap, open,
+            MAP_SHARED, O_RDWR, PROT_READ, PROT_WRITE,
+        };
+        use std::ffi::CString;
+
+        use crate::{
+            bolts::{
+                shmem::{ShMem, ShMemId, ShMemProvider},
+                AsMutSlice, AsSlice,
+            },
+            Error,
+        };
+
+        /// A `memfd` based shared map.
+        ///
+        /// The id is `<pid>/<fd>`, so that any process can map it via `/proc/<pid>/fd/<fd>`, as long as the creating process is alive.
+        /// Plain `<fd>` ids refer to a fd of the current process, as handed out by the [`crate::bolts::shmem::ShMemService`],
+        /// the resulting map takes ownership of this fd.
+        #[derive(Clone, Debug)]
+        pub struct MemfdShMem {
+            id: ShMemId,
+            fd: c_int,
+            map: *mut u8,
+            map_size: usize,
+        }
+
+        impl MemfdShMem {
+            /// Create a new shared memory mapping, using `memfd_create`
+            pub fn new(map_size: usize) -> Result<Self, Error> {
+                unsafe {
+                    let name = CString::new("libafl_memfd").unwrap();
+                    // No `MFD_CLOEXEC`, so spawned children inherit the fd
+                    let fd = memfd_create(name.as_ptr(), 0);
+                    if fd == -1 {
+                        return Err(Error::unknown(format!(
+                            "Failed to create a memfd: {}",
+                            std::io::Error::last_os_error()
+                        )));
+                    }
+
+                    if ftruncate(fd, map_size.try_into()?) != 0 {
+                        close(fd);
+                        return Err(Error::unknown(format!(
+                            "Failed to set the size of the memfd to {}",
+                            map_size
+                        )));
+                    }
+
+                    Self::map_fd(fd, map_size)
+                }
+            }
+
+            /// Map an existing [`MemfdShMem`], identified by its id
+            pub fn shmem_from_id_and_size(id: ShMemId, map_size: usize) -> Result<Self, Error> {
+                let id_str = id.as_str();
+                let fd = if let Some((pid, fd)) = id_str.split_once('/') {
+                    let pid: i32 = pid.parse()?;
+                    let fd: c_int = fd.parse()?;
+                    unsafe {
+                        if pid == getpid() {
+                            dup(fd)
+                        } else {
+                            let path = CString::new(format!("/proc/{}/fd/{}", pid, fd)).unwrap();
+                            open(path.as_ptr(), O_RDWR)
+                        }
+                    }
+                } else {
+                    id_str.parse()?
+                };
+                if fd == -1 {
+                    return Err(Error::unknown(format!(
+                        "Failed to open the memfd with id {}: {}",
+                        id,
+                        std::io::Error::last_os_error()
+                    )));
+                }
+
+                unsafe {
+                    let mut stat = core::mem::zeroed::<libc::stat>();
+                    #[allow(clippy::cast_sign_loss)]
+                    if fstat(fd, &mut stat) != 0 || (stat.st_size as usize) < map_size {
+                        close(fd);
+                        return Err(Error::illegal_argument(format!(
+                            "The memfd with id {} is smaller than the requested size {}",
+                            id, map_size
+                        )));
+                    }
+
+                    Self::map_fd(fd, map_size)
+                }
+            }
+
+            /// Map the memfd and take ownership of the fd
+            unsafe fn map_fd(fd: c_int, map_size: usize) -> Result<Self, Error> {
+                let map = mmap(
+                    ptr::null_mut(),
+                    map_size,
+                    PROT_READ | PROT_WRITE,
+                    MAP_SHARED,
+                    fd,
+                    0,
+                );
+                if map == libc::MAP_FAILED || map.is_null() {
+                    close(fd);
+                    return Err(Error::unknown(
+                        "Failed to map the memfd mapping".to_string(),
+                    ));
+                }
+
+                Ok(Self {
+                    id: ShMemId::from_string(&format!("{}/{}", getpid(), fd)),
+                    fd,
+                    map: map as *mut u8,
+                    map_size,
+                })
+            }
+
+            /// The file descriptor of this map, in the current process
+            #[must_use]
+            pub fn fd(&self) -> c_int {
+                self.fd
+            }
+        }
+
+        impl ShMem for MemfdShMem {
+            fn id(&self) -> ShMemId {
+                self.id
+            }
+
+            fn len(&self) -> usize {
+                self.map_size
+            }
+        }
+
+        impl AsSlice<u8> for MemfdShMem {
+            fn as_slice(&self) -> &[u8] {
+                unsafe { slice::from_raw_parts(self.map, self.map_size) }
+            }
+        }
+
+        impl AsMutSlice<u8> for MemfdShMem {
+            fn as_mut_slice(&mut self) -> &mut [u8] {
+                unsafe { slice::from_raw_parts_mut(self.map, self.map_size) }
+            }
+        }
+
+        /// [`Drop`] implementation for [`MemfdShMem`], which unmaps the map and closes the fd.
+        /// The kernel frees the memory once all processes did so.
+        impl Drop for MemfdShMem {
+            fn drop(&mut self) {
+                unsafe {
+                    munmap(self.map as *mut _, self.map_size);
+                    close(self.fd);
+                }
+            }
+        }
+
+        /// A [`ShMemProvider`] which uses `memfd_create` to provide shared memory mappings.
+        /// Maps can be shared via their `/proc/<pid>/fd` path, or wrapped in a [`crate::bolts::shmem::ServedShMemProvider`]
+        /// to pass the fds over unix domain sockets instead.
+        #[derive(Clone, Debug)]
+        pub struct MemfdShMemProvider {}
+
+        unsafe impl Send for MemfdShMemProvider {}
+
+        impl Default for MemfdShMemProvider {
+            fn default() -> Self {
+                Self::new().unwrap()
+            }
+        }
+
+        /// Implement [`ShMemProvider`] for [`MemfdShMemProvider`]
+        impl ShMemProvider for MemfdShMemProvider {
+            type ShMem = MemfdShMem;
+
+            fn new() -> Result<Self, Error> {
+                Ok(Self {})
+            }
+
+            fn new_shmem(&mut self, map_size: usize) -> Result<Self::ShMem, Error> {
+                MemfdShMem::new(map_size)
+            }
+
+            fn shmem_from_id_and_size(
+                &mut self,
+                id: ShMemId,
+                size: usize,
+            ) -> Result<Self::ShMem, Error> {
+                MemfdShMem::shmem_from_id_and_size(id, size)
+            }
+        }
+    }
 }
 
 /// Then `win32` implementation for shared memory.
@@ -1436,4 +1639,22 @@ mod tests {
         map.as_mut_slice()[0] = 1;
         assert!(map.as_slice()[0] == 1);
     }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_memfd_shmem() {
+        use crate::bolts::shmem::{MemfdShMemProvider, ShMem};
+
+        let mut provider = MemfdShMemProvider::new().unwrap();
+        let mut map = provider.new_shmem(1024).unwrap();
+        map.as_mut_slice()[0] = 1;
+
+        let mut other = provider.clone_ref(&map).unwrap();
+        assert_ne!(map.id(), other.id());
+        assert_eq!(other.as_slice()[0], 1);
+        other.as_mut_slice()[1] = 2;
+        assert_eq!(map.as_slice()[1], 2);
+
+        assert!(provider.shmem_from_id_and_size(map.id(), 4096).is_err());
+    }
 }
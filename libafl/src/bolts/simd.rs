@@ -0,0 +1,438 @@
+//! Vectorized operations on `u8` coverage maps: hitcount classification and history merging.
+//!
+//! The implementation is picked at runtime: `AVX2` (if detected) or `SSE2` on `x86_64`, `NEON` on `aarch64`,
+//! and a scalar loop everywhere else. Without the `std` feature, `AVX2` is only used if it is enabled at compile time.
+
+use alloc::vec::Vec;
+
+/// The hitcount class of each `u8` count, as in `AFL`
+pub static COUNT_CLASS_LOOKUP: [u8; 256] = [
+    0, 1, 2, 4, 8, 8, 8, 8, 16, 16, 16, 16, 16, 16, 16, 16, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32,
+    32, 32, 32, 32, 32, 32, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
+    64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
+    64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
+    64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
+    64, 64, 64, 64, 64, 64, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
+    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
+    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
+    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
+    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
+    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
+    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
+];
+
+/// The lower bounds of the hitcount classes above 2, and their class
+const CLASS_BOUNDS: [(u8, u8); 6] = [(3, 4), (4, 8), (8, 16), (16, 32), (32, 64), (128, 128)];
+
+/// The vector instructions used for the map operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    /// Plain loops
+    Scalar,
+    /// 128 bit `SSE2` vectors
+    Sse2,
+    /// 256 bit `AVX2` vectors
+    Avx2,
+    /// 128 bit `NEON` vectors
+    Neon,
+}
+
+/// The best vector instructions supported by the current CPU
+#[must_use]
+pub fn simd_level() -> SimdLevel {
+    #[cfg(target_arch = "x86_64")]
+    {
+        #[cfg(feature = "std")]
+        if std::is_x86_feature_detected!("avx2") {
+            return SimdLevel::Avx2;
+        }
+        if cfg!(target_feature = "avx2") {
+            SimdLevel::Avx2
+        } else {
+            SimdLevel::Sse2
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        SimdLevel::Neon
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        SimdLevel::Scalar
+    }
+}
+
+/// Classifies each hitcount of the `map` into its bucket, see [`COUNT_CLASS_LOOKUP`]
+pub fn classify_counts(map: &mut [u8]) {
+    classify_counts_with(simd_level(), map);
+}
+
+/// Classifies each hitcount of the `map` into its bucket, with the given vector instructions.
+///
+/// # Panics
+/// Panics if the `level` is not available on this architecture.
+pub fn classify_counts_with(level: SimdLevel, map: &mut [u8]) {
+    let done = match level {
+        SimdLevel::Scalar => 0,
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::classify_counts_sse2(map) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::classify_counts_avx2(map) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { neon::classify_counts_neon(map) },
+        #[allow(unreachable_patterns)]
+        _ => panic!("{:?} is not supported on this architecture", level),
+    };
+    classify_counts_scalar(&mut map[done..]);
+}
+
+/// Classifies each hitcount of the `map` into its bucket, one byte after the other
+pub fn classify_counts_scalar(map: &mut [u8]) {
+    for item in map {
+        *item = COUNT_CLASS_LOOKUP[*item as usize];
+    }
+}
+
+/// Merges the `map` into the `history`, keeping the maximum of each entry.
+/// The indexes of the entries that grew are appended to `novelties`, if given.
+/// Returns `true` if any entry grew.
+pub fn merge_max(history: &mut [u8], map: &[u8], novelties: Option<&mut Vec<usize>>) -> bool {
+    merge_max_with(simd_level(), history, map, novelties)
+}
+
+/// Merges the `map` into the `history`, keeping the maximum of each entry, with the given vector instructions.
+///
+/// # Panics
+/// Panics if the `level` is not available on this architecture.
+pub fn merge_max_with(
+    level: SimdLevel,
+    history: &mut [u8],
+    map: &[u8],
+    mut novelties: Option<&mut Vec<usize>>,
+) -> bool {
+    let len = history.len().min(map.len());
+    let (history, map) = (&mut history[..len], &map[..len]);
+    let (novel, done) = match level {
+        SimdLevel::Scalar => (false, 0),
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::merge_max_sse2(history, map, &mut novelties) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::merge_max_avx2(history, map, &mut novelties) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { neon::merge_max_neon(history, map, &mut novelties) },
+        #[allow(unreachable_patterns)]
+        _ => panic!("{:?} is not supported on this architecture", level),
+    };
+    let novel_tail = merge_max_scalar_from(done, history, map, &mut novelties);
+    novel || novel_tail
+}
+
+/// Merges the `map` into the `history`, keeping the maximum of each entry, one byte after the other
+pub fn merge_max_scalar(
+    history: &mut [u8],
+    map: &[u8],
+    mut novelties: Option<&mut Vec<usize>>,
+) -> bool {
+    merge_max_scalar_from(0, history, map, &mut novelties)
+}
+
+fn merge_max_scalar_from(
+    start: usize,
+    history: &mut [u8],
+    map: &[u8],
+    novelties: &mut Option<&mut Vec<usize>>,
+) -> bool {
+    let mut novel = false;
+    for i in start..history.len().min(map.len()) {
+        if map[i] > history[i] {
+            history[i] = map[i];
+            novel = true;
+            if let Some(novelties) = novelties.as_mut() {
+                novelties.push(i);
+            }
+        }
+    }
+    novel
+}
+
+/// Appends `base + i` to the `novelties` for each bit `i` set in `bits`
+#[inline]
+fn push_novelties(novelties: &mut Option<&mut Vec<usize>>, base: usize, mut bits: u32) {
+    if let Some(novelties) = novelties.as_mut() {
+        while bits != 0 {
+            novelties.push(base + bits.trailing_zeros() as usize);
+            bits &= bits - 1;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[allow(
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss,
+    clippy::cast_ptr_alignment
+)]
+mod x86 {
+    use alloc::vec::Vec;
+    use core::arch::x86_64::{
+        __m128i, __m256i, _mm256_and_si256, _mm256_andnot_si256, _mm256_cmpeq_epi8,
+        _mm256_loadu_si256, _mm256_max_epu8, _mm256_movemask_epi8, _mm256_or_si256,
+        _mm256_set1_epi8, _mm256_setzero_si256, _mm256_storeu_si256, _mm_and_si128,
+        _mm_andnot_si128, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_max_epu8, _mm_movemask_epi8,
+        _mm_or_si128, _mm_set1_epi8, _mm_setzero_si128, _mm_storeu_si128,
+    };
+
+    use super::{push_novelties, CLASS_BOUNDS};
+
+    const SSE2_LANES: usize = 16;
+    const SSE2_ALL: i32 = 0xffff;
+    const AVX2_LANES: usize = 32;
+    const AVX2_ALL: i32 = -1;
+
+    /// Returns the number of classified bytes, the tail is left to the caller
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn classify_counts_sse2(map: &mut [u8]) -> usize {
+        let zero = _mm_setzero_si128();
+        let steps = map.len() / SSE2_LANES;
+        for step in 0..steps {
+            let ptr = map.as_mut_ptr().add(step * SSE2_LANES) as *mut __m128i;
+            let items = _mm_loadu_si128(ptr);
+            if _mm_movemask_epi8(_mm_cmpeq_epi8(items, zero)) == SSE2_ALL {
+                continue;
+            }
+            let mut classes = items;
+            for (bound, class) in CLASS_BOUNDS {
+                // items >= bound
+                let ge = _mm_cmpeq_epi8(_mm_max_epu8(items, _mm_set1_epi8(bound as i8)), items);
+                classes = _mm_or_si128(
+                    _mm_and_si128(ge, _mm_set1_epi8(class as i8)),
+                    _mm_andnot_si128(ge, classes),
+                );
+            }
+            _mm_storeu_si128(ptr, classes);
+        }
+        steps * SSE2_LANES
+    }
+
+    /// Returns the number of classified bytes, the tail is left to the caller
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn classify_counts_avx2(map: &mut [u8]) -> usize {
+        let zero = _mm256_setzero_si256();
+        let steps = map.len() / AVX2_LANES;
+        for step in 0..steps {
+            let ptr = map.as_mut_ptr().add(step * AVX2_LANES) as *mut __m256i;
+            let items = _mm256_loadu_si256(ptr);
+            if _mm256_movemask_epi8(_mm256_cmpeq_epi8(items, zero)) == AVX2_ALL {
+                continue;
+            }
+            let mut classes = items;
+            for (bound, class) in CLASS_BOUNDS {
+                let ge =
+                    _mm256_cmpeq_epi8(_mm256_max_epu8(items, _mm256_set1_epi8(bound as i8)), items);
+                classes = _mm256_or_si256(
+                    _mm256_and_si256(ge, _mm256_set1_epi8(class as i8)),
+                    _mm256_andnot_si256(ge, classes),
+                );
+            }
+            _mm256_storeu_si256(ptr, classes);
+        }
+        steps * AVX2_LANES
+    }
+
+    /// Returns if a novel entry was found, and the number of merged bytes
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn merge_max_sse2(
+        history: &mut [u8],
+        map: &[u8],
+        novelties: &mut Option<&mut Vec<usize>>,
+    ) -> (bool, usize) {
+        let mut novel = false;
+        let steps = history.len() / SSE2_LANES;
+        for step in 0..steps {
+            let i = step * SSE2_LANES;
+            let ptr = history.as_mut_ptr().add(i) as *mut __m128i;
+            let h = _mm_loadu_si128(ptr);
+            let max = _mm_max_epu8(h, _mm_loadu_si128(map.as_ptr().add(i) as *const __m128i));
+            let bits = !_mm_movemask_epi8(_mm_cmpeq_epi8(max, h)) & SSE2_ALL;
+            if bits != 0 {
+                novel = true;
+                _mm_storeu_si128(ptr, max);
+                push_novelties(novelties, i, bits as u32);
+            }
+        }
+        (novel, steps * SSE2_LANES)
+    }
+
+    /// Returns if a novel entry was found, and the number of merged bytes
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn merge_max_avx2(
+        history: &mut [u8],
+        map: &[u8],
+        novelties: &mut Option<&mut Vec<usize>>,
+    ) -> (bool, usize) {
+        let mut novel = false;
+        let steps = history.len() / AVX2_LANES;
+        for step in 0..steps {
+            let i = step * AVX2_LANES;
+            let ptr = history.as_mut_ptr().add(i) as *mut __m256i;
+            let h = _mm256_loadu_si256(ptr);
+            let max = _mm256_max_epu8(h, _mm256_loadu_si256(map.as_ptr().add(i) as *const __m256i));
+            let bits = !_mm256_movemask_epi8(_mm256_cmpeq_epi8(max, h));
+            if bits != 0 {
+                novel = true;
+                _mm256_storeu_si256(ptr, max);
+                push_novelties(novelties, i, bits as u32);
+            }
+        }
+        (novel, steps * AVX2_LANES)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use alloc::vec::Vec;
+    use core::arch::aarch64::{
+        vbslq_u8, vceqq_u8, vcgeq_u8, vdupq_n_u8, vld1q_u8, vmaxq_u8, vmaxvq_u8, vminvq_u8,
+        vmvnq_u8, vst1q_u8,
+    };
+
+    use super::CLASS_BOUNDS;
+
+    const LANES: usize = 16;
+
+    /// Returns the number of classified bytes, the tail is left to the caller
+    #[target_feature(enable = "neon")]
+    pub unsafe fn classify_counts_neon(map: &mut [u8]) -> usize {
+        let steps = map.len() / LANES;
+        for step in 0..steps {
+            let ptr = map.as_mut_ptr().add(step * LANES);
+            let items = vld1q_u8(ptr);
+            if vmaxvq_u8(items) == 0 {
+                continue;
+            }
+            let mut classes = items;
+            for (bound, class) in CLASS_BOUNDS {
+                let ge = vcgeq_u8(items, vdupq_n_u8(bound));
+                classes = vbslq_u8(ge, vdupq_n_u8(class), classes);
+            }
+            vst1q_u8(ptr, classes);
+        }
+        steps * LANES
+    }
+
+    /// Returns if a novel entry was found, and the number of merged bytes
+    #[target_feature(enable = "neon")]
+    pub unsafe fn merge_max_neon(
+        history: &mut [u8],
+        map: &[u8],
+        novelties: &mut Option<&mut Vec<usize>>,
+    ) -> (bool, usize) {
+        let mut novel = false;
+        let steps = history.len() / LANES;
+        for step in 0..steps {
+            let i = step * LANES;
+            let ptr = history.as_mut_ptr().add(i);
+            let h = vld1q_u8(ptr);
+            let m = vld1q_u8(map.as_ptr().add(i));
+            let max = vmaxq_u8(h, m);
+            if vmaxvq_u8(vmvnq_u8(vceqq_u8(max, h))) != 0 {
+                novel = true;
+                if let Some(novelties) = novelties.as_mut() {
+                    for j in i..(i + LANES) {
+                        if *map.get_unchecked(j) > *history.get_unchecked(j) {
+                            novelties.push(j);
+                        }
+                    }
+                }
+                vst1q_u8(ptr, max);
+            }
+        }
+        (novel, steps * LANES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::bolts::{
+        rands::{Rand, StdRand},
+        simd::{
+            classify_counts_scalar, classify_counts_with, merge_max_scalar, merge_max_with,
+            simd_level, SimdLevel,
+        },
+    };
+
+    fn random_map(rand: &mut StdRand, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                if rand.below(4) == 0 {
+                    rand.below(256) as u8
+                } else {
+                    0
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let level = simd_level();
+        let mut rand = StdRand::with_seed(1337);
+        for len in [0, 1, 15, 16, 33, 100, 1024] {
+            let map = random_map(&mut rand, len);
+
+            let mut expected = map.clone();
+            classify_counts_scalar(&mut expected);
+            let mut classified = map.clone();
+            classify_counts_with(level, &mut classified);
+            assert_eq!(classified, expected);
+
+            let history = random_map(&mut rand, len);
+
+            let mut expected_history = history.clone();
+            let mut expected_novelties = vec![];
+            let expected_novel =
+                merge_max_scalar(&mut expected_history, &map, Some(&mut expected_novelties));
+            let mut merged_history = history.clone();
+            let mut novelties = vec![];
+            let novel = merge_max_with(level, &mut merged_history, &map, Some(&mut novelties));
+            assert_eq!(novel, expected_novel);
+            assert_eq!(merged_history, expected_history);
+            assert_eq!(novelties, expected_novelties);
+        }
+
+        let mut scalar = vec![3, 200, 0, 17];
+        classify_counts_with(SimdLevel::Scalar, &mut scalar);
+        assert_eq!(scalar, vec![4, 128, 0, 32]);
+    }
+
+    #[test]
+    fn test_classify_counts_afl_buckets() {
+        // The first count of each AFL bucket, and its class
+        let buckets = [
+            (0, 0),
+            (1, 1),
+            (2, 2),
+            (3, 4),
+            (4, 8),
+            (8, 16),
+            (16, 32),
+            (32, 64),
+            (128, 128),
+        ];
+        let mut map: Vec<u8> = (0..=255).collect();
+        classify_counts_scalar(&mut map);
+        for (count, class) in map.iter().enumerate() {
+            let expected = buckets
+                .iter()
+                .rev()
+                .find(|(start, _)| count >= *start)
+                .unwrap()
+                .1;
+            assert_eq!(*class, expected, "wrong class for count {}", count);
+        }
+        assert_eq!(map[127], 64);
+        assert_eq!(map[128], 128);
+    }
+}
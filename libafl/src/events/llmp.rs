@@ -6,13 +6,16 @@ use crate::bolts::core_affinity::CoreId;
 use crate::bolts::os::startable_self;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 use crate::bolts::os::{fork, ForkResult};
+#[cfg(feature = "std")]
+use crate::bolts::{
+    checkpoint::StateCheckpoint, llmp::LlmpConnection, shmem::StdShMemProvider,
+    staterestore::StateRestorer,
+};
 #[cfg(feature = "llmp_compression")]
 use crate::bolts::{
     compress::GzipCompressor,
     llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
 };
-#[cfg(feature = "std")]
-use crate::bolts::{llmp::LlmpConnection, shmem::StdShMemProvider, staterestore::StateRestorer};
 use crate::{
     bolts::{
         llmp::{self, Flags, LlmpClient, LlmpClientDescription, Tag},
@@ -212,6 +215,10 @@ where
                 // Correctly handled the event
                 Ok(BrokerEventResult::Handled)
             }
+            Event::UpdateTestcaseInfo { info, phantom: _ } => {
+                monitor.display_testcase(info, client_id);
+                Ok(BrokerEventResult::Handled)
+            }
             Event::Objective { objective_size } => {
                 let client = monitor.client_stats_mut_for(client_id);
                 client.update_objective_size(*objective_size as u64);
@@ -223,10 +230,7 @@ where
                 message,
                 phantom: _,
             } => {
-                let (_, _) = (severity_level, message);
-                // TODO rely on Monitor
-                #[cfg(feature = "std")]
-                println!("[LOG {}]: {}", severity_level, message);
+                monitor.display_log(*severity_level, message, client_id);
                 Ok(BrokerEventResult::Handled)
             } //_ => Ok(BrokerEventResult::Forward),
         }
@@ -679,6 +683,7 @@ pub enum ManagerKind {
 /// Sets up a restarting fuzzer, using the [`StdShMemProvider`], and standard features.
 /// The restarting mgr is a combination of restarter and runner, that can be used on systems with and without `fork` support.
 /// The restarter will spawn a new process each time the child crashes or timeouts.
+/// To resume from an on-disk [`StateCheckpoint`], use [`setup_restarting_mgr_std_with_checkpoint`].
 #[cfg(feature = "std")]
 #[allow(clippy::type_complexity)]
 pub fn setup_restarting_mgr_std<I, MT, OT, S>(
@@ -692,6 +697,31 @@ pub fn setup_restarting_mgr_std<I, MT, OT, S>(
     ),
     Error,
 >
+where
+    I: Input,
+    MT: Monitor + Clone,
+    OT: ObserversTuple<I, S> + DeserializeOwned,
+    S: DeserializeOwned,
+{
+    setup_restarting_mgr_std_with_checkpoint(monitor, broker_port, configuration, None)
+}
+
+/// Sets up a restarting fuzzer like [`setup_restarting_mgr_std`].
+/// If there is no state from a previous client to restore, the first client resumes from the `checkpoint`, if any.
+#[cfg(feature = "std")]
+#[allow(clippy::type_complexity)]
+pub fn setup_restarting_mgr_std_with_checkpoint<I, MT, OT, S>(
+    monitor: MT,
+    broker_port: u16,
+    configuration: EventConfig,
+    checkpoint: Option<StateCheckpoint>,
+) -> Result<
+    (
+        Option<S>,
+        LlmpRestartingEventManager<I, OT, S, StdShMemProvider>,
+    ),
+    Error,
+>
 where
     I: Input,
     MT: Monitor + Clone,
@@ -703,6 +733,7 @@ where
         .monitor(Some(monitor))
         .broker_port(broker_port)
         .configuration(configuration)
+        .checkpoint(checkpoint)
         .build()
         .launch()
 }
@@ -739,6 +770,9 @@ where
     /// The type of manager to build
     #[builder(default = ManagerKind::Any)]
     kind: ManagerKind,
+    /// The on-disk checkpoint to resume from, if there is no state from a previous client to restore
+    #[builder(default = None)]
+    checkpoint: Option<StateCheckpoint>,
     #[builder(setter(skip), default = PhantomData)]
     phantom_data: PhantomData<(I, OT, S)>,
 }
@@ -910,7 +944,20 @@ where
                 ),
             )
         } else {
-            println!("First run. Let's set it all up");
+            // Resume an interrupted campaign from disk, if possible
+            let state = match &self.checkpoint {
+                Some(checkpoint) => {
+                    let state = checkpoint.load()?;
+                    if state.is_some() {
+                        println!("Resuming from checkpoint {:?}", checkpoint.path());
+                    }
+                    state
+                }
+                None => None,
+            };
+            if state.is_none() {
+                println!("First run. Let's set it all up");
+            }
             // Mgr to send and receive msgs from/to all other fuzzer instances
             let mgr = LlmpEventManager::<I, OT, S, SP>::existing_client_from_env(
                 new_shmem_provider,
@@ -918,7 +965,7 @@ where
                 self.configuration,
             )?;
 
-            (None, LlmpRestartingEventManager::new(mgr, staterestorer))
+            (state, LlmpRestartingEventManager::new(mgr, staterestorer))
         };
         // We reset the staterestorer, the next staterestorer and receiver (after crash) will reuse the page from the initial message.
         mgr.staterestorer.reset();
@@ -20,7 +20,7 @@ use crate::{
     bolts::current_time,
     executors::ExitKind,
     inputs::Input,
-    monitors::UserStats,
+    monitors::{TestcaseInfo, UserStats},
     observers::ObserversTuple,
     state::{HasClientPerfMonitor, HasExecutions},
     Error,
@@ -36,7 +36,6 @@ pub struct EventManagerId {
 
 #[cfg(feature = "introspection")]
 use crate::monitors::ClientPerfMonitor;
-#[cfg(feature = "introspection")]
 use alloc::boxed::Box;
 
 /// The log event severity
@@ -217,6 +216,13 @@ where
         /// phantomm data
         phantom: PhantomData<I>,
     },
+    /// Information about a corpus entry or a solution of this client, for monitors that show the corpus
+    UpdateTestcaseInfo {
+        /// The information about the testcase
+        info: Box<TestcaseInfo>,
+        /// [`PhantomData`]
+        phantom: PhantomData<I>,
+    },
     /// A new objective was found
     Objective {
         /// Objective corpus size
@@ -270,6 +276,10 @@ where
                 introspection_monitor: _,
                 phantom: _,
             } => "PerfMonitor",
+            Event::UpdateTestcaseInfo {
+                info: _,
+                phantom: _,
+            } => "TestcaseInfo",
             Event::Objective { objective_size: _ } => "Objective",
             Event::Log {
                 severity_level: _,
@@ -185,6 +185,10 @@ where
                 monitor.display(event.name().to_string(), 0);
                 Ok(BrokerEventResult::Handled)
             }
+            Event::UpdateTestcaseInfo { info, phantom: _ } => {
+                monitor.display_testcase(info, 0);
+                Ok(BrokerEventResult::Handled)
+            }
             Event::Objective { objective_size } => {
                 monitor
                     .client_stats_mut_for(0)
@@ -197,9 +201,7 @@ where
                 message,
                 phantom: _,
             } => {
-                let (_, _) = (message, severity_level);
-                #[cfg(feature = "std")]
-                println!("[LOG {}]: {}", severity_level, message);
+                monitor.display_log(*severity_level, message, 0);
                 Ok(BrokerEventResult::Handled)
             } //_ => Ok(BrokerEventResult::Forward),
         }
@@ -24,7 +24,9 @@ use crate::{
         AsSlice,
     },
     inputs::HasTargetBytes,
-    observers::{ASANBacktraceObserver, ObserversTuple, StdErrObserver, StdOutObserver},
+    observers::{
+        ASANBacktraceObserver, ExitStatusObserver, ObserversTuple, StdErrObserver, StdOutObserver,
+    },
 };
 #[cfg(feature = "std")]
 use crate::{inputs::Input, Error};
@@ -154,12 +156,14 @@ where
     observers: OT,
     /// cache if the AsanBacktraceObserver is present
     has_asan_observer: bool,
-    /// If set, we found a [`StdErrObserver`] in the observer list.
-    /// Pipe the child's `stderr` instead of closing it.
-    has_stdout_observer: bool,
     /// If set, we found a [`StdOutObserver`] in the observer list
     /// Pipe the child's `stdout` instead of closing it.
+    has_stdout_observer: bool,
+    /// If set, we found a [`StdErrObserver`] in the observer list.
+    /// Pipe the child's `stderr` instead of closing it.
     has_stderr_observer: bool,
+    /// If set, we found an [`ExitStatusObserver`] in the observer list
+    has_exit_status_observer: bool,
     phantom: PhantomData<(EM, I, S, Z)>,
 }
 
@@ -232,7 +236,7 @@ where
             .match_name::<StdOutObserver>("StdOutObserver")
             .is_some();
         if has_stdout_observer {
-            command.stderr(Stdio::piped());
+            command.stdout(Stdio::piped());
         }
 
         let has_stderr_observer = observers
@@ -245,6 +249,10 @@ where
             command.stderr(Stdio::piped());
         }
 
+        let has_exit_status_observer = observers
+            .match_name::<ExitStatusObserver>("ExitStatusObserver")
+            .is_some();
+
         Ok(Self {
             observers,
             has_asan_observer,
@@ -257,6 +265,7 @@ where
             },
             has_stdout_observer,
             has_stderr_observer,
+            has_exit_status_observer,
             phantom: PhantomData,
         })
     }
@@ -324,11 +333,20 @@ where
 
         let mut child = self.configurer.spawn_child(input)?;
 
-        let res = match child
+        let status = child
             .wait_timeout(Duration::from_secs(5))
-            .expect("waiting on child failed")
-            .map(|status| status.signal())
-        {
+            .expect("waiting on child failed");
+
+        if self.has_exit_status_observer {
+            let observer = self
+                .observers
+                .match_name_mut::<ExitStatusObserver>("ExitStatusObserver")
+                .unwrap();
+            observer.code = status.and_then(|status| status.code());
+            observer.signal = status.and_then(|status| status.signal());
+        }
+
+        let res = match status.map(|status| status.signal()) {
             // for reference: https://www.man7.org/linux/man-pages/man7/signal.7.html
             Some(Some(9)) => Ok(ExitKind::Oom),
             Some(Some(_)) => Ok(ExitKind::Crash),
@@ -648,11 +666,16 @@ pub trait CommandConfigurator: Sized + Debug {
             .match_name::<StdErrObserver>("StdErrObserver")
             .is_some();
 
+        let has_exit_status_observer = observers
+            .match_name::<ExitStatusObserver>("ExitStatusObserver")
+            .is_some();
+
         CommandExecutor {
             observers,
             has_asan_observer,
             has_stdout_observer,
             has_stderr_observer,
+            has_exit_status_observer,
             configurer: self,
             phantom: PhantomData,
         }
@@ -3,37 +3,54 @@
 //! In comparison to the [`crate::executors::CombinedExecutor`] it also runs the secondary executor in `run_target`.
 //!
 use crate::{
+    bolts::{ownedref::OwnedPtrMut, tuples::MatchName},
     executors::{Executor, ExitKind, HasObservers},
     inputs::Input,
     observers::ObserversTuple,
     Error,
 };
-use core::fmt::Debug;
+use core::{fmt::Debug, ptr};
+use serde::{Deserialize, Serialize};
 
-/// A [`DiffExecutor`] wraps a primary executor, forwarding its methods, and a secondary one
+/// A [`DiffExecutor`] wraps a primary executor, forwarding its methods, and a secondary one.
+/// Its observers are the observers of both executors, see [`ProxyObserversTuple`].
 #[derive(Debug)]
-pub struct DiffExecutor<A, B>
+pub struct DiffExecutor<A, B, OTA, OTB>
 where
     A: Debug,
     B: Debug,
+    OTA: Debug,
+    OTB: Debug,
 {
     primary: A,
     secondary: B,
+    observers: ProxyObserversTuple<OTA, OTB>,
 }
 
-impl<A, B> DiffExecutor<A, B>
+impl<A, B, OTA, OTB> DiffExecutor<A, B, OTA, OTB>
 where
     A: Debug,
     B: Debug,
+    OTA: Debug,
+    OTB: Debug,
 {
     /// Create a new `DiffExecutor`, wrapping the given `executor`s.
     pub fn new<EM, I, S, Z>(primary: A, secondary: B) -> Self
     where
-        A: Executor<EM, I, S, Z>,
-        B: Executor<EM, I, S, Z>,
+        A: Executor<EM, I, S, Z> + HasObservers<I, OTA, S>,
+        B: Executor<EM, I, S, Z> + HasObservers<I, OTB, S>,
+        OTA: ObserversTuple<I, S>,
+        OTB: ObserversTuple<I, S>,
         I: Input,
     {
-        Self { primary, secondary }
+        Self {
+            primary,
+            secondary,
+            observers: ProxyObserversTuple {
+                primary: OwnedPtrMut::Ptr(ptr::null_mut()),
+                secondary: OwnedPtrMut::Ptr(ptr::null_mut()),
+            },
+        }
     }
 
     /// Retrieve the primary `Executor` that is wrapped by this `DiffExecutor`.
@@ -47,10 +64,12 @@ where
     }
 }
 
-impl<A, B, EM, I, S, Z> Executor<EM, I, S, Z> for DiffExecutor<A, B>
+impl<A, B, OTA, OTB, EM, I, S, Z> Executor<EM, I, S, Z> for DiffExecutor<A, B, OTA, OTB>
 where
-    A: Executor<EM, I, S, Z>,
-    B: Executor<EM, I, S, Z>,
+    A: Executor<EM, I, S, Z> + HasObservers<I, OTA, S>,
+    B: Executor<EM, I, S, Z> + HasObservers<I, OTB, S>,
+    OTA: ObserversTuple<I, S>,
+    OTB: ObserversTuple<I, S>,
     I: Input,
 {
     fn run_target(
@@ -64,6 +83,9 @@ where
         self.primary.post_run_reset();
         let ret2 = self.secondary.run_target(fuzzer, state, mgr, input)?;
         self.secondary.post_run_reset();
+        // The executors may have moved since the last run, point to their current observers
+        self.observers
+            .set(self.primary.observers_mut(), self.secondary.observers_mut());
         if ret1 == ret2 {
             Ok(ret1)
         } else {
@@ -76,19 +98,255 @@ where
     }
 }
 
-impl<A, B, I, OT, S> HasObservers<I, OT, S> for DiffExecutor<A, B>
+/// The observers of the primary and of the secondary executor of a [`DiffExecutor`], seen as one tuple,
+/// so that a [`crate::feedbacks::DiffFeedback`] can compare an observer of each side.
+/// The observer names are first looked up in the primary observers.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(
+    bound = "A: serde::Serialize + serde::de::DeserializeOwned, B: serde::Serialize + serde::de::DeserializeOwned"
+)]
+pub struct ProxyObserversTuple<A, B> {
+    primary: OwnedPtrMut<A>,
+    secondary: OwnedPtrMut<B>,
+}
+
+impl<A, B> ProxyObserversTuple<A, B> {
+    fn set(&mut self, primary: &mut A, secondary: &mut B) {
+        self.primary = OwnedPtrMut::Ptr(primary as *mut A);
+        self.secondary = OwnedPtrMut::Ptr(secondary as *mut B);
+    }
+
+    /// The observers of the primary executor
+    #[must_use]
+    pub fn primary(&self) -> &A {
+        self.primary.as_ref()
+    }
+
+    /// The observers of the secondary executor
+    #[must_use]
+    pub fn secondary(&self) -> &B {
+        self.secondary.as_ref()
+    }
+}
+
+impl<A, B> MatchName for ProxyObserversTuple<A, B>
 where
-    A: HasObservers<I, OT, S>,
-    B: Debug,
-    OT: ObserversTuple<I, S>,
+    A: MatchName,
+    B: MatchName,
+{
+    fn match_name<T>(&self, name: &str) -> Option<&T> {
+        self.primary
+            .as_ref()
+            .match_name::<T>(name)
+            .or_else(|| self.secondary.as_ref().match_name::<T>(name))
+    }
+
+    fn match_name_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        match self.primary.as_mut().match_name_mut::<T>(name) {
+            Some(observer) => Some(observer),
+            None => self.secondary.as_mut().match_name_mut::<T>(name),
+        }
+    }
+}
+
+impl<A, B, I, S> ObserversTuple<I, S> for ProxyObserversTuple<A, B>
+where
+    A: ObserversTuple<I, S>,
+    B: ObserversTuple<I, S>,
+{
+    fn pre_exec_all(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.primary.as_mut().pre_exec_all(state, input)?;
+        self.secondary.as_mut().pre_exec_all(state, input)
+    }
+
+    fn post_exec_all(
+        &mut self,
+        state: &mut S,
+        input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.primary
+            .as_mut()
+            .post_exec_all(state, input, exit_kind)?;
+        self.secondary
+            .as_mut()
+            .post_exec_all(state, input, exit_kind)
+    }
+
+    fn pre_exec_child_all(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.primary.as_mut().pre_exec_child_all(state, input)?;
+        self.secondary.as_mut().pre_exec_child_all(state, input)
+    }
+
+    fn post_exec_child_all(
+        &mut self,
+        state: &mut S,
+        input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.primary
+            .as_mut()
+            .post_exec_child_all(state, input, exit_kind)?;
+        self.secondary
+            .as_mut()
+            .post_exec_child_all(state, input, exit_kind)
+    }
+}
+
+impl<A, B, I, OTA, OTB, S> HasObservers<I, ProxyObserversTuple<OTA, OTB>, S>
+    for DiffExecutor<A, B, OTA, OTB>
+where
+    A: HasObservers<I, OTA, S>,
+    B: HasObservers<I, OTB, S>,
+    OTA: ObserversTuple<I, S>,
+    OTB: ObserversTuple<I, S>,
 {
+    /// The observers of the wrapped executors, as of the last call to `run_target` or `observers_mut`
     #[inline]
-    fn observers(&self) -> &OT {
-        self.primary.observers()
+    fn observers(&self) -> &ProxyObserversTuple<OTA, OTB> {
+        &self.observers
     }
 
     #[inline]
-    fn observers_mut(&mut self) -> &mut OT {
-        self.primary.observers_mut()
+    fn observers_mut(&mut self) -> &mut ProxyObserversTuple<OTA, OTB> {
+        self.observers
+            .set(self.primary.observers_mut(), self.secondary.observers_mut());
+        &mut self.observers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        bolts::{
+            rands::StdRand,
+            tuples::{tuple_list, tuple_list_type, Named},
+        },
+        corpus::{Corpus, InMemoryCorpus},
+        events::NopEventManager,
+        executors::{DiffExecutor, Executor, ExitKind, HasObservers},
+        feedbacks::{differential::DiffResult, DiffFeedback},
+        fuzzer::{Evaluator, StdFuzzer},
+        inputs::{BytesInput, HasBytesVec},
+        observers::Observer,
+        schedulers::QueueScheduler,
+        state::{HasSolutions, StdState},
+        Error,
+    };
+
+    /// Stores the output of the target for the first byte of the input
+    #[derive(Serialize, Deserialize, Debug)]
+    struct ByteObserver {
+        name: String,
+        value: u8,
+    }
+
+    impl<I, S> Observer<I, S> for ByteObserver {}
+
+    impl Named for ByteObserver {
+        fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    #[derive(Debug)]
+    struct ByteExecutor {
+        observers: tuple_list_type!(ByteObserver),
+        target: fn(u8) -> u8,
+    }
+
+    impl ByteExecutor {
+        fn new(name: &str, target: fn(u8) -> u8) -> Self {
+            Self {
+                observers: tuple_list!(ByteObserver {
+                    name: name.to_string(),
+                    value: 0,
+                }),
+                target,
+            }
+        }
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for ByteExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            self.observers.0.value = (self.target)(input.bytes()[0]);
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl<S> HasObservers<BytesInput, tuple_list_type!(ByteObserver), S> for ByteExecutor {
+        fn observers(&self) -> &tuple_list_type!(ByteObserver) {
+            &self.observers
+        }
+
+        fn observers_mut(&mut self) -> &mut tuple_list_type!(ByteObserver) {
+            &mut self.observers
+        }
+    }
+
+    #[test]
+    fn test_diff_executor() {
+        let primary = ByteExecutor::new("primary", |b| b);
+        // Differs from the primary target for the inputs starting with 0xff
+        let secondary = ByteExecutor::new("secondary", |b| if b == 0xff { 0 } else { b });
+
+        let mut objective = DiffFeedback::new(
+            "diff",
+            &primary.observers.0,
+            &secondary.observers.0,
+            |o1: &ByteObserver, o2: &ByteObserver| {
+                if o1.value == o2.value {
+                    DiffResult::Equal
+                } else {
+                    DiffResult::Diff
+                }
+            },
+        )
+        .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), objective);
+        let mut mgr = NopEventManager {};
+        // The byte executors run with any state and fuzzer
+        let mut executor =
+            DiffExecutor::new::<NopEventManager, BytesInput, (), ()>(primary, secondary);
+
+        let input = BytesInput::new(vec![0x41]);
+        let exit_kind = fuzzer
+            .execute_input(&mut state, &mut executor, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(executor.primary().observers.0.value, 0x41);
+        assert_eq!(executor.secondary().observers.0.value, 0x41);
+
+        fuzzer
+            .evaluate_input(&mut state, &mut executor, &mut mgr, input)
+            .unwrap();
+        assert_eq!(state.solutions().count(), 0);
+
+        fuzzer
+            .evaluate_input(
+                &mut state,
+                &mut executor,
+                &mut mgr,
+                BytesInput::new(vec![0xff]),
+            )
+            .unwrap();
+        assert_eq!(state.solutions().count(), 1);
     }
 }
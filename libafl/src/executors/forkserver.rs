@@ -20,7 +20,7 @@ use crate::{
         shmem::{ShMem, ShMemProvider, StdShMemProvider},
         AsMutSlice, AsSlice,
     },
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
     inputs::{HasTargetBytes, Input},
     mutators::Tokens,
     observers::{get_asan_runtime_flags_with_log_path, ASANBacktraceObserver, ObserversTuple},
@@ -376,6 +376,18 @@ impl<E: Debug> TimeoutForkserverExecutor<E> {
     }
 }
 
+impl<E: Debug> HasTimeout for TimeoutForkserverExecutor<E> {
+    #[allow(clippy::cast_sign_loss)]
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout.num_milliseconds() as u64)
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = TimeSpec::milliseconds(timeout.as_millis() as i64);
+    }
+}
+
 impl<E: Debug, EM, I, S, Z> Executor<EM, I, S, Z> for TimeoutForkserverExecutor<E>
 where
     I: Input + HasTargetBytes,
@@ -0,0 +1,141 @@
+//! A `HangConfirmExecutor` re-runs inputs that timed out with a larger timeout, to confirm they really hang
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::Input,
+    observers::ObserversTuple,
+    Error,
+};
+
+/// A [`HangConfirmExecutor`] wraps an executor with a timeout (see [`HasTimeout`]).
+/// Whenever a run hits the (possibly calibrated) timeout of the wrapped executor,
+/// the input is executed once more using the larger `hang_timeout`.
+/// Only if this second run times out as well, [`ExitKind::Timeout`] is reported,
+/// otherwise the result of the second run is returned.
+///
+/// This only works for executors that return [`ExitKind::Timeout`] to the caller,
+/// such as the `TimeoutForkserverExecutor` or an `InProcessForkExecutor` wrapped in a `TimeoutExecutor`.
+/// A plain [`crate::executors::InProcessExecutor`] handles timeouts in its signal handler and never returns.
+pub struct HangConfirmExecutor<E, OT> {
+    /// The wrapped executor
+    executor: E,
+    /// The timeout used to confirm a hang
+    hang_timeout: Duration,
+    /// phantom data
+    phantom: PhantomData<OT>,
+}
+
+impl<E: Debug, OT> Debug for HangConfirmExecutor<E, OT> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HangConfirmExecutor")
+            .field("executor", &self.executor)
+            .field("hang_timeout", &self.hang_timeout)
+            .finish()
+    }
+}
+
+impl<E, OT> HangConfirmExecutor<E, OT>
+where
+    E: HasTimeout,
+{
+    /// Create a new [`HangConfirmExecutor`], wrapping the given `executor`.
+    /// Inputs timing out are re-run with `hang_timeout` before they are reported as timeouts.
+    pub fn new(executor: E, hang_timeout: Duration) -> Self {
+        Self {
+            executor,
+            hang_timeout,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The timeout used to confirm a hang
+    #[inline]
+    #[must_use]
+    pub fn hang_timeout(&self) -> Duration {
+        self.hang_timeout
+    }
+
+    /// Set the timeout used to confirm a hang
+    #[inline]
+    pub fn set_hang_timeout(&mut self, hang_timeout: Duration) {
+        self.hang_timeout = hang_timeout;
+    }
+
+    /// Retrieve the inner `Executor` that is wrapped by this `HangConfirmExecutor`.
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.executor
+    }
+}
+
+impl<E, EM, I, OT, S, Z> Executor<EM, I, S, Z> for HangConfirmExecutor<E, OT>
+where
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S> + HasTimeout,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        let exit_kind = self.executor.run_target(fuzzer, state, mgr, input)?;
+
+        let timeout = self.executor.timeout();
+        if exit_kind != ExitKind::Timeout || self.hang_timeout <= timeout {
+            return Ok(exit_kind);
+        }
+
+        // Reset the observers, so that they only contain the results of the confirmation run
+        self.executor.observers_mut().pre_exec_all(state, input)?;
+
+        self.executor.set_timeout(self.hang_timeout);
+        let ret = self.executor.run_target(fuzzer, state, mgr, input);
+        self.executor.set_timeout(timeout);
+
+        ret
+    }
+
+    #[inline]
+    fn post_run_reset(&mut self) {
+        self.executor.post_run_reset();
+    }
+}
+
+impl<E, OT> HasTimeout for HangConfirmExecutor<E, OT>
+where
+    E: HasTimeout,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.executor.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executor.set_timeout(timeout);
+    }
+}
+
+impl<E, I, OT, S> HasObservers<I, OT, S> for HangConfirmExecutor<E, OT>
+where
+    E: HasObservers<I, OT, S>,
+    OT: ObserversTuple<I, S>,
+{
+    #[inline]
+    fn observers(&self) -> &OT {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut OT {
+        self.executor.observers_mut()
+    }
+}
@@ -6,7 +6,7 @@ pub use inprocess::InProcessExecutor;
 pub use inprocess::InProcessForkExecutor;
 
 pub mod differential;
-pub use differential::DiffExecutor;
+pub use differential::{DiffExecutor, ProxyObserversTuple};
 
 /// Timeout executor.
 /// Not possible on `no-std` Windows or `no-std`, but works for unix
@@ -23,6 +23,9 @@ pub use forkserver::{Forkserver, ForkserverExecutor, TimeoutForkserverExecutor};
 pub mod combined;
 pub use combined::CombinedExecutor;
 
+pub mod hang;
+pub use hang::HangConfirmExecutor;
+
 pub mod shadow;
 pub use shadow::ShadowExecutor;
 
@@ -41,7 +44,7 @@ use crate::{
     Error,
 };
 
-use core::fmt::Debug;
+use core::{fmt::Debug, time::Duration};
 use serde::{Deserialize, Serialize};
 
 /// How an execution finished.
@@ -111,6 +114,15 @@ where
     fn observers_mut(&mut self) -> &mut OT;
 }
 
+/// An executor that enforces a timeout on each run of the target, which can be changed at runtime.
+pub trait HasTimeout {
+    /// The timeout currently applied to each run
+    fn timeout(&self) -> Duration;
+
+    /// Set the timeout for all following runs
+    fn set_timeout(&mut self, timeout: Duration);
+}
+
 /// An executor takes the given inputs, and runs the harness/target.
 pub trait Executor<EM, I, S, Z>: Debug
 where
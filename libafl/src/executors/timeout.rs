@@ -7,7 +7,7 @@ use core::{
 };
 
 use crate::{
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
     inputs::Input,
     observers::ObserversTuple,
     Error,
@@ -185,6 +185,22 @@ impl<E> TimeoutExecutor<E> {
     }
 }
 
+#[cfg(target_os = "linux")]
+impl<E> HasTimeout for TimeoutExecutor<E> {
+    #[allow(clippy::cast_sign_loss)]
+    fn timeout(&self) -> Duration {
+        Duration::new(
+            self.itimerspec.it_value.tv_sec as _,
+            self.itimerspec.it_value.tv_nsec as _,
+        )
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        TimeoutExecutor::set_timeout(self, timeout);
+    }
+}
+
 #[cfg(all(unix, not(target_os = "linux")))]
 impl<E> TimeoutExecutor<E> {
     /// Create a new [`TimeoutExecutor`], wrapping the given `executor` and checking for timeouts.
@@ -193,7 +209,7 @@ impl<E> TimeoutExecutor<E> {
         let milli_sec = exec_tmout.as_millis();
         let it_value = Timeval {
             tv_sec: (milli_sec / 1000) as i64,
-            tv_usec: (milli_sec % 1000) as i64,
+            tv_usec: ((milli_sec % 1000) * 1000) as i64,
         };
         let it_interval = Timeval {
             tv_sec: 0,
@@ -214,7 +230,7 @@ impl<E> TimeoutExecutor<E> {
         let milli_sec = exec_tmout.as_millis();
         let it_value = Timeval {
             tv_sec: (milli_sec / 1000) as i64,
-            tv_usec: (milli_sec % 1000) as i64,
+            tv_usec: ((milli_sec % 1000) * 1000) as i64,
         };
         let it_interval = Timeval {
             tv_sec: 0,
@@ -228,6 +244,22 @@ impl<E> TimeoutExecutor<E> {
     }
 }
 
+#[cfg(all(unix, not(target_os = "linux")))]
+impl<E> HasTimeout for TimeoutExecutor<E> {
+    #[allow(clippy::cast_sign_loss)]
+    fn timeout(&self) -> Duration {
+        Duration::new(
+            self.itimerval.it_value.tv_sec as _,
+            (self.itimerval.it_value.tv_usec * 1000) as _,
+        )
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        TimeoutExecutor::set_timeout(self, timeout);
+    }
+}
+
 #[cfg(windows)]
 impl<E: HasInProcessHandlers> TimeoutExecutor<E> {
     /// Create a new [`TimeoutExecutor`], wrapping the given `executor` and checking for timeouts.
@@ -268,6 +300,19 @@ impl<E: HasInProcessHandlers> TimeoutExecutor<E> {
     }
 }
 
+#[cfg(windows)]
+impl<E: HasInProcessHandlers> HasTimeout for TimeoutExecutor<E> {
+    #[allow(clippy::cast_sign_loss)]
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.milli_sec as u64)
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        TimeoutExecutor::set_timeout(self, timeout);
+    }
+}
+
 #[cfg(windows)]
 impl<E, EM, I, S, Z> Executor<EM, I, S, Z> for TimeoutExecutor<E>
 where
@@ -0,0 +1,171 @@
+//! The [`BranchHitsFeedback`] counts how many executions hit each entry of a map.
+//! `FairFuzz` uses these counts to find the rare branches of the target, see the `RareBranchScheduler`.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{tuples::Named, AsRefIterator},
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::Input,
+    observers::{MapObserver, ObserversTuple},
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+
+/// The state metadata of the [`BranchHitsFeedback`], the global hit counts of each map entry
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BranchHitsMetadata {
+    /// How many executions hit each map entry
+    pub hits: Vec<u64>,
+}
+
+crate::impl_serdeany!(BranchHitsMetadata);
+
+impl BranchHitsMetadata {
+    /// Creates a new [`struct@BranchHitsMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The hit count of the given map entry
+    #[must_use]
+    pub fn hits(&self, idx: usize) -> u64 {
+        self.hits.get(idx).copied().unwrap_or(0)
+    }
+
+    /// The current rarity cutoff, as in `FairFuzz`: the smallest power of two that is larger than the hit count
+    /// of the least hit branch. Returns `None` if no branch was hit yet.
+    #[must_use]
+    pub fn rare_threshold(&self) -> Option<u64> {
+        self.hits
+            .iter()
+            .copied()
+            .filter(|hits| *hits > 0)
+            .min()
+            .map(|min| (min + 1).next_power_of_two())
+    }
+
+    /// Returns if the given map entry was hit, but less often than the `threshold`
+    #[must_use]
+    pub fn is_rare(&self, idx: usize, threshold: u64) -> bool {
+        let hits = self.hits(idx);
+        hits > 0 && hits < threshold
+    }
+}
+
+/// A [`BranchHitsFeedback`] counts the executions hitting each entry of a map.
+/// It never considers an input interesting, so it is usually combined with another feedback using `feedback_or!`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BranchHitsFeedback<O> {
+    observer_name: String,
+    o_type: PhantomData<O>,
+}
+
+impl<I, O, S> Feedback<I, S> for BranchHitsFeedback<O>
+where
+    I: Input,
+    O: MapObserver,
+    for<'it> O: AsRefIterator<'it, Item = O::Entry>,
+    S: HasClientPerfMonitor + HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        if !state.has_metadata::<BranchHitsMetadata>() {
+            state.add_metadata(BranchHitsMetadata::new());
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found".to_string()))?;
+        let initial = observer.initial();
+
+        let meta = state
+            .metadata_mut()
+            .get_mut::<BranchHitsMetadata>()
+            .ok_or_else(|| Error::key_not_found("BranchHitsMetadata not found".to_string()))?;
+        let len = observer.usable_count();
+        if meta.hits.len() < len {
+            meta.hits.resize(len, 0);
+        }
+
+        for (hits, item) in meta.hits.iter_mut().zip(observer.as_ref_iter()) {
+            if *item != initial {
+                *hits += 1;
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<O> Named for BranchHitsFeedback<O> {
+    #[inline]
+    fn name(&self) -> &str {
+        "BranchHitsFeedback"
+    }
+}
+
+impl<O> HasObserverName for BranchHitsFeedback<O> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<O> BranchHitsFeedback<O>
+where
+    O: MapObserver,
+{
+    /// Creates a new [`BranchHitsFeedback`], counting the hits of the map of the given observer
+    #[must_use]
+    pub fn new(observer: &O) -> Self {
+        Self::with_observer_name(observer.name())
+    }
+
+    /// Creates a new [`BranchHitsFeedback`], counting the hits of the map of the observer with the given name
+    #[must_use]
+    pub fn with_observer_name(observer_name: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            o_type: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feedbacks::BranchHitsMetadata;
+
+    #[test]
+    fn test_rare_threshold() {
+        let mut meta = BranchHitsMetadata::new();
+        assert_eq!(meta.rare_threshold(), None);
+
+        meta.hits = vec![0, 100, 3, 5000];
+        assert_eq!(meta.rare_threshold(), Some(4));
+        assert!(meta.is_rare(2, 4));
+        assert!(!meta.is_rare(0, 4));
+        assert!(!meta.is_rare(1, 4));
+    }
+}
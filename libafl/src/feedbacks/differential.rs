@@ -1,4 +1,5 @@
-//! Diff Feedback, comparing the content of two observers of the same type.
+//! Diff Feedback, comparing the content of two observers.
+//! Together with the [`crate::executors::DiffExecutor`], the observers can belong to two different executors.
 //!
 
 use alloc::string::{String, ToString};
@@ -120,7 +121,7 @@ where
     F: FnMut(&O1, &O2) -> DiffResult,
     I: Input,
     S: HasMetadata + HasClientPerfMonitor,
-    O1: Observer<I, S> + PartialEq<O2>,
+    O1: Observer<I, S>,
     O2: Observer<I, S>,
 {
     #[allow(clippy::wrong_self_convention)]
@@ -147,7 +148,7 @@ where
             .match_name(&self.o2_name)
             .ok_or_else(|| err(&self.o2_name))?;
 
-        Ok(o1 != o2)
+        Ok((self.compare_fn)(o1, o2).is_diff())
     }
 }
 
@@ -270,4 +271,26 @@ mod tests {
     fn test_diff_neq() {
         test_diff(false);
     }
+
+    #[test]
+    fn test_diff_compare_fn() {
+        let mut nop_state = NopState;
+
+        let o1 = NopObserver::new("o1", true);
+        let o2 = NopObserver::new("o2", false);
+
+        // The values differ, but the compare function decides they are the same
+        let mut diff_feedback =
+            DiffFeedback::new("diff_feedback", &o1, &o2, |_o1, _o2| DiffResult::Equal).unwrap();
+        let observers = tuple_list![o1, o2];
+        assert!(!diff_feedback
+            .is_interesting(
+                &mut nop_state,
+                &mut NopEventFirer {},
+                &BytesInput::new(vec![0]),
+                &observers,
+                &ExitKind::Ok
+            )
+            .unwrap());
+    }
 }
@@ -0,0 +1,167 @@
+//! The [`EntropicFeedback`] updates the local feature frequencies of the corpus entry being fuzzed on each execution,
+//! as libFuzzer does for the `EntropicScheduler`.
+
+use alloc::string::{String, ToString};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    corpus::Corpus,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::Input,
+    observers::{MapObserver, ObserversTuple},
+    schedulers::entropic::{EntropicMetadata, EntropicTestcaseMetadata},
+    state::{HasClientPerfMonitor, HasCorpus, HasMetadata},
+    Error,
+};
+
+/// An [`EntropicFeedback`] credits the rare features hit by each execution to the corpus entry the input was
+/// derived from, so that the `EntropicScheduler` knows how much information the entry still reveals.
+/// It never considers an input interesting, so it is usually combined with another feedback using `feedback_or!`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EntropicFeedback<O> {
+    observer_name: String,
+    o_type: PhantomData<O>,
+}
+
+impl<I, O, S> Feedback<I, S> for EntropicFeedback<O>
+where
+    I: Input,
+    O: MapObserver,
+    S: HasClientPerfMonitor + HasMetadata + HasCorpus<I>,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found".to_string()))?;
+
+        let idx = match *state.corpus().current() {
+            Some(idx) if idx < state.corpus().count() => idx,
+            _ => return Ok(false),
+        };
+        // Nothing to update before the scheduler added its metadata
+        let meta = match state.metadata().get::<EntropicMetadata>() {
+            Some(meta) => meta,
+            None => return Ok(false),
+        };
+
+        let mut testcase = state.corpus().get(idx)?.borrow_mut();
+        if let Some(tcmeta) = testcase
+            .metadata_mut()
+            .get_mut::<EntropicTestcaseMetadata>()
+        {
+            // Only the few rare features are checked, not the whole map
+            let initial = observer.initial();
+            let len = observer.usable_count();
+            for feature in meta.rare_features().keys() {
+                if *feature < len && *observer.get(*feature) != initial {
+                    tcmeta.add_feature_hit(*feature);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<O> Named for EntropicFeedback<O> {
+    #[inline]
+    fn name(&self) -> &str {
+        "EntropicFeedback"
+    }
+}
+
+impl<O> HasObserverName for EntropicFeedback<O> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<O> EntropicFeedback<O>
+where
+    O: MapObserver,
+{
+    /// Creates a new [`EntropicFeedback`], reading the features from the map of the given observer
+    #[must_use]
+    pub fn new(observer: &O) -> Self {
+        Self::with_observer_name(observer.name())
+    }
+
+    /// Creates a new [`EntropicFeedback`], reading the features from the map of the observer with the given name
+    #[must_use]
+    pub fn with_observer_name(observer_name: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            o_type: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{EntropicFeedback, Feedback, MapIndexesMetadata},
+        inputs::BytesInput,
+        observers::StdMapObserver,
+        schedulers::{entropic::EntropicTestcaseMetadata, EntropicScheduler, Scheduler},
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    fn test_entropic_feedback() {
+        let scheduler = EntropicScheduler::new();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        let mut testcase = Testcase::new(BytesInput::new(vec![0]));
+        testcase.add_metadata(MapIndexesMetadata::new(vec![1, 2]));
+        let idx = state.corpus_mut().add(testcase).unwrap();
+        scheduler.on_add(&mut state, idx).unwrap();
+        scheduler.next(&mut state).unwrap();
+
+        let mut map = [0_u8, 1, 0, 0];
+        let observers = tuple_list!(StdMapObserver::new("map", &mut map));
+        let mut feedback = EntropicFeedback::new(&observers.0);
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![1]);
+        for _ in 0..2 {
+            assert!(!feedback
+                .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                .unwrap());
+        }
+
+        let testcase = state.corpus().get(idx).unwrap().borrow();
+        let freqs = testcase
+            .metadata()
+            .get::<EntropicTestcaseMetadata>()
+            .unwrap()
+            .feature_freqs();
+        assert_eq!(freqs.get(&1), Some(&2));
+        assert_eq!(freqs.get(&2), None);
+    }
+}
@@ -0,0 +1,83 @@
+//! The ``LeakFeedback`` reports runs that leaked memory, as detected by an [`ObserverWithLeakCheck`]
+
+use alloc::string::{String, ToString};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::Input,
+    observers::{ObserverWithLeakCheck, ObserversTuple},
+    state::HasClientPerfMonitor,
+    Error,
+};
+
+/// The prefix of the [`LeakFeedback`] names
+pub const LEAKFEEDBACK_PREFIX: &str = "leakfeedback_";
+
+/// A [`LeakFeedback`] reports as interesting if the target leaked memory during the last run.
+/// Use it as an objective, next to the `CrashFeedback`, to store leaking inputs as solutions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeakFeedback<O> {
+    name: String,
+    observer_name: String,
+    o_type: PhantomData<O>,
+}
+
+impl<I, S, O> Feedback<I, S> for LeakFeedback<O>
+where
+    I: Input,
+    S: HasClientPerfMonitor,
+    O: ObserverWithLeakCheck + Named + Debug,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| Error::key_not_found("LeakObserver not found".to_string()))?;
+        Ok(observer.leaked())
+    }
+}
+
+impl<O> Named for LeakFeedback<O> {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<O> HasObserverName for LeakFeedback<O> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<O> LeakFeedback<O>
+where
+    O: ObserverWithLeakCheck + Named + Debug,
+{
+    /// Returns a new [`LeakFeedback`] for the given observer.
+    #[must_use]
+    pub fn new(observer: &O) -> Self {
+        Self {
+            name: LEAKFEEDBACK_PREFIX.to_string() + observer.name(),
+            observer_name: observer.name().to_string(),
+            o_type: PhantomData,
+        }
+    }
+}
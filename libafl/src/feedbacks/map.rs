@@ -6,9 +6,12 @@ use alloc::{
 };
 use core::ops::{BitAnd, BitOr};
 use core::{fmt::Debug, marker::PhantomData};
+use hashbrown::HashMap;
 use num_traits::PrimInt;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+#[rustversion::nightly]
+use crate::bolts::simd::merge_max;
 use crate::{
     bolts::{tuples::Named, AsMutSlice, AsRefIterator, AsSlice, HasRefCnt},
     corpus::Testcase,
@@ -240,6 +243,31 @@ impl MapIndexesMetadata {
     }
 }
 
+/// A testcase metadata holding the lists of indexes of several maps, by the name of their [`MapFeedback`].
+/// It is used instead of the [`struct@MapIndexesMetadata`] by the feedbacks created [`MapFeedback::with_named_indexes`],
+/// so that the indexes of different maps, e.g. edges and cmp coverage, can be told apart.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NamedMapIndexesMetadata {
+    /// The lists of indexes, by feedback name
+    pub map: HashMap<String, Vec<usize>>,
+}
+
+crate::impl_serdeany!(NamedMapIndexesMetadata);
+
+impl NamedMapIndexesMetadata {
+    /// Creates a new [`struct@NamedMapIndexesMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The indexes stored by the feedback with the given name
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&[usize]> {
+        self.map.get(name).map(Vec::as_slice)
+    }
+}
+
 /// A testcase metadata holding a list of indexes of a map
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MapNoveltiesMetadata {
@@ -331,6 +359,8 @@ where
     indexes: Option<Vec<usize>>,
     /// New indexes observed in the last observation
     novelties: Option<Vec<usize>>,
+    /// Store the indexes in the [`struct@NamedMapIndexesMetadata`]
+    named_indexes: bool,
     /// Name identifier of this instance
     name: String,
     /// Name identifier of the observer
@@ -390,8 +420,20 @@ where
 
     fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
         if let Some(v) = self.indexes.as_mut() {
-            let meta = MapIndexesMetadata::new(core::mem::take(v));
-            testcase.add_metadata(meta);
+            if self.named_indexes {
+                if !testcase.has_metadata::<NamedMapIndexesMetadata>() {
+                    testcase.add_metadata(NamedMapIndexesMetadata::new());
+                }
+                testcase
+                    .metadata_mut()
+                    .get_mut::<NamedMapIndexesMetadata>()
+                    .unwrap()
+                    .map
+                    .insert(self.name.clone(), core::mem::take(v));
+            } else {
+                let meta = MapIndexesMetadata::new(core::mem::take(v));
+                testcase.add_metadata(meta);
+            }
         };
         if let Some(v) = self.novelties.as_mut() {
             let meta = MapNoveltiesMetadata::new(core::mem::take(v));
@@ -427,21 +469,21 @@ where
         &mut self,
         state: &mut S,
         manager: &mut EM,
-        _input: &I,
+        input: &I,
         observers: &OT,
-        _exit_kind: &ExitKind,
+        exit_kind: &ExitKind,
     ) -> Result<bool, Error>
     where
         EM: EventFirer<I>,
         OT: ObserversTuple<I, S>,
     {
-        // 128 bits vectors
-        type VectorType = core::simd::u8x16;
-
-        let mut interesting = false;
         // TODO Replace with match_name_type when stable
         let observer = observers.match_name::<O>(&self.observer_name).unwrap();
 
+        if observer.touched_indexes().is_some() {
+            return self.is_interesting_default(state, manager, input, observers, exit_kind);
+        }
+
         let map_state = state
             .named_metadata_mut()
             .get_mut::<MapFeedbackMetadata<u8>>(&self.name)
@@ -457,55 +499,12 @@ where
 
         let history_map = map_state.history_map.as_mut_slice();
 
-        // Non vector implementation for reference
-        /*for (i, history) in history_map.iter_mut().enumerate() {
-            let item = map[i];
-            let reduced = MaxReducer::reduce(*history, item);
-            if DifferentIsNovel::is_novel(*history, reduced) {
-                *history = reduced;
-                interesting = true;
-                if self.novelties.is_some() {
-                    self.novelties.as_mut().unwrap().push(i);
-                }
-            }
-        }*/
-
-        let steps = size / VectorType::LANES;
-        let left = size % VectorType::LANES;
-
-        for step in 0..steps {
-            let i = step * VectorType::LANES;
-            let history = VectorType::from_slice(&history_map[i..]);
-            let items = VectorType::from_slice(&map[i..]);
-
-            if items.max(history) != history {
-                interesting = true;
-                unsafe {
-                    for j in i..(i + VectorType::LANES) {
-                        let item = *map.get_unchecked(j);
-                        if item > *history_map.get_unchecked(j) {
-                            *history_map.get_unchecked_mut(j) = item;
-                            if self.novelties.is_some() {
-                                self.novelties.as_mut().unwrap().push(j);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        for j in (size - left)..size {
-            unsafe {
-                let item = *map.get_unchecked(j);
-                if item > *history_map.get_unchecked(j) {
-                    interesting = true;
-                    *history_map.get_unchecked_mut(j) = item;
-                    if self.novelties.is_some() {
-                        self.novelties.as_mut().unwrap().push(j);
-                    }
-                }
-            }
-        }
+        // Vectorized, picking SSE2, AVX2 or NEON at runtime
+        let interesting = merge_max(
+            &mut history_map[..size],
+            &map[..size],
+            self.novelties.as_mut(),
+        );
 
         let initial = observer.initial();
         if interesting {
@@ -583,6 +582,7 @@ where
         Self {
             indexes: None,
             novelties: None,
+            named_indexes: false,
             name: MAPFEEDBACK_PREFIX.to_string() + map_observer.name(),
             observer_name: map_observer.name().to_string(),
             stats_name: create_stats_name(map_observer.name()),
@@ -596,6 +596,7 @@ where
         Self {
             indexes: if track_indexes { Some(vec![]) } else { None },
             novelties: if track_novelties { Some(vec![]) } else { None },
+            named_indexes: false,
             name: MAPFEEDBACK_PREFIX.to_string() + map_observer.name(),
             observer_name: map_observer.name().to_string(),
             stats_name: create_stats_name(map_observer.name()),
@@ -609,6 +610,7 @@ where
         Self {
             indexes: None,
             novelties: None,
+            named_indexes: false,
             name: name.to_string(),
             observer_name: observer_name.to_string(),
             stats_name: create_stats_name(name),
@@ -627,6 +629,7 @@ where
         Self {
             indexes: if track_indexes { Some(vec![]) } else { None },
             novelties: if track_novelties { Some(vec![]) } else { None },
+            named_indexes: false,
             observer_name: observer_name.to_string(),
             stats_name: create_stats_name(name),
             name: name.to_string(),
@@ -634,6 +637,17 @@ where
         }
     }
 
+    /// Track the indexes of used entries, and store them in the [`struct@NamedMapIndexesMetadata`]
+    /// under the name of this feedback, instead of the [`struct@MapIndexesMetadata`]
+    #[must_use]
+    pub fn with_named_indexes(mut self) -> Self {
+        if self.indexes.is_none() {
+            self.indexes = Some(vec![]);
+        }
+        self.named_indexes = true;
+        self
+    }
+
     #[allow(clippy::wrong_self_convention)]
     #[allow(clippy::needless_range_loop)]
     #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -664,17 +678,32 @@ where
 
         let history_map = map_state.history_map.as_mut_slice();
 
-        for (i, (item, history)) in observer
-            .as_ref_iter()
-            .zip(history_map.iter_mut())
-            .enumerate()
-        {
-            let reduced = R::reduce(*history, *item);
-            if N::is_novel(*history, reduced) {
-                *history = reduced;
-                interesting = true;
-                if self.novelties.is_some() {
-                    self.novelties.as_mut().unwrap().push(i);
+        if let Some(touched) = observer.touched_indexes() {
+            // The untouched entries hold the initial value, they can't be novel
+            for i in touched {
+                let history = &mut history_map[*i];
+                let reduced = R::reduce(*history, *observer.get(*i));
+                if N::is_novel(*history, reduced) {
+                    *history = reduced;
+                    interesting = true;
+                    if self.novelties.is_some() {
+                        self.novelties.as_mut().unwrap().push(*i);
+                    }
+                }
+            }
+        } else {
+            for (i, (item, history)) in observer
+                .as_ref_iter()
+                .zip(history_map.iter_mut())
+                .enumerate()
+            {
+                let reduced = R::reduce(*history, *item);
+                if N::is_novel(*history, reduced) {
+                    *history = reduced;
+                    interesting = true;
+                    if self.novelties.is_some() {
+                        self.novelties.as_mut().unwrap().push(i);
+                    }
                 }
             }
         }
@@ -803,7 +832,21 @@ where
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "std")]
+    use core::ptr::addr_of_mut;
+
     use crate::feedbacks::{AllIsNovel, IsNovel, NextPow2IsNovel};
+    #[cfg(feature = "std")]
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{Feedback, MaxMapFeedback},
+        inputs::BytesInput,
+        observers::{MapObserver, ObserversTuple, TouchedMapObserver},
+        state::StdState,
+    };
 
     #[test]
     fn test_map_is_novel() {
@@ -824,6 +867,63 @@ mod tests {
         assert!(NextPow2IsNovel::is_novel(254_u8, 255));
         assert!(!NextPow2IsNovel::is_novel(255_u8, 255));
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_touched_map_feedback() {
+        const LEN: usize = 64;
+        let mut map = [0_u8; LEN];
+        let mut touched = [0_usize; LEN];
+        let mut touched_len = 0;
+        let map_ptr = map.as_mut_ptr();
+        let touched_ptr = touched.as_mut_ptr();
+        let touched_len_ptr = addr_of_mut!(touched_len);
+
+        // Simulates the instrumentation
+        let run_target = |hits: &[(usize, u8)]| unsafe {
+            for (idx, count) in hits {
+                if *map_ptr.add(*idx) == 0 {
+                    *touched_ptr.add(*touched_len_ptr) = *idx;
+                    *touched_len_ptr += 1;
+                }
+                *map_ptr.add(*idx) = *count;
+            }
+        };
+
+        let observer = unsafe {
+            TouchedMapObserver::new_from_ptr("map", map_ptr, LEN, touched_ptr, LEN, touched_len_ptr)
+        };
+        let mut feedback = MaxMapFeedback::<BytesInput, _, _, u8>::new(&observer);
+        let mut observers = tuple_list!(observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut (),
+        )
+        .unwrap();
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![0]);
+
+        for (hits, interesting) in [
+            (&[(3, 1), (40, 2)][..], true),
+            (&[(3, 1)][..], false),
+            (&[(3, 4)][..], true),
+        ] {
+            observers.pre_exec_all(&mut state, &input).unwrap();
+            run_target(hits);
+            assert_eq!(observers.0.touched_indexes().unwrap().len(), hits.len());
+            assert_eq!(
+                feedback
+                    .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                    .unwrap(),
+                interesting
+            );
+        }
+        // Only the entry of the last run is set, the others were reset
+        assert_eq!(observers.0.count_bytes(), 1);
+    }
 }
 
 /// `MapFeedback` Python bindings
@@ -16,7 +16,19 @@ pub mod new_hash_feedback;
 #[cfg(feature = "std")]
 pub use new_hash_feedback::NewHashFeedback;
 #[cfg(feature = "std")]
-pub use new_hash_feedback::NewHashFeedbackMetadata;
+pub use new_hash_feedback::{BacktraceHashMetadata, NewHashFeedbackMetadata};
+
+pub mod leak;
+pub use leak::LeakFeedback;
+
+pub mod branch_hits;
+pub use branch_hits::{BranchHitsFeedback, BranchHitsMetadata};
+
+pub mod entropic;
+pub use entropic::EntropicFeedback;
+
+pub mod perf;
+pub use perf::{MaxHitCountFeedback, TotalHitCountFeedback};
 
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
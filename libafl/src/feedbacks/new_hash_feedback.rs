@@ -8,12 +8,13 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     bolts::tuples::Named,
+    corpus::Testcase,
     events::EventFirer,
     executors::ExitKind,
     feedbacks::{Feedback, HasObserverName},
     inputs::Input,
     observers::{ObserverWithHashField, ObserversTuple},
-    state::{HasClientPerfMonitor, HasNamedMetadata},
+    state::{HasClientPerfMonitor, HasMetadata, HasNamedMetadata},
     Error,
 };
 
@@ -66,11 +67,32 @@ impl HashSetState<u64> for NewHashFeedbackMetadata {
     }
 }
 
+/// The testcase metadata added by the [`NewHashFeedback`], holding the hash of the backtrace.
+/// Solutions with the same hash usually belong to the same bug, so it can be used to bucket crashes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct BacktraceHashMetadata {
+    /// The hash of the backtrace
+    pub hash: u64,
+}
+
+crate::impl_serdeany!(BacktraceHashMetadata);
+
+impl BacktraceHashMetadata {
+    /// Create a new [`BacktraceHashMetadata`]
+    #[must_use]
+    pub fn new(hash: u64) -> Self {
+        Self { hash }
+    }
+}
+
 /// A [`NewHashFeedback`] maintains a hashset of already seen stacktraces and considers interesting unseen ones
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NewHashFeedback<O> {
     name: String,
     observer_name: String,
+    /// The hash of the last run, to add it to the testcase
+    #[serde(skip)]
+    last_hash: Option<u64>,
     o_type: PhantomData<O>,
 }
 
@@ -107,6 +129,8 @@ where
             .get_mut::<NewHashFeedbackMetadata>(&self.name)
             .unwrap();
 
+        self.last_hash = *observer.hash();
+
         match observer.hash() {
             Some(hash) => {
                 let res = backtrace_state
@@ -120,6 +144,18 @@ where
             }
         }
     }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(hash) = self.last_hash.take() {
+            testcase.add_metadata(BacktraceHashMetadata::new(hash));
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_hash = None;
+        Ok(())
+    }
 }
 
 impl<O> Named for NewHashFeedback<O> {
@@ -147,6 +183,7 @@ where
         Self {
             name: name.to_string(),
             observer_name: observer_name.to_string(),
+            last_hash: None,
             o_type: PhantomData,
         }
     }
@@ -157,6 +194,7 @@ where
         Self {
             name: NEWHASHFEEDBACK_PREFIX.to_string() + observer.name(),
             observer_name: observer.name().to_string(),
+            last_hash: None,
             o_type: PhantomData,
         }
     }
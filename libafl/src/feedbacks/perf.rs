@@ -0,0 +1,278 @@
+//! Feedbacks for performance fuzzing, as in `PerfFuzz` (`https://github.com/carolemieux/perffuzz`).
+//! The [`MaxHitCountFeedback`] keeps the maximum hit count of each edge, the `MaxHitCountScheduler` favors
+//! the inputs holding a maximum, and the [`TotalHitCountFeedback`] reports inputs that execute too many edges,
+//! to find algorithmic complexity bugs.
+//! They need a map of raw hit counts, i.e. a map observer that does not classify the counts into buckets.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{tuples::Named, AsRefIterator},
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::Input,
+    observers::{MapObserver, ObserversTuple},
+    state::{HasClientPerfMonitor, HasMetadata, HasNamedMetadata},
+    Error,
+};
+
+/// The prefix of the metadata names of the [`MaxHitCountFeedback`]
+pub const MAXHITCOUNTFEEDBACK_PREFIX: &str = "maxhitcountfeedback_metadata_";
+
+/// The state of the [`MaxHitCountFeedback`], the maximum hit count of each edge seen so far
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MaxHitCountMetadata {
+    /// The maximum hit count of each edge
+    pub max_counts: Vec<u64>,
+}
+
+crate::impl_serdeany!(MaxHitCountMetadata);
+
+impl MaxHitCountMetadata {
+    /// Creates a new [`struct@MaxHitCountMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A testcase metadata holding the hit counts of the edges hit by the testcase
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HitCountsMetadata {
+    /// The edges hit by the testcase, and how often
+    pub counts: Vec<(usize, u64)>,
+}
+
+crate::impl_serdeany!(HitCountsMetadata);
+
+impl HitCountsMetadata {
+    /// Creates a new [`struct@HitCountsMetadata`]
+    #[must_use]
+    pub fn new(counts: Vec<(usize, u64)>) -> Self {
+        Self { counts }
+    }
+
+    /// The sum of the hit counts
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.counts
+            .iter()
+            .fold(0_u64, |acc, (_, count)| acc.saturating_add(*count))
+    }
+}
+
+/// A [`MaxHitCountFeedback`] considers an input interesting if it hits an edge more often than any input before.
+/// It adds the [`struct@HitCountsMetadata`] to new corpus entries.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MaxHitCountFeedback<O> {
+    name: String,
+    observer_name: String,
+    /// The hit counts of the last run
+    #[serde(skip)]
+    last_counts: Vec<(usize, u64)>,
+    o_type: PhantomData<O>,
+}
+
+impl<I, O, S> Feedback<I, S> for MaxHitCountFeedback<O>
+where
+    I: Input,
+    O: MapObserver,
+    O::Entry: Into<u64>,
+    for<'it> O: AsRefIterator<'it, Item = O::Entry>,
+    S: HasClientPerfMonitor + HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata(MaxHitCountMetadata::new(), &self.name);
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found".to_string()))?;
+
+        let meta = state
+            .named_metadata_mut()
+            .get_mut::<MaxHitCountMetadata>(&self.name)
+            .ok_or_else(|| Error::key_not_found("MaxHitCountMetadata not found".to_string()))?;
+        let len = observer.usable_count();
+        if meta.max_counts.len() < len {
+            meta.max_counts.resize(len, 0);
+        }
+
+        self.last_counts.clear();
+        let mut interesting = false;
+        for (i, item) in observer.as_ref_iter().enumerate().take(len) {
+            let count: u64 = (*item).into();
+            if count == 0 {
+                continue;
+            }
+            self.last_counts.push((i, count));
+            if count > meta.max_counts[i] {
+                meta.max_counts[i] = count;
+                interesting = true;
+            }
+        }
+        Ok(interesting)
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        testcase.add_metadata(HitCountsMetadata::new(core::mem::take(
+            &mut self.last_counts,
+        )));
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_counts.clear();
+        Ok(())
+    }
+}
+
+impl<O> Named for MaxHitCountFeedback<O> {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<O> HasObserverName for MaxHitCountFeedback<O> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<O> MaxHitCountFeedback<O>
+where
+    O: MapObserver,
+{
+    /// Creates a new [`MaxHitCountFeedback`] for the hit counts map of the given observer
+    #[must_use]
+    pub fn new(observer: &O) -> Self {
+        Self::with_names(
+            &(MAXHITCOUNTFEEDBACK_PREFIX.to_string() + observer.name()),
+            observer.name(),
+        )
+    }
+
+    /// Creates a new [`MaxHitCountFeedback`] with the given name, for the observer with the given name
+    #[must_use]
+    pub fn with_names(name: &str, observer_name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            observer_name: observer_name.to_string(),
+            last_counts: vec![],
+            o_type: PhantomData,
+        }
+    }
+}
+
+/// A [`TotalHitCountFeedback`] considers an input interesting if the sum of all hit counts exceeds a threshold,
+/// i.e. if it executes too many edges or instructions. It is meant to be used as objective.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TotalHitCountFeedback<O> {
+    observer_name: String,
+    threshold: u64,
+    o_type: PhantomData<O>,
+}
+
+impl<I, O, S> Feedback<I, S> for TotalHitCountFeedback<O>
+where
+    I: Input,
+    O: MapObserver,
+    O::Entry: Into<u64>,
+    for<'it> O: AsRefIterator<'it, Item = O::Entry>,
+    S: HasClientPerfMonitor,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found".to_string()))?;
+        let total = observer
+            .as_ref_iter()
+            .take(observer.usable_count())
+            .fold(0_u64, |acc, item| acc.saturating_add((*item).into()));
+        Ok(total > self.threshold)
+    }
+}
+
+impl<O> Named for TotalHitCountFeedback<O> {
+    #[inline]
+    fn name(&self) -> &str {
+        "TotalHitCountFeedback"
+    }
+}
+
+impl<O> HasObserverName for TotalHitCountFeedback<O> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<O> TotalHitCountFeedback<O>
+where
+    O: MapObserver,
+{
+    /// Creates a new [`TotalHitCountFeedback`], triggering if the sum of the hit counts of the observer's map
+    /// exceeds the `threshold`
+    #[must_use]
+    pub fn new(observer: &O, threshold: u64) -> Self {
+        Self {
+            observer_name: observer.name().to_string(),
+            threshold,
+            o_type: PhantomData,
+        }
+    }
+
+    /// The threshold of this feedback
+    #[must_use]
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feedbacks::perf::HitCountsMetadata;
+
+    #[test]
+    fn test_hit_counts_total() {
+        let meta = HitCountsMetadata::new(vec![(1, 3), (5, u64::MAX), (7, 1)]);
+        assert_eq!(meta.total(), u64::MAX);
+        let meta = HitCountsMetadata::new(vec![(1, 3), (7, 1)]);
+        assert_eq!(meta.total(), 4);
+    }
+}
@@ -1,14 +1,24 @@
 //! Monitors that wrap a base one and log on disk
 
-use alloc::{string::String, vec::Vec};
+use alloc::{boxed::Box, string::String, vec::Vec};
 use core::time::Duration;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "introspection")]
+use crate::monitors::ClientPerfMonitor;
 use crate::{
     bolts::{current_time, format_duration_hms},
-    monitors::{ClientStats, Monitor, NopMonitor},
+    events::LogSeverity,
+    monitors::{ClientStats, Monitor, NopMonitor, TestcaseInfo, UserStats},
+    Error,
 };
 
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
 
 /// Wrap a monitor and log the current state of the monitor into a TOML file.
 #[derive(Debug, Clone)]
@@ -104,6 +114,14 @@ exec_sec = {}
 
         self.base.display(event_msg, sender_id);
     }
+
+    fn display_log(&mut self, severity_level: LogSeverity, message: &str, sender_id: u32) {
+        self.base.display_log(severity_level, message, sender_id);
+    }
+
+    fn display_testcase(&mut self, info: &TestcaseInfo, sender_id: u32) {
+        self.base.display_testcase(info, sender_id);
+    }
 }
 
 impl<M> OnDiskTOMLMonitor<M>
@@ -134,3 +152,387 @@ impl OnDiskTOMLMonitor<NopMonitor> {
         Self::new(filename, NopMonitor::new())
     }
 }
+
+/// Wrap a monitor and append every update of the monitor, as well as all log messages, to a JSON-lines file.
+/// Unlike the [`OnDiskTOMLMonitor`], the whole history is kept, so the file can be analyzed after the campaign,
+/// see [`read_json_log`] and [`coverage_over_time`].
+#[derive(Debug)]
+pub struct OnDiskJSONMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    filename: PathBuf,
+    /// The log file, opened on the first record and kept open
+    file: Option<File>,
+}
+
+impl<M> Clone for OnDiskJSONMonitor<M>
+where
+    M: Monitor + Clone,
+{
+    fn clone(&self) -> Self {
+        // The clone opens its own handle, appending to the same file
+        Self {
+            base: self.base.clone(),
+            filename: self.filename.clone(),
+            file: None,
+        }
+    }
+}
+
+impl<M> Monitor for OnDiskJSONMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&mut self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn display(&mut self, event_msg: String, sender_id: u32) {
+        let cur_time = current_time();
+        let run_time = cur_time - self.start_time();
+
+        let client = self.client_stats_mut_for(sender_id);
+        let stats = JsonClientStats {
+            corpus_size: client.corpus_size,
+            objective_size: client.objective_size,
+            executions: client.executions,
+            execs_per_sec: client.execs_per_sec(cur_time),
+            user_stats: client.user_monitor.clone(),
+            #[cfg(feature = "introspection")]
+            introspection: Some(client.introspection_monitor.clone()),
+        };
+        let stats = Box::new(stats);
+
+        let global = JsonGlobalStats {
+            // The client stats start with a dummy entry for the broker
+            clients: self.client_stats().len().saturating_sub(1) as u64,
+            corpus_size: self.corpus_size(),
+            objective_size: self.objective_size(),
+            executions: self.total_execs(),
+            execs_per_sec: self.execs_per_sec(),
+        };
+
+        self.append(&JsonLogRecord::Update {
+            run_time_ms: run_time.as_millis() as u64,
+            timestamp_ms: cur_time.as_millis() as u64,
+            client: sender_id,
+            event: event_msg.clone(),
+            stats,
+            global,
+        });
+
+        self.base.display(event_msg, sender_id);
+    }
+
+    fn display_log(&mut self, severity_level: LogSeverity, message: &str, sender_id: u32) {
+        let cur_time = current_time();
+        let run_time = cur_time - self.start_time();
+        self.append(&JsonLogRecord::Log {
+            run_time_ms: run_time.as_millis() as u64,
+            timestamp_ms: cur_time.as_millis() as u64,
+            client: sender_id,
+            severity: severity_level,
+            message: message.into(),
+        });
+
+        self.base.display_log(severity_level, message, sender_id);
+    }
+
+    fn display_testcase(&mut self, info: &TestcaseInfo, sender_id: u32) {
+        self.base.display_testcase(info, sender_id);
+    }
+}
+
+impl<M> OnDiskJSONMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create new [`OnDiskJSONMonitor`], appending to the file at `filename`
+    #[must_use]
+    pub fn new<P>(filename: P, base: M) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            base,
+            filename: filename.into(),
+            file: None,
+        }
+    }
+
+    /// Appends a single record to the log file.
+    /// Failures are reported on stderr and do not stop the fuzzer, the file is reopened for the next record.
+    fn append(&mut self, record: &JsonLogRecord) {
+        let mut line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Failed to serialize the JSON record: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+        if self.file.is_none() {
+            match OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&self.filename)
+            {
+                Ok(file) => self.file = Some(file),
+                Err(e) => {
+                    eprintln!("Failed to open the JSON log {:?}: {}", self.filename, e);
+                    return;
+                }
+            }
+        }
+        if let Some(file) = &mut self.file {
+            // A single `write_all` in append mode, so a crash never leaves half a line in the middle of the log
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                eprintln!("Failed to write to the JSON log {:?}: {}", self.filename, e);
+                self.file = None;
+            }
+        }
+    }
+}
+
+impl OnDiskJSONMonitor<NopMonitor> {
+    /// Create new [`OnDiskJSONMonitor`] without a base
+    #[must_use]
+    pub fn nop<P>(filename: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::new(filename, NopMonitor::new())
+    }
+}
+
+/// The stats of a single client, as written by the [`OnDiskJSONMonitor`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JsonClientStats {
+    /// The corpus size of this client
+    pub corpus_size: u64,
+    /// The amount of objectives found by this client
+    pub objective_size: u64,
+    /// The executions of this client
+    pub executions: u64,
+    /// The executions per second of this client
+    pub execs_per_sec: u64,
+    /// The user stats of this client
+    pub user_stats: HashMap<String, UserStats>,
+    /// The introspection stats of this client, only written with the `introspection` feature
+    #[cfg(feature = "introspection")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub introspection: Option<ClientPerfMonitor>,
+}
+
+/// The stats, combined for all clients, as written by the [`OnDiskJSONMonitor`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonGlobalStats {
+    /// The amount of clients
+    pub clients: u64,
+    /// The combined corpus size
+    pub corpus_size: u64,
+    /// The combined amount of objectives
+    pub objective_size: u64,
+    /// The combined executions
+    pub executions: u64,
+    /// The combined executions per second
+    pub execs_per_sec: u64,
+}
+
+/// A single line of the log written by the [`OnDiskJSONMonitor`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum JsonLogRecord {
+    /// The stats of a client got updated
+    Update {
+        /// Milliseconds since the start of the campaign
+        run_time_ms: u64,
+        /// Milliseconds since the UNIX epoch
+        timestamp_ms: u64,
+        /// The client that sent the update
+        client: u32,
+        /// The event that caused the update, such as `Testcase`, `Objective` or `Stats`
+        event: String,
+        /// The new stats of the client
+        stats: Box<JsonClientStats>,
+        /// The stats of all clients at this point in time
+        global: JsonGlobalStats,
+    },
+    /// A client sent a log message
+    Log {
+        /// Milliseconds since the start of the campaign
+        run_time_ms: u64,
+        /// Milliseconds since the UNIX epoch
+        timestamp_ms: u64,
+        /// The client that sent the message
+        client: u32,
+        /// The severity of the message
+        severity: LogSeverity,
+        /// The message
+        message: String,
+    },
+}
+
+impl JsonLogRecord {
+    /// The time since the start of the campaign
+    #[must_use]
+    pub fn run_time(&self) -> Duration {
+        match self {
+            JsonLogRecord::Update { run_time_ms, .. } | JsonLogRecord::Log { run_time_ms, .. } => {
+                Duration::from_millis(*run_time_ms)
+            }
+        }
+    }
+
+    /// The client this record belongs to
+    #[must_use]
+    pub fn client(&self) -> u32 {
+        match self {
+            JsonLogRecord::Update { client, .. } | JsonLogRecord::Log { client, .. } => *client,
+        }
+    }
+}
+
+/// Reads all records of a log written by the [`OnDiskJSONMonitor`].
+/// A truncated last line, as left by a killed broker, is ignored.
+pub fn read_json_log<P>(path: P) -> Result<Vec<JsonLogRecord>, Error>
+where
+    P: AsRef<Path>,
+{
+    let mut records = vec![];
+    let mut lines = BufReader::new(File::open(path)?)
+        .lines()
+        .enumerate()
+        .peekable();
+    while let Some((i, line)) = lines.next() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(_) if lines.peek().is_none() => break,
+            Err(e) => {
+                return Err(Error::serialize(format!(
+                    "Invalid record in line {} of the JSON log: {}",
+                    i + 1,
+                    e
+                )))
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Computes the coverage-over-time curve from the records of a JSON log.
+/// The coverage is read from the [`UserStats`] called `name`, for map feedbacks the name of the map observer, e.g. `edges`.
+/// Since clients share their findings, the coverage at each point in time is the maximum over all clients.
+///
+/// Returns `(run time, coverage)` pairs, one for each time the coverage changed.
+#[must_use]
+pub fn coverage_over_time(records: &[JsonLogRecord], name: &str) -> Vec<(Duration, u64)> {
+    let mut per_client = HashMap::<u32, u64>::new();
+    let mut curve: Vec<(Duration, u64)> = vec![];
+    for record in records {
+        if let JsonLogRecord::Update { client, stats, .. } = record {
+            let value = match stats.user_stats.get(name) {
+                Some(UserStats::Number(n) | UserStats::Ratio(n, _)) => *n,
+                _ => continue,
+            };
+            per_client.insert(*client, value);
+            let max = per_client.values().copied().max().unwrap_or_default();
+            if curve.last().map_or(true, |(_, last)| *last != max) {
+                curve.push((record.run_time(), max));
+            }
+        }
+    }
+    curve
+}
+
+/// Computes a curve over any of the [`JsonGlobalStats`], for example the corpus size or the objectives, over time.
+///
+/// Returns `(run time, value)` pairs, one for each time the value changed.
+pub fn global_over_time<F>(records: &[JsonLogRecord], mut f: F) -> Vec<(Duration, u64)>
+where
+    F: FnMut(&JsonGlobalStats) -> u64,
+{
+    let mut curve: Vec<(Duration, u64)> = vec![];
+    for record in records {
+        if let JsonLogRecord::Update { global, .. } = record {
+            let value = f(global);
+            if curve.last().map_or(true, |(_, last)| *last != value) {
+                curve.push((record.run_time(), value));
+            }
+        }
+    }
+    curve
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+    use std::{env::temp_dir, fs};
+
+    use crate::{
+        events::LogSeverity,
+        monitors::{
+            disk::{coverage_over_time, global_over_time, read_json_log, JsonLogRecord},
+            Monitor, OnDiskJSONMonitor, UserStats,
+        },
+    };
+
+    #[test]
+    fn test_json_log_roundtrip() {
+        let path = temp_dir().join(format!("libafl_json_log_test_{}.jsonl", std::process::id()));
+        drop(fs::remove_file(&path));
+
+        let mut monitor = OnDiskJSONMonitor::nop(&path);
+        for (client, edges) in [(1, 10), (2, 5), (1, 10), (2, 12)] {
+            monitor
+                .client_stats_mut_for(client)
+                .update_user_stats("edges".to_string(), UserStats::Ratio(edges, 100));
+            monitor.display("Stats".to_string(), client);
+        }
+        monitor.client_stats_mut_for(1).update_corpus_size(3);
+        monitor.display("Testcase".to_string(), 1);
+        monitor.display_log(LogSeverity::Info, "hello", 2);
+
+        let records = read_json_log(&path).unwrap();
+        assert_eq!(records.len(), 6);
+        assert!(matches!(&records[5], JsonLogRecord::Log { message, .. } if message == "hello"));
+
+        let coverage: Vec<u64> = coverage_over_time(&records, "edges")
+            .iter()
+            .map(|(_, edges)| *edges)
+            .collect();
+        assert_eq!(coverage, vec![10, 12]);
+
+        let corpus: Vec<u64> = global_over_time(&records, |global| global.corpus_size)
+            .iter()
+            .map(|(_, corpus)| *corpus)
+            .collect();
+        assert_eq!(corpus, vec![0, 3]);
+
+        // The dummy client of the broker is not counted
+        let clients: Vec<u64> = global_over_time(&records, |global| global.clients)
+            .iter()
+            .map(|(_, clients)| *clients)
+            .collect();
+        assert_eq!(clients, vec![1, 2]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -10,7 +10,12 @@ pub mod tui;
 #[cfg(feature = "std")]
 pub mod disk;
 #[cfg(feature = "std")]
-pub use disk::OnDiskTOMLMonitor;
+pub use disk::{OnDiskJSONMonitor, OnDiskTOMLMonitor};
+
+#[cfg(feature = "std")]
+pub mod prometheus;
+#[cfg(feature = "std")]
+pub use prometheus::PrometheusMonitor;
 
 use alloc::{string::String, vec::Vec};
 
@@ -21,7 +26,10 @@ use core::{fmt, time::Duration};
 use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 
-use crate::bolts::{current_time, format_duration_hms};
+use crate::{
+    bolts::{current_time, format_duration_hms},
+    events::LogSeverity,
+};
 
 #[cfg(feature = "afl_exec_sec")]
 const CLIENT_STATS_TIME_WINDOW_SECS: u64 = 5; // 5 seconds
@@ -114,40 +122,64 @@ impl ClientStats {
     }
 
     /// Get the calculated executions per second for this client
-    #[allow(clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    #[allow(clippy::cast_sign_loss)]
     #[cfg(feature = "afl_exec_sec")]
     pub fn execs_per_sec(&mut self, cur_time: Duration) -> u64 {
+        if let Some(execs_per_sec) = self.next_execs_per_sec(cur_time) {
+            self.last_execs_per_sec = execs_per_sec;
+        }
+        self.last_execs_per_sec as u64
+    }
+
+    /// Get the calculated executions per second for this client, without moving the time window.
+    /// Use this to read the speed more often than the stats are updated, e.g., when rendering them.
+    #[allow(clippy::cast_sign_loss)]
+    #[cfg(feature = "afl_exec_sec")]
+    #[must_use]
+    pub fn current_execs_per_sec(&self, cur_time: Duration) -> u64 {
+        self.next_execs_per_sec(cur_time)
+            .unwrap_or(self.last_execs_per_sec) as u64
+    }
+
+    /// The smoothed executions per second at `cur_time`, or `None` if the last value is still valid
+    #[allow(clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    #[cfg(feature = "afl_exec_sec")]
+    fn next_execs_per_sec(&self, cur_time: Duration) -> Option<f64> {
         if self.executions == 0 {
-            return 0;
+            return Some(0.0);
         }
 
         let elapsed = cur_time
             .checked_sub(self.last_window_time)
             .map_or(0.0, |d| d.as_secs_f64());
         if elapsed as u64 == 0 {
-            return self.last_execs_per_sec as u64;
+            return None;
         }
 
         let cur_avg = ((self.executions - self.last_window_executions) as f64) / elapsed;
-        if self.last_window_executions == 0 {
-            self.last_execs_per_sec = cur_avg;
-            return self.last_execs_per_sec as u64;
-        }
-
         // If there is a dramatic (5x+) jump in speed, reset the indicator more quickly
-        if cur_avg * 5.0 < self.last_execs_per_sec || cur_avg / 5.0 > self.last_execs_per_sec {
-            self.last_execs_per_sec = cur_avg;
+        if self.last_window_executions == 0
+            || cur_avg * 5.0 < self.last_execs_per_sec
+            || cur_avg / 5.0 > self.last_execs_per_sec
+        {
+            return Some(cur_avg);
         }
 
-        self.last_execs_per_sec =
-            self.last_execs_per_sec * (1.0 - 1.0 / 16.0) + cur_avg * (1.0 / 16.0);
-        self.last_execs_per_sec as u64
+        Some(self.last_execs_per_sec * (1.0 - 1.0 / 16.0) + cur_avg * (1.0 / 16.0))
     }
 
     /// Get the calculated executions per second for this client
-    #[allow(clippy::cast_sign_loss, clippy::cast_precision_loss)]
     #[cfg(not(feature = "afl_exec_sec"))]
     pub fn execs_per_sec(&mut self, cur_time: Duration) -> u64 {
+        self.current_execs_per_sec(cur_time)
+    }
+
+    /// Get the calculated executions per second for this client, without moving the time window.
+    /// Use this to read the speed more often than the stats are updated, e.g., when rendering them.
+    #[allow(clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    #[cfg(not(feature = "afl_exec_sec"))]
+    #[must_use]
+    pub fn current_execs_per_sec(&self, cur_time: Duration) -> u64 {
         if self.executions == 0 {
             return 0;
         }
@@ -179,6 +211,42 @@ impl ClientStats {
     }
 }
 
+/// Whether a [`TestcaseInfo`] describes an entry of the corpus or a solution
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TestcaseKind {
+    /// An entry of the corpus
+    Corpus,
+    /// A solution, i.e., an objective
+    Solution,
+}
+
+/// Information about a single testcase of a client, sent to the monitor to browse the corpus and the solutions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TestcaseInfo {
+    /// If this is a corpus entry or a solution
+    pub kind: TestcaseKind,
+    /// The index in the corpus (or solutions) of the client
+    pub idx: usize,
+    /// The executions of the client when the testcase was found
+    pub executions: usize,
+    /// The execution time, once calibrated
+    pub exec_time: Option<Duration>,
+    /// The amount of covered map entries, once calibrated
+    pub bitmap_size: Option<u64>,
+    /// If the scheduler currently favors this testcase
+    pub favored: bool,
+    /// The depth in the mutation tree, i.e., the amount of ancestors
+    pub depth: Option<u64>,
+    /// The mutations that led to this testcase, if logged
+    pub mutations: Vec<String>,
+    /// The crash bucket (the backtrace hash) of a solution, if known
+    pub crash_bucket: Option<u64>,
+    /// The length of the input
+    pub len: usize,
+    /// The bytes of the input, possibly truncated
+    pub bytes: Vec<u8>,
+}
+
 /// The monitor trait keeps track of all the client's monitor, and offers methods to display them.
 pub trait Monitor {
     /// The client monitor (mutable)
@@ -193,6 +261,17 @@ pub trait Monitor {
     /// Show the monitor to the user
     fn display(&mut self, event_msg: String, sender_id: u32);
 
+    /// Show a log message sent by a client. By default, it is printed to stdout (with `std`).
+    #[allow(unused_variables)]
+    fn display_log(&mut self, severity_level: LogSeverity, message: &str, sender_id: u32) {
+        #[cfg(feature = "std")]
+        println!("[LOG {}]: {}", severity_level, message);
+    }
+
+    /// Show information about a corpus entry or a solution of a client. Ignored by default.
+    #[allow(unused_variables)]
+    fn display_testcase(&mut self, info: &TestcaseInfo, sender_id: u32) {}
+
     /// Amount of elements in the corpus (combined for all children)
     fn corpus_size(&self) -> u64 {
         self.client_stats()
@@ -822,7 +901,10 @@ impl Default for ClientPerfMonitor {
 #[cfg(feature = "python")]
 #[allow(missing_docs)]
 pub mod pybind {
-    use crate::monitors::{Monitor, SimpleMonitor};
+    use crate::{
+        events::LogSeverity,
+        monitors::{Monitor, SimpleMonitor, TestcaseInfo},
+    };
     use pyo3::prelude::*;
     use pyo3::types::PyUnicode;
 
@@ -952,6 +1034,16 @@ pub mod pybind {
         fn display(&mut self, event_msg: String, sender_id: u32) {
             unwrap_me_mut!(self.wrapper, m, { m.display(event_msg, sender_id) });
         }
+
+        fn display_log(&mut self, severity_level: LogSeverity, message: &str, sender_id: u32) {
+            unwrap_me_mut!(self.wrapper, m, {
+                m.display_log(severity_level, message, sender_id);
+            });
+        }
+
+        fn display_testcase(&mut self, info: &TestcaseInfo, sender_id: u32) {
+            unwrap_me_mut!(self.wrapper, m, { m.display_testcase(info, sender_id) });
+        }
     }
     /// Register the classes to the python module
     pub fn register(_py: Python, m: &PyModule) -> PyResult<()> {
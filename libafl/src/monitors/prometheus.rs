@@ -0,0 +1,453 @@
+//! A monitor that serves its stats as an [`OpenMetrics`](https://openmetrics.io/) endpoint,
+//! so that fuzzing campaigns can be scraped by Prometheus like any other service.
+//!
+//! All per-client metrics carry a `client` label with the client id, aggregated metrics are prefixed with `libafl_global_`.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{fmt::Write, time::Duration};
+use std::{
+    io::{Read, Write as IoWrite},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    thread,
+};
+
+use crate::{
+    bolts::current_time,
+    events::LogSeverity,
+    monitors::{ClientStats, Monitor, NopMonitor, TestcaseInfo, UserStats},
+    Error,
+};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+
+/// The content type of the `OpenMetrics` text format
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Wrap a monitor and serve the current stats on `http://<addr>/metrics` in the `OpenMetrics` text format.
+/// The HTTP server is started in a background thread on the first call to `display`,
+/// i.e., only in the broker, which is the process that actually receives the stats.
+#[derive(Debug, Clone)]
+pub struct PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    addr: SocketAddr,
+    /// The last rendered metrics, shared with the server thread
+    metrics: Arc<Mutex<String>>,
+    server_started: bool,
+}
+
+impl<M> Monitor for PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&mut self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn display(&mut self, event_msg: String, sender_id: u32) {
+        if !self.server_started {
+            self.server_started = true;
+            if let Err(err) = self.spawn_server() {
+                println!(
+                    "Failed to start the Prometheus endpoint on {}: {:?}",
+                    self.addr, err
+                );
+            }
+        }
+
+        let metrics = self.render();
+        *self.metrics.lock().unwrap() = metrics;
+
+        self.base.display(event_msg, sender_id);
+    }
+
+    fn display_log(&mut self, severity_level: LogSeverity, message: &str, sender_id: u32) {
+        self.base.display_log(severity_level, message, sender_id);
+    }
+
+    fn display_testcase(&mut self, info: &TestcaseInfo, sender_id: u32) {
+        self.base.display_testcase(info, sender_id);
+    }
+}
+
+impl<M> PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`PrometheusMonitor`], serving the metrics on `addr`, for example `127.0.0.1:9090`.
+    pub fn new<A>(addr: A, base: M) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::illegal_argument("No address to listen on given"))?;
+        Ok(Self {
+            base,
+            addr,
+            metrics: Arc::new(Mutex::new(String::new())),
+            server_started: false,
+        })
+    }
+
+    /// The address the metrics are served on
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Renders the current stats in the `OpenMetrics` text format.
+    /// The executions per second are only read, so rendering does not move the time window of the clients.
+    pub fn render(&mut self) -> String {
+        let cur_time = current_time();
+        let run_time = cur_time - self.start_time();
+        let mut out = String::new();
+
+        self.render_global(&mut out, cur_time, run_time);
+        self.render_clients(&mut out, cur_time);
+
+        #[cfg(feature = "introspection")]
+        self.render_introspection(&mut out);
+
+        out.push_str("# EOF\n");
+        out
+    }
+
+    /// Renders the stats combined for all clients
+    #[allow(clippy::cast_precision_loss)]
+    fn render_global(&mut self, out: &mut String, cur_time: Duration, run_time: Duration) {
+        let execs_per_sec = self
+            .client_stats()
+            .iter()
+            .fold(0_u64, |acc, x| acc + x.current_execs_per_sec(cur_time));
+        let clients = self.client_stats().len().saturating_sub(1);
+        write_metric(
+            out,
+            "libafl_global_clients",
+            "gauge",
+            "Number of connected clients",
+            &[(String::new(), clients as f64)],
+        );
+        write_metric(
+            out,
+            "libafl_global_run_time_seconds",
+            "gauge",
+            "Time since the start of the campaign",
+            &[(String::new(), run_time.as_secs_f64())],
+        );
+        write_metric(
+            out,
+            "libafl_global_corpus_size",
+            "gauge",
+            "Amount of corpus entries, combined for all clients",
+            &[(String::new(), self.corpus_size() as f64)],
+        );
+        write_metric(
+            out,
+            "libafl_global_objective_size",
+            "gauge",
+            "Amount of objectives, combined for all clients",
+            &[(String::new(), self.objective_size() as f64)],
+        );
+        write_counter(
+            out,
+            "libafl_global_executions",
+            "Executions, combined for all clients",
+            &[(String::new(), self.total_execs() as f64)],
+        );
+        write_metric(
+            out,
+            "libafl_global_execs_per_sec",
+            "gauge",
+            "Executions per second, combined for all clients",
+            &[(String::new(), execs_per_sec as f64)],
+        );
+    }
+
+    /// Renders the stats of each client
+    #[allow(clippy::cast_precision_loss)]
+    fn render_clients(&self, out: &mut String, cur_time: Duration) {
+        let mut corpus = vec![];
+        let mut objectives = vec![];
+        let mut executions = vec![];
+        let mut execs_per_sec = vec![];
+        let mut user_stats = vec![];
+        for (i, client) in self.client_stats().iter().enumerate().skip(1) {
+            let labels = format!("client=\"{}\"", i);
+            corpus.push((labels.clone(), client.corpus_size as f64));
+            objectives.push((labels.clone(), client.objective_size as f64));
+            executions.push((labels.clone(), client.executions as f64));
+            execs_per_sec.push((
+                labels.clone(),
+                client.current_execs_per_sec(cur_time) as f64,
+            ));
+
+            for (name, value) in &client.user_monitor {
+                let value = match value {
+                    UserStats::Number(n) => *n as f64,
+                    UserStats::Float(f) => *f,
+                    UserStats::Ratio(a, b) => {
+                        if *b == 0 {
+                            continue;
+                        }
+                        *a as f64 / *b as f64
+                    }
+                    UserStats::String(_) => continue,
+                };
+                user_stats.push((
+                    format!("{},name=\"{}\"", labels, escape_label_value(name)),
+                    value,
+                ));
+            }
+        }
+
+        write_metric(
+            out,
+            "libafl_corpus_size",
+            "gauge",
+            "Amount of corpus entries of this client",
+            &corpus,
+        );
+        write_metric(
+            out,
+            "libafl_objective_size",
+            "gauge",
+            "Amount of objectives of this client",
+            &objectives,
+        );
+        write_counter(
+            out,
+            "libafl_executions",
+            "Executions of this client",
+            &executions,
+        );
+        write_metric(
+            out,
+            "libafl_execs_per_sec",
+            "gauge",
+            "Executions per second of this client",
+            &execs_per_sec,
+        );
+        write_metric(
+            out,
+            "libafl_user_stats",
+            "gauge",
+            "Numeric user stats reported by this client, ratios are reported as fraction",
+            &user_stats,
+        );
+    }
+
+    /// Renders the introspection stats of all clients, in clock cycles
+    #[cfg(feature = "introspection")]
+    #[allow(clippy::cast_precision_loss)]
+    fn render_introspection(&self, out: &mut String) {
+        let mut elapsed = vec![];
+        let mut scheduler = vec![];
+        let mut manager = vec![];
+        let mut stages = vec![];
+        let mut feedbacks = vec![];
+        for (i, client) in self.client_stats().iter().enumerate().skip(1) {
+            let labels = format!("client=\"{}\"", i);
+            let perf = &client.introspection_monitor;
+            elapsed.push((labels.clone(), perf.elapsed_cycles() as f64));
+            scheduler.push((labels.clone(), perf.scheduler_cycles() as f64));
+            manager.push((labels.clone(), perf.manager_cycles() as f64));
+
+            for (stage_index, features) in perf.used_stages() {
+                for (feature_index, cycles) in features.iter().enumerate() {
+                    if *cycles == 0 {
+                        continue;
+                    }
+                    let feature: PerfFeature = feature_index.into();
+                    stages.push((
+                        format!(
+                            "{},stage=\"{}\",feature=\"{:?}\"",
+                            labels, stage_index, feature
+                        ),
+                        *cycles as f64,
+                    ));
+                }
+            }
+
+            for (name, cycles) in perf.feedbacks() {
+                feedbacks.push((
+                    format!("{},feedback=\"{}\"", labels, escape_label_value(name)),
+                    *cycles as f64,
+                ));
+            }
+        }
+
+        write_metric(
+            out,
+            "libafl_perf_elapsed_cycles",
+            "gauge",
+            "Clock cycles measured by the introspection of this client",
+            &elapsed,
+        );
+        write_metric(
+            out,
+            "libafl_perf_scheduler_cycles",
+            "gauge",
+            "Clock cycles spent in the scheduler",
+            &scheduler,
+        );
+        write_metric(
+            out,
+            "libafl_perf_manager_cycles",
+            "gauge",
+            "Clock cycles spent in the event manager",
+            &manager,
+        );
+        write_metric(
+            out,
+            "libafl_perf_stage_cycles",
+            "gauge",
+            "Clock cycles spent in each feature of each stage",
+            &stages,
+        );
+        write_metric(
+            out,
+            "libafl_perf_feedback_cycles",
+            "gauge",
+            "Clock cycles spent in each feedback",
+            &feedbacks,
+        );
+    }
+
+    /// Binds to the address and serves the metrics from a background thread
+    fn spawn_server(&self) -> Result<(), Error> {
+        let listener = TcpListener::bind(self.addr)?;
+        let metrics = self.metrics.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A misbehaving scraper should never take the broker down
+                let _ = serve_metrics(stream, &metrics);
+            }
+        });
+        Ok(())
+    }
+}
+
+impl PrometheusMonitor<NopMonitor> {
+    /// Create new [`PrometheusMonitor`] without a base
+    pub fn nop<A>(addr: A) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::new(addr, NopMonitor::new())
+    }
+}
+
+/// Answers a single HTTP request with the current metrics
+fn serve_metrics(mut stream: TcpStream, metrics: &Mutex<String>) -> Result<(), Error> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut buf = [0_u8; 4096];
+    let len = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+
+    let response = if method == "GET" && (path == "/metrics" || path == "/") {
+        let body = metrics.lock().unwrap().clone();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            OPENMETRICS_CONTENT_TYPE,
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".into()
+    };
+    stream.write_all(response.as_bytes())?;
+    Ok(())
+}
+
+/// Writes a metric family with the given samples, each consisting of the labels and the value.
+/// Families without samples are skipped.
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, f64)]) {
+    if samples.is_empty() {
+        return;
+    }
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    for (labels, value) in samples {
+        write_sample(out, name, labels, *value);
+    }
+}
+
+/// Writes a counter family, the samples get the `_total` suffix required by `OpenMetrics`.
+fn write_counter(out: &mut String, name: &str, help: &str, samples: &[(String, f64)]) {
+    if samples.is_empty() {
+        return;
+    }
+    writeln!(out, "# TYPE {} counter", name).unwrap();
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    for (labels, value) in samples {
+        write_sample(out, &format!("{}_total", name), labels, *value);
+    }
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &str, value: f64) {
+    if labels.is_empty() {
+        writeln!(out, "{} {}", name, value).unwrap();
+    } else {
+        writeln!(out, "{}{{{}}} {}", name, labels, value).unwrap();
+    }
+}
+
+/// Escapes a label value, as required by the text format
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use crate::monitors::{prometheus::PrometheusMonitor, Monitor, UserStats};
+
+    #[test]
+    fn test_prometheus_render() {
+        let mut monitor = PrometheusMonitor::nop("127.0.0.1:0").unwrap();
+        let client = monitor.client_stats_mut_for(1);
+        client.update_corpus_size(42);
+        client.update_objective_size(1);
+        client.update_user_stats("my \"stat\"".to_string(), UserStats::Ratio(1, 4));
+
+        let metrics = monitor.render();
+        assert!(metrics.contains("libafl_global_clients 1\n"));
+        assert!(metrics.contains("libafl_global_corpus_size 42\n"));
+        assert!(metrics.contains("libafl_corpus_size{client=\"1\"} 42\n"));
+        assert!(metrics.contains("libafl_executions_total{client=\"1\"} 0\n"));
+        assert!(metrics.contains("libafl_user_stats{client=\"1\",name=\"my \\\"stat\\\"\"} 0.25\n"));
+        assert!(metrics.ends_with("# EOF\n"));
+    }
+}
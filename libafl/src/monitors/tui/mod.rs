@@ -9,7 +9,7 @@ use hashbrown::HashMap;
 use tui::{backend::CrosstermBackend, Terminal};
 
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     fmt::Write,
     io::{self, BufRead},
     string::String,
@@ -24,7 +24,8 @@ use super::{ClientPerfMonitor, PerfFeature};
 
 use crate::{
     bolts::{current_time, format_duration_hms},
-    monitors::{ClientStats, Monitor, UserStats},
+    events::LogSeverity,
+    monitors::{ClientStats, Monitor, TestcaseInfo, TestcaseKind, UserStats},
 };
 
 mod ui;
@@ -185,6 +186,27 @@ impl ClientTuiContext {
     }
 }
 
+/// The corpus entries or solutions of all clients, as reported to the monitor
+#[derive(Debug, Default, Clone)]
+pub struct TestcaseTuiList {
+    pub entries: Vec<(u32, TestcaseInfo)>,
+    /// (client, index) -> position in `entries`
+    positions: HashMap<(u32, usize), usize>,
+}
+
+impl TestcaseTuiList {
+    /// Adds a new testcase, or replaces the previous info of the same testcase
+    pub fn update(&mut self, sender_id: u32, info: TestcaseInfo) {
+        if let Some(pos) = self.positions.get(&(sender_id, info.idx)) {
+            self.entries[*pos] = (sender_id, info);
+        } else {
+            self.positions
+                .insert((sender_id, info.idx), self.entries.len());
+            self.entries.push((sender_id, info));
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TuiContext {
     pub graphs: Vec<String>,
@@ -193,6 +215,12 @@ pub struct TuiContext {
     pub corpus_size_timed: TimedStats,
     pub objective_size_timed: TimedStats,
     pub execs_per_sec_timed: TimedStats,
+    /// The covered entries of each coverage map (reported as ratio user stats), the maximum over all clients.
+    /// This is one chart per map, not per edge.
+    pub coverage_timed: BTreeMap<String, TimedStats>,
+
+    pub corpus_entries: TestcaseTuiList,
+    pub solutions: TestcaseTuiList,
 
     #[cfg(feature = "introspection")]
     pub introspection: HashMap<usize, PerfTuiContext>,
@@ -215,6 +243,10 @@ impl TuiContext {
             corpus_size_timed: TimedStats::new(Duration::from_secs(DEFAULT_TIME_WINDOW)),
             objective_size_timed: TimedStats::new(Duration::from_secs(DEFAULT_TIME_WINDOW)),
             execs_per_sec_timed: TimedStats::new(Duration::from_secs(DEFAULT_TIME_WINDOW)),
+            coverage_timed: BTreeMap::new(),
+
+            corpus_entries: TestcaseTuiList::default(),
+            solutions: TestcaseTuiList::default(),
 
             #[cfg(feature = "introspection")]
             introspection: HashMap::default(),
@@ -262,7 +294,23 @@ impl Monitor for TuiMonitor {
             let totalexec = self.total_execs();
             let run_time = cur_time - self.start_time;
 
+            let mut coverage = HashMap::<&String, u64>::new();
+            for client in &self.client_stats {
+                for (key, val) in &client.user_monitor {
+                    if let UserStats::Ratio(covered, _) = val {
+                        let max = coverage.entry(key).or_default();
+                        *max = (*max).max(*covered);
+                    }
+                }
+            }
+
             let mut ctx = self.context.write().unwrap();
+            for (key, covered) in coverage {
+                ctx.coverage_timed
+                    .entry(key.clone())
+                    .or_insert_with(|| TimedStats::new(Duration::from_secs(DEFAULT_TIME_WINDOW)))
+                    .add(run_time, covered);
+            }
             ctx.corpus_size_timed.add(run_time, self.corpus_size());
             ctx.objective_size_timed
                 .add(run_time, self.objective_size());
@@ -316,6 +364,25 @@ impl Monitor for TuiMonitor {
             }
         }
     }
+
+    fn display_log(&mut self, severity_level: LogSeverity, message: &str, sender_id: u32) {
+        let mut ctx = self.context.write().unwrap();
+        while ctx.client_logs.len() >= DEFAULT_LOGS_NUMBER {
+            ctx.client_logs.pop_front();
+        }
+        ctx.client_logs.push_back(format!(
+            "[LOG {} #{}] {}",
+            severity_level, sender_id, message
+        ));
+    }
+
+    fn display_testcase(&mut self, info: &TestcaseInfo, sender_id: u32) {
+        let mut ctx = self.context.write().unwrap();
+        match info.kind {
+            TestcaseKind::Corpus => ctx.corpus_entries.update(sender_id, info.clone()),
+            TestcaseKind::Solution => ctx.solutions.update(sender_id, info.clone()),
+        }
+    }
 }
 
 impl TuiMonitor {
@@ -377,9 +444,9 @@ fn run_tui_thread(
                     match key.code {
                         KeyCode::Char(c) => ui.on_key(c),
                         KeyCode::Left => ui.on_left(),
-                        //KeyCode::Up => ui.on_up(),
+                        KeyCode::Up => ui.on_up(),
                         KeyCode::Right => ui.on_right(),
-                        //KeyCode::Down => ui.on_down(),
+                        KeyCode::Down => ui.on_down(),
                         _ => {}
                     }
                 }
@@ -414,3 +481,41 @@ fn run_tui_thread(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TestcaseTuiList;
+    use crate::monitors::{TestcaseInfo, TestcaseKind};
+
+    fn info(idx: usize, executions: usize) -> TestcaseInfo {
+        TestcaseInfo {
+            kind: TestcaseKind::Corpus,
+            idx,
+            executions,
+            exec_time: None,
+            bitmap_size: None,
+            favored: false,
+            depth: None,
+            mutations: vec![],
+            crash_bucket: None,
+            len: 0,
+            bytes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_testcase_tui_list_update() {
+        let mut list = TestcaseTuiList::default();
+        list.update(0, info(0, 1));
+        list.update(1, info(0, 2));
+        list.update(0, info(1, 3));
+        assert_eq!(list.entries.len(), 3);
+
+        // The same testcase of the same client is replaced in place
+        list.update(1, info(0, 4));
+        assert_eq!(list.entries.len(), 3);
+        assert_eq!(list.entries[0], (0, info(0, 1)));
+        assert_eq!(list.entries[1], (1, info(0, 4)));
+        assert_eq!(list.entries[2], (0, info(1, 3)));
+    }
+}
@@ -1,6 +1,9 @@
-use super::{current_time, format_duration_hms, Duration, String, TimedStats, TuiContext};
+use super::{
+    current_time, format_duration_hms, Duration, String, TestcaseInfo, TestcaseTuiList, TimedStats,
+    TuiContext,
+};
 
-use alloc::vec::Vec;
+use alloc::{string::ToString, vec::Vec};
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
@@ -8,16 +11,24 @@ use tui::{
     symbols,
     text::{Span, Spans},
     widgets::{
-        Axis, Block, Borders, Cell, Chart, Dataset, List, ListItem, Paragraph, Row, Table, Tabs,
+        Axis, Block, Borders, Cell, Chart, Dataset, List, ListItem, Paragraph, Row, Table,
+        TableState, Tabs, Wrap,
     },
     Frame,
 };
 
 use std::{
     cmp::{max, min},
+    fmt::Write,
     sync::{Arc, RwLock},
 };
 
+/// The amount of fixed charts before the charts of the coverage maps
+const FIXED_CHARTS: usize = 3;
+
+/// The views of the TUI, switched with `v`
+const VIEWS: [&str; 3] = ["monitor", "corpus", "solutions"];
+
 #[derive(Default)]
 pub struct TuiUI {
     title: String,
@@ -26,7 +37,10 @@ pub struct TuiUI {
     clients_idx: usize,
     clients: usize,
     charts_tab_idx: usize,
+    charts: usize,
     graph_data: Vec<(f64, f64)>,
+    view_idx: usize,
+    testcases: TableState,
 
     pub should_quit: bool,
 }
@@ -38,6 +52,7 @@ impl TuiUI {
             enhanced_graphics,
             show_logs: true,
             clients_idx: 1,
+            charts: FIXED_CHARTS,
             ..TuiUI::default()
         }
     }
@@ -48,7 +63,11 @@ impl TuiUI {
                 self.should_quit = true;
             }
             'g' => {
-                self.charts_tab_idx = (self.charts_tab_idx + 1) % 3;
+                self.charts_tab_idx = (self.charts_tab_idx + 1) % self.charts;
+            }
+            'v' => {
+                self.view_idx = (self.view_idx + 1) % VIEWS.len();
+                self.testcases.select(None);
             }
             't' => {
                 self.show_logs = !self.show_logs;
@@ -57,9 +76,16 @@ impl TuiUI {
         }
     }
 
-    //pub fn on_up(&mut self) {}
+    pub fn on_up(&mut self) {
+        let selected = self.testcases.selected().unwrap_or_default();
+        self.testcases.select(Some(selected.saturating_sub(1)));
+    }
 
-    //pub fn on_down(&mut self) {}
+    pub fn on_down(&mut self) {
+        // clamped to the amount of testcases while drawing
+        let selected = self.testcases.selected().map_or(0, |selected| selected + 1);
+        self.testcases.select(Some(selected));
+    }
 
     pub fn on_right(&mut self) {
         // never 0
@@ -80,6 +106,7 @@ impl TuiUI {
         B: Backend,
     {
         self.clients = app.read().unwrap().clients_num;
+        self.charts = FIXED_CHARTS + app.read().unwrap().coverage_timed.len();
 
         let body = Layout::default()
             .constraints(if self.show_logs {
@@ -89,6 +116,14 @@ impl TuiUI {
             })
             .split(f.size());
 
+        if self.view_idx > 0 {
+            self.draw_testcases(f, app, body[0]);
+            if self.show_logs {
+                self.draw_logs(f, app, body[1]);
+            }
+            return;
+        }
+
         let top_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
@@ -115,7 +150,7 @@ impl TuiUI {
         let right_layout = Layout::default()
             .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
             .split(top_layout[1]);
-        let titles = vec![
+        let mut titles = vec![
             Spans::from(Span::styled(
                 "speed",
                 Style::default().fg(Color::LightGreen),
@@ -129,6 +164,12 @@ impl TuiUI {
                 Style::default().fg(Color::LightGreen),
             )),
         ];
+        for name in app.read().unwrap().coverage_timed.keys() {
+            titles.push(Spans::from(Span::styled(
+                name.clone(),
+                Style::default().fg(Color::LightGreen),
+            )));
+        }
         let tabs = Tabs::new(titles)
             .block(
                 Block::default()
@@ -175,7 +216,18 @@ impl TuiUI {
                     &ctx.objective_size_timed,
                 );
             }
-            _ => {}
+            idx => {
+                let ctx = app.read().unwrap();
+                if let Some((name, stats)) = ctx.coverage_timed.iter().nth(idx - FIXED_CHARTS) {
+                    self.draw_time_chart(
+                        &format!("{} map coverage chart", name),
+                        "covered entries",
+                        f,
+                        right_layout[1],
+                        stats,
+                    );
+                }
+            }
         }
 
         if self.show_logs {
@@ -484,6 +536,184 @@ impl TuiUI {
         }
     }
 
+    /// Draws the corpus or solutions view: a list of the testcases, the details and a hex view of the selected one
+    fn draw_testcases<B>(&mut self, f: &mut Frame<B>, app: &Arc<RwLock<TuiContext>>, area: Rect)
+    where
+        B: Backend,
+    {
+        let ctx = app.read().unwrap();
+        let (list, title): (&TestcaseTuiList, &str) = if self.view_idx == 1 {
+            (
+                &ctx.corpus_entries,
+                "corpus (`v` switch view, up/down arrows to select)",
+            )
+        } else {
+            (
+                &ctx.solutions,
+                "solutions (`v` switch view, up/down arrows to select)",
+            )
+        };
+
+        if let Some(selected) = self.testcases.selected() {
+            if list.entries.is_empty() {
+                self.testcases.select(None);
+            } else if selected >= list.entries.len() {
+                self.testcases.select(Some(list.entries.len() - 1));
+            }
+        } else if !list.entries.is_empty() {
+            self.testcases.select(Some(0));
+        }
+
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(area);
+
+        let header = Row::new(vec![
+            "client",
+            "id",
+            "len",
+            "exec time",
+            "bitmap",
+            "favored",
+            "depth",
+            "bucket",
+        ])
+        .style(Style::default().fg(Color::LightGreen));
+        let rows: Vec<Row> = list
+            .entries
+            .iter()
+            .map(|(client, info)| {
+                Row::new(vec![
+                    Cell::from(format!("#{}", client)),
+                    Cell::from(format!("{}", info.idx)),
+                    Cell::from(format!("{}", info.len)),
+                    Cell::from(
+                        info.exec_time
+                            .map_or_else(|| "-".into(), |t| format!("{}us", t.as_micros())),
+                    ),
+                    Cell::from(
+                        info.bitmap_size
+                            .map_or_else(|| "-".into(), |s| s.to_string()),
+                    ),
+                    Cell::from(if info.favored { "yes" } else { "" }),
+                    Cell::from(info.depth.map_or_else(|| "-".into(), |d| d.to_string())),
+                    Cell::from(
+                        info.crash_bucket
+                            .map_or_else(|| "-".into(), |b| format!("{:016x}", b)),
+                    ),
+                ])
+            })
+            .collect();
+        let table = Table::new(rows)
+            .header(header)
+            .block(
+                Block::default()
+                    .title(Span::styled(
+                        title,
+                        Style::default()
+                            .fg(Color::LightCyan)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                    .borders(Borders::ALL),
+            )
+            .highlight_style(Style::default().fg(Color::LightYellow))
+            .widths(&[
+                Constraint::Length(6),
+                Constraint::Length(7),
+                Constraint::Length(7),
+                Constraint::Length(10),
+                Constraint::Length(7),
+                Constraint::Length(7),
+                Constraint::Length(5),
+                Constraint::Length(16),
+            ]);
+        f.render_stateful_widget(table, layout[0], &mut self.testcases);
+
+        let selected = self
+            .testcases
+            .selected()
+            .and_then(|selected| list.entries.get(selected));
+        Self::draw_testcase_details(f, selected, layout[1]);
+    }
+
+    /// Draws the details and a hex view of the selected testcase
+    fn draw_testcase_details<B>(
+        f: &mut Frame<B>,
+        selected: Option<&(u32, TestcaseInfo)>,
+        area: Rect,
+    ) where
+        B: Backend,
+    {
+        let right_layout = Layout::default()
+            .constraints([Constraint::Length(10), Constraint::Min(0)].as_ref())
+            .split(area);
+
+        let mut details = vec![];
+        let mut hex = vec![];
+        if let Some((client, info)) = selected {
+            details.push(Spans::from(format!(
+                "client #{}, id {}, found after {} executions",
+                client, info.idx, info.executions
+            )));
+            details.push(Spans::from(format!(
+                "len: {} bytes{}",
+                info.len,
+                if info.len > info.bytes.len() {
+                    format!(" (showing {})", info.bytes.len())
+                } else {
+                    String::new()
+                }
+            )));
+            if let Some(bucket) = info.crash_bucket {
+                details.push(Spans::from(format!("crash bucket: {:016x}", bucket)));
+            }
+            details.push(Spans::from(format!(
+                "lineage: {}",
+                if info.mutations.is_empty() {
+                    "-".into()
+                } else {
+                    info.mutations.join(", ")
+                }
+            )));
+
+            let max_lines = right_layout[1].height.saturating_sub(2) as usize;
+            hex = info
+                .bytes
+                .chunks(16)
+                .take(max_lines)
+                .enumerate()
+                .map(|(i, line)| Spans::from(hex_line(i * 16, line)))
+                .collect();
+        }
+
+        let details = Paragraph::new(details)
+            .block(
+                Block::default()
+                    .title(Span::styled(
+                        "details",
+                        Style::default()
+                            .fg(Color::LightCyan)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                    .borders(Borders::ALL),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(details, right_layout[0]);
+
+        let hex = Paragraph::new(hex).block(
+            Block::default()
+                .title(Span::styled(
+                    "hex",
+                    Style::default()
+                        .fg(Color::LightCyan)
+                        .add_modifier(Modifier::BOLD),
+                ))
+                .borders(Borders::ALL),
+        );
+        f.render_widget(hex, right_layout[1]);
+    }
+
     #[allow(clippy::unused_self)]
     fn draw_logs<B>(&mut self, f: &mut Frame<B>, app: &Arc<RwLock<TuiContext>>, area: Rect)
     where
@@ -506,3 +736,45 @@ impl TuiUI {
         f.render_widget(logs, area);
     }
 }
+
+/// Formats up to 16 bytes like `hexdump -C`
+fn hex_line(offset: usize, bytes: &[u8]) -> String {
+    let mut line = format!("{:08x}  ", offset);
+    for i in 0..16 {
+        if let Some(byte) = bytes.get(i) {
+            write!(line, "{:02x} ", byte).unwrap();
+        } else {
+            line.push_str("   ");
+        }
+        if i == 7 {
+            line.push(' ');
+        }
+    }
+    line.push_str(" |");
+    for byte in bytes {
+        line.push(if byte.is_ascii_graphic() || *byte == b' ' {
+            *byte as char
+        } else {
+            '.'
+        });
+    }
+    line.push('|');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hex_line;
+
+    #[test]
+    fn test_hex_line() {
+        assert_eq!(
+            hex_line(0x10, b"0123456789abcdef"),
+            "00000010  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|"
+        );
+        assert_eq!(
+            hex_line(0, &[0x41, 0x00, 0x20, 0xff]),
+            "00000000  41 00 20 ff                                       |A. .|"
+        );
+    }
+}
@@ -14,6 +14,8 @@ pub mod gramatron;
 pub use gramatron::*;
 pub mod grimoire;
 pub use grimoire::*;
+pub mod mutation_mask;
+pub use mutation_mask::*;
 
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
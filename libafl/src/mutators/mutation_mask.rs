@@ -0,0 +1,194 @@
+//! Mutation masks from `FairFuzz`, keeping the bytes of an input that are needed to hit a rare branch.
+
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    inputs::{HasBytesVec, Input},
+    mutators::{MutationResult, Mutator},
+    state::HasMetadata,
+    Error,
+};
+
+/// The bytes of an input that can be mutated without losing the rare branch it hits, see the `MutationMaskStage`.
+/// It is stored in the corpus entry, and in the state while the entry is fuzzed.
+/// As long as it is set in the state, the [`MaskedMutator`] only changes mutable bytes.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MutationMaskMetadata {
+    /// The map index of the rare branch this mask was computed for
+    pub target: usize,
+    /// If the byte at each offset of the input can be mutated
+    pub mask: Vec<bool>,
+}
+
+crate::impl_serdeany!(MutationMaskMetadata);
+
+impl MutationMaskMetadata {
+    /// Creates a new [`struct@MutationMaskMetadata`]
+    #[must_use]
+    pub fn new(target: usize, mask: Vec<bool>) -> Self {
+        Self { target, mask }
+    }
+
+    /// Returns if the byte at the given offset can be mutated.
+    /// Bytes beyond the end of the mask, e.g. after the input grew, are mutable.
+    #[must_use]
+    pub fn is_mutable(&self, offset: usize) -> bool {
+        self.mask.get(offset).copied().unwrap_or(true)
+    }
+}
+
+/// A [`MaskedMutator`] wraps another mutator, such as the havoc mutations, and reverts its changes to the bytes
+/// that are not mutable according to the [`MutationMaskMetadata`] in the state.
+/// The mask is defined per offset, so while it is set, mutations that change the length of the input are rejected.
+/// Without a mask, the wrapped mutator is used as is.
+#[derive(Debug)]
+pub struct MaskedMutator<M> {
+    inner: M,
+    /// The input before the mutation, reused to avoid an allocation per mutation
+    original: Vec<u8>,
+}
+
+impl<I, M, S> Mutator<I, S> for MaskedMutator<M>
+where
+    I: Input + HasBytesVec,
+    M: Mutator<I, S>,
+    S: HasMetadata,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        if !state.has_metadata::<MutationMaskMetadata>() {
+            return self.inner.mutate(state, input, stage_idx);
+        }
+
+        self.original.clear();
+        self.original.extend_from_slice(input.bytes());
+        if self.inner.mutate(state, input, stage_idx)? == MutationResult::Skipped {
+            return Ok(MutationResult::Skipped);
+        }
+
+        if input.bytes().len() != self.original.len() {
+            let bytes = input.bytes_mut();
+            bytes.clear();
+            bytes.extend_from_slice(&self.original);
+            return Ok(MutationResult::Skipped);
+        }
+
+        let meta = match state.metadata().get::<MutationMaskMetadata>() {
+            Some(meta) => meta,
+            None => return Ok(MutationResult::Mutated),
+        };
+        let mut mutated = false;
+        for (i, (byte, original)) in input
+            .bytes_mut()
+            .iter_mut()
+            .zip(self.original.iter())
+            .enumerate()
+        {
+            if !meta.is_mutable(i) {
+                *byte = *original;
+            } else if *byte != *original {
+                mutated = true;
+            }
+        }
+        if mutated {
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+
+    #[inline]
+    fn post_exec(
+        &mut self,
+        state: &mut S,
+        stage_idx: i32,
+        corpus_idx: Option<usize>,
+    ) -> Result<(), Error> {
+        self.inner.post_exec(state, stage_idx, corpus_idx)
+    }
+}
+
+impl<M> Named for MaskedMutator<M> {
+    fn name(&self) -> &str {
+        "MaskedMutator"
+    }
+}
+
+impl<M> MaskedMutator<M> {
+    /// Creates a new [`MaskedMutator`], masking the mutations of `inner`
+    #[must_use]
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            original: Vec::new(),
+        }
+    }
+
+    /// The wrapped mutator
+    pub fn inner(&mut self) -> &mut M {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::InMemoryCorpus,
+        inputs::{BytesInput, HasBytesVec},
+        mutators::{
+            ByteFlipMutator, BytesDeleteMutator, MaskedMutator, MutationMaskMetadata,
+            MutationResult, Mutator, WordAddMutator,
+        },
+        state::{HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_masked_mutator() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        state.add_metadata(MutationMaskMetadata::new(
+            0,
+            vec![false, false, true, false],
+        ));
+
+        let mut mutator = MaskedMutator::new(ByteFlipMutator::new());
+        let mut input = BytesInput::new(vec![0; 4]);
+        for _ in 0..16 {
+            mutator.mutate(&mut state, &mut input, 0).unwrap();
+            assert_eq!(input.bytes()[0], 0);
+            assert_eq!(input.bytes()[1], 0);
+            assert_eq!(input.bytes()[3], 0);
+        }
+
+        let mut mutator = MaskedMutator::new(WordAddMutator::new());
+        let mut input = BytesInput::new(vec![0; 4]);
+        for _ in 0..16 {
+            mutator.mutate(&mut state, &mut input, 0).unwrap();
+            assert_eq!(input.bytes()[0], 0);
+            assert_eq!(input.bytes()[1], 0);
+            assert_eq!(input.bytes()[3], 0);
+        }
+
+        // The length of the input must not change
+        let mut mutator = MaskedMutator::new(BytesDeleteMutator::new());
+        let mut input = BytesInput::new(vec![1, 2, 3, 4]);
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input, 0).unwrap(),
+            MutationResult::Skipped
+        );
+        assert_eq!(input.bytes(), &[1, 2, 3, 4]);
+    }
+}
@@ -382,3 +382,8 @@ pub use metadata::ConcolicMetadata;
 mod observer;
 #[cfg(feature = "std")]
 pub use observer::ConcolicObserver;
+
+#[cfg(feature = "std")]
+pub mod solver;
+#[cfg(feature = "std")]
+pub use solver::{SimpleSolution, SimpleSolver};
@@ -0,0 +1,715 @@
+//! A lightweight solver for the simple path constraints found in most concolic traces.
+//!
+//! Many branches in a target compare (a few) input bytes against a constant, possibly after some constant
+//! arithmetic or an integer cast. The [`SimpleSolver`] tracks such expressions directly and solves the negated
+//! path constraints without a full SMT solver, so concolic mutations are possible in builds without `z3`.
+//! Every negated path constraint is solved on its own, previous path constraints are not taken into account.
+
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+
+use super::{SymExpr, SymExprRef};
+
+/// The widest bitvector the [`SimpleSolver`] can reason about
+const MAX_BITS: u32 = 64;
+
+#[inline]
+const fn mask(bits: u32) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+/// Sign extends the lowest `bits` bits of `value`
+#[inline]
+#[allow(clippy::cast_possible_wrap)]
+const fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// The result of solving a negated path constraint with the [`SimpleSolver`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimpleSolution {
+    /// The input byte replacements that take the other branch
+    Solved(Vec<(usize, u8)>),
+    /// The other branch can not be taken by changing the input bytes of this constraint
+    Unsat,
+    /// The constraint is too complex for the [`SimpleSolver`]
+    Unsupported,
+}
+
+/// A byte of a symbolic bitvector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Byte {
+    /// The input byte at the given offset
+    Input(usize),
+    /// A constant byte
+    Const(u8),
+}
+
+/// An invertible operation applied to the bytes of a symbolic bitvector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add(u64),
+    Xor(u64),
+    Neg,
+    /// Zero extension from the given width
+    Zext(u32),
+    /// Sign extension from the given width
+    Sext(u32),
+    /// Truncation from the given width
+    Trunc(u32),
+}
+
+/// A bitvector made of input and constant bytes (most significant first), followed by a chain of operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Symbolic {
+    bytes: Vec<Byte>,
+    ops: Vec<Op>,
+    bits: u32,
+}
+
+impl Symbolic {
+    fn push_op(mut self, op: Op, bits: u32) -> Self {
+        self.ops.push(op);
+        self.bits = bits;
+        self
+    }
+
+    /// Computes the input bytes for which this bitvector evaluates to `value`.
+    /// If `value` is the only value satisfying the constraint, a conflict means that the constraint is [`SimpleSolution::Unsat`],
+    /// otherwise another value may still work, and the constraint is [`SimpleSolution::Unsupported`].
+    #[allow(clippy::cast_sign_loss)]
+    fn invert(&self, mut value: u64, unique: bool) -> SimpleSolution {
+        let mut bits = self.bits;
+        // a conflict is only definite if no other value could have been chosen
+        let mut exact = unique;
+        let conflict = |exact: bool| {
+            if exact {
+                SimpleSolution::Unsat
+            } else {
+                SimpleSolution::Unsupported
+            }
+        };
+        for op in self.ops.iter().rev() {
+            match *op {
+                Op::Add(k) => value = value.wrapping_sub(k) & mask(bits),
+                Op::Xor(k) => value = (value ^ k) & mask(bits),
+                Op::Neg => value = value.wrapping_neg() & mask(bits),
+                Op::Zext(from) => {
+                    if value & !mask(from) != 0 {
+                        return conflict(exact);
+                    }
+                    bits = from;
+                }
+                Op::Sext(from) => {
+                    if sign_extend(value, from) as u64 & mask(bits) != value {
+                        return conflict(exact);
+                    }
+                    value &= mask(from);
+                    bits = from;
+                }
+                // the truncated bits are chosen to be zero, any other value would do as well
+                Op::Trunc(from) => {
+                    bits = from;
+                    exact = false;
+                }
+            }
+        }
+
+        let mut replacements: Vec<(usize, u8)> = Vec::with_capacity(self.bytes.len());
+        let len = self.bytes.len();
+        for (i, byte) in self.bytes.iter().enumerate() {
+            let new_byte = (value >> (8 * (len - 1 - i))) as u8;
+            match *byte {
+                Byte::Const(c) => {
+                    if c != new_byte {
+                        return conflict(exact);
+                    }
+                }
+                Byte::Input(offset) => {
+                    if let Some((_, prev)) = replacements.iter().find(|(o, _)| *o == offset) {
+                        if *prev != new_byte {
+                            return conflict(exact);
+                        }
+                    } else {
+                        replacements.push((offset, new_byte));
+                    }
+                }
+            }
+        }
+        SimpleSolution::Solved(replacements)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpKind {
+    Equal,
+    NotEqual,
+    UnsignedLessThan,
+    UnsignedLessEqual,
+    UnsignedGreaterThan,
+    UnsignedGreaterEqual,
+    SignedLessThan,
+    SignedLessEqual,
+    SignedGreaterThan,
+    SignedGreaterEqual,
+}
+
+impl CmpKind {
+    /// The comparison that holds iff this one does not
+    fn negate(self) -> Self {
+        match self {
+            Self::Equal => Self::NotEqual,
+            Self::NotEqual => Self::Equal,
+            Self::UnsignedLessThan => Self::UnsignedGreaterEqual,
+            Self::UnsignedLessEqual => Self::UnsignedGreaterThan,
+            Self::UnsignedGreaterThan => Self::UnsignedLessEqual,
+            Self::UnsignedGreaterEqual => Self::UnsignedLessThan,
+            Self::SignedLessThan => Self::SignedGreaterEqual,
+            Self::SignedLessEqual => Self::SignedGreaterThan,
+            Self::SignedGreaterThan => Self::SignedLessEqual,
+            Self::SignedGreaterEqual => Self::SignedLessThan,
+        }
+    }
+
+    /// The comparison with swapped operands
+    fn mirror(self) -> Self {
+        match self {
+            Self::Equal | Self::NotEqual => self,
+            Self::UnsignedLessThan => Self::UnsignedGreaterThan,
+            Self::UnsignedLessEqual => Self::UnsignedGreaterEqual,
+            Self::UnsignedGreaterThan => Self::UnsignedLessThan,
+            Self::UnsignedGreaterEqual => Self::UnsignedLessEqual,
+            Self::SignedLessThan => Self::SignedGreaterThan,
+            Self::SignedLessEqual => Self::SignedGreaterEqual,
+            Self::SignedGreaterThan => Self::SignedLessThan,
+            Self::SignedGreaterEqual => Self::SignedLessEqual,
+        }
+    }
+
+    fn eval(self, a: u64, b: u64, bits: u32) -> bool {
+        let (sa, sb) = (sign_extend(a, bits), sign_extend(b, bits));
+        match self {
+            Self::Equal => a == b,
+            Self::NotEqual => a != b,
+            Self::UnsignedLessThan => a < b,
+            Self::UnsignedLessEqual => a <= b,
+            Self::UnsignedGreaterThan => a > b,
+            Self::UnsignedGreaterEqual => a >= b,
+            Self::SignedLessThan => sa < sb,
+            Self::SignedLessEqual => sa <= sb,
+            Self::SignedGreaterThan => sa > sb,
+            Self::SignedGreaterEqual => sa >= sb,
+        }
+    }
+
+    /// A value `v`, for which `v <self> rhs` holds, if there is one
+    fn satisfy(self, rhs: u64, bits: u32) -> Option<u64> {
+        let smin = 1_u64 << (bits - 1);
+        let smax = smin - 1;
+        let value = match self {
+            Self::Equal
+            | Self::UnsignedLessEqual
+            | Self::UnsignedGreaterEqual
+            | Self::SignedLessEqual
+            | Self::SignedGreaterEqual => rhs,
+            Self::NotEqual => rhs ^ 1,
+            Self::UnsignedLessThan => rhs.checked_sub(1)?,
+            Self::UnsignedGreaterThan => {
+                if rhs == mask(bits) {
+                    return None;
+                }
+                rhs + 1
+            }
+            Self::SignedLessThan => {
+                if rhs == smin {
+                    return None;
+                }
+                rhs.wrapping_sub(1)
+            }
+            Self::SignedGreaterThan => {
+                if rhs == smax {
+                    return None;
+                }
+                rhs.wrapping_add(1)
+            }
+        };
+        Some(value & mask(bits))
+    }
+}
+
+/// What the [`SimpleSolver`] knows about an expression of the trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Const {
+        value: u64,
+        bits: u32,
+    },
+    Bool(bool),
+    Symbolic(Symbolic),
+    Cmp {
+        kind: CmpKind,
+        lhs: Symbolic,
+        rhs: u64,
+    },
+}
+
+/// A lightweight, `z3`-less solver for path constraints of concolic traces.
+///
+/// It understands comparisons of input bytes (and their concatenations and extractions) with constants,
+/// after constant additions, subtractions, xors, negations and integer casts.
+/// Everything else is reported as [`SimpleSolution::Unsupported`].
+#[derive(Debug, Default)]
+pub struct SimpleSolver {
+    terms: HashMap<SymExprRef, Term>,
+}
+
+impl SimpleSolver {
+    /// Creates a new [`SimpleSolver`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the next expression of a trace.
+    /// Returns the solution of the negated constraint, if the expression is a path constraint.
+    pub fn add(&mut self, id: SymExprRef, expr: &SymExpr) -> Option<SimpleSolution> {
+        match expr {
+            SymExpr::PathConstraint {
+                constraint, taken, ..
+            } => Some(self.solve_negated(*constraint, *taken)),
+            SymExpr::ExpressionsUnreachable { exprs } => {
+                for expr in exprs {
+                    self.terms.remove(expr);
+                }
+                None
+            }
+            _ => {
+                if let Some(term) = self.translate(expr) {
+                    self.terms.insert(id, term);
+                }
+                None
+            }
+        }
+    }
+
+    /// Solves all negated path constraints of a trace, returning the input byte replacements for each of them.
+    pub fn generate_mutations(
+        &mut self,
+        iter: impl Iterator<Item = (SymExprRef, SymExpr)>,
+    ) -> Vec<Vec<(usize, u8)>> {
+        let mut res = Vec::new();
+        for (id, expr) in iter {
+            if let Some(SimpleSolution::Solved(replacements)) = self.add(id, &expr) {
+                if !replacements.is_empty() && !res.contains(&replacements) {
+                    res.push(replacements);
+                }
+            }
+        }
+        res
+    }
+
+    fn solve_negated(&self, constraint: SymExprRef, taken: bool) -> SimpleSolution {
+        match self.terms.get(&constraint) {
+            // this constraint is always sat or unsat
+            Some(Term::Bool(_)) => SimpleSolution::Unsat,
+            Some(Term::Cmp { kind, lhs, rhs }) => {
+                let kind = if taken { kind.negate() } else { *kind };
+                match kind.satisfy(*rhs, lhs.bits) {
+                    Some(value) => lhs.invert(value, kind == CmpKind::Equal),
+                    None => SimpleSolution::Unsat,
+                }
+            }
+            _ => SimpleSolution::Unsupported,
+        }
+    }
+
+    fn constant(&self, id: SymExprRef) -> Option<(u64, u32)> {
+        match self.terms.get(&id)? {
+            Term::Const { value, bits } => Some((*value, *bits)),
+            _ => None,
+        }
+    }
+
+    /// The bytes of `id`, if it is a byte-sized constant or a symbolic bitvector without operations
+    fn bytes(&self, id: SymExprRef) -> Option<Vec<Byte>> {
+        match self.terms.get(&id)? {
+            Term::Const { value, bits } if *bits % 8 == 0 => Some(
+                (0..*bits / 8)
+                    .rev()
+                    .map(|i| Byte::Const((*value >> (8 * i)) as u8))
+                    .collect(),
+            ),
+            Term::Symbolic(symbolic) if symbolic.ops.is_empty() => Some(symbolic.bytes.clone()),
+            _ => None,
+        }
+    }
+
+    fn binop(
+        &self,
+        a: SymExprRef,
+        b: SymExprRef,
+        fold: fn(u64, u64) -> Option<u64>,
+        op: Option<fn(u64) -> Op>,
+    ) -> Option<Term> {
+        match (self.terms.get(&a)?, self.terms.get(&b)?) {
+            (Term::Const { value: a, bits }, Term::Const { value: b, .. }) => Some(Term::Const {
+                value: fold(*a, *b)? & mask(*bits),
+                bits: *bits,
+            }),
+            (Term::Symbolic(sym), Term::Const { value, .. }) => {
+                Some(Term::Symbolic(sym.clone().push_op(op?(*value), sym.bits)))
+            }
+            // only used for commutative operations
+            (Term::Const { value, .. }, Term::Symbolic(sym)) => {
+                Some(Term::Symbolic(sym.clone().push_op(op?(*value), sym.bits)))
+            }
+            _ => None,
+        }
+    }
+
+    fn cmp(&self, a: SymExprRef, b: SymExprRef, kind: CmpKind) -> Option<Term> {
+        match (self.terms.get(&a)?, self.terms.get(&b)?) {
+            (Term::Const { value: a, bits }, Term::Const { value: b, .. }) => {
+                Some(Term::Bool(kind.eval(*a, *b, *bits)))
+            }
+            (Term::Symbolic(lhs), Term::Const { value, .. }) => Some(Term::Cmp {
+                kind,
+                lhs: lhs.clone(),
+                rhs: *value,
+            }),
+            (Term::Const { value, .. }, Term::Symbolic(rhs)) => Some(Term::Cmp {
+                kind: kind.mirror(),
+                lhs: rhs.clone(),
+                rhs: *value,
+            }),
+            _ => None,
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn cast(&self, op: SymExprRef, bits: u32, cast: fn(u32) -> Op) -> Option<Term> {
+        match self.terms.get(&op)? {
+            Term::Const { value, bits: from } => {
+                let value = match cast(*from) {
+                    Op::Sext(_) => sign_extend(*value, *from) as u64,
+                    _ => *value,
+                };
+                Some(Term::Const {
+                    value: value & mask(bits),
+                    bits,
+                })
+            }
+            Term::Symbolic(sym) => {
+                let mut sym = sym.clone();
+                match cast(sym.bits) {
+                    // operate directly on the bytes, if possible
+                    Op::Zext(_) if sym.ops.is_empty() && bits % 8 == 0 => {
+                        let mut bytes = vec![Byte::Const(0); ((bits - sym.bits) / 8) as usize];
+                        bytes.append(&mut sym.bytes);
+                        sym.bytes = bytes;
+                        sym.bits = bits;
+                        Some(Term::Symbolic(sym))
+                    }
+                    Op::Trunc(_) if sym.ops.is_empty() && bits % 8 == 0 && bits <= sym.bits => {
+                        let drop = ((sym.bits - bits) / 8) as usize;
+                        sym.bytes.drain(..drop);
+                        sym.bits = bits;
+                        Some(Term::Symbolic(sym))
+                    }
+                    op => Some(Term::Symbolic(sym.push_op(op, bits))),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn translate(&self, expr: &SymExpr) -> Option<Term> {
+        self.translate_expr(expr).filter(|term| match term {
+            Term::Const { bits, .. } | Term::Symbolic(Symbolic { bits, .. }) => {
+                *bits > 0 && *bits <= MAX_BITS
+            }
+            _ => true,
+        })
+    }
+
+    fn translate_expr(&self, expr: &SymExpr) -> Option<Term> {
+        match *expr {
+            SymExpr::InputByte { offset } => Some(Term::Symbolic(Symbolic {
+                bytes: vec![Byte::Input(offset)],
+                ops: Vec::new(),
+                bits: 8,
+            })),
+            SymExpr::Integer { value, bits } => Some(Term::Const {
+                value: value & mask(u32::from(bits)),
+                bits: u32::from(bits),
+            }),
+            SymExpr::NullPointer => Some(Term::Const {
+                value: 0,
+                bits: usize::BITS,
+            }),
+            SymExpr::True => Some(Term::Bool(true)),
+            SymExpr::False => Some(Term::Bool(false)),
+            SymExpr::Bool { value } => Some(Term::Bool(value)),
+            SymExpr::Neg { op } => self.neg(op),
+            SymExpr::Not { op } => self.not(op),
+            SymExpr::Add { a, b } => {
+                self.binop(a, b, |a, b| Some(a.wrapping_add(b)), Some(Op::Add))
+            }
+            SymExpr::Sub { a, b } => match (self.terms.get(&a)?, self.terms.get(&b)?) {
+                // k - x == -x + k
+                (Term::Const { value, .. }, Term::Symbolic(sym)) => Some(Term::Symbolic(
+                    sym.clone()
+                        .push_op(Op::Neg, sym.bits)
+                        .push_op(Op::Add(*value), sym.bits),
+                )),
+                _ => self.binop(
+                    a,
+                    b,
+                    |a, b| Some(a.wrapping_sub(b)),
+                    Some(|k| Op::Add(k.wrapping_neg())),
+                ),
+            },
+            SymExpr::Xor { a, b } => self.binop(a, b, |a, b| Some(a ^ b), Some(Op::Xor)),
+            SymExpr::Mul { a, b } => self.binop(a, b, |a, b| Some(a.wrapping_mul(b)), None),
+            SymExpr::UnsignedDiv { a, b } => self.binop(a, b, u64::checked_div, None),
+            SymExpr::UnsignedRem { a, b } => self.binop(a, b, u64::checked_rem, None),
+            SymExpr::And { a, b } => self.binop(a, b, |a, b| Some(a & b), None),
+            SymExpr::Or { a, b } => self.binop(a, b, |a, b| Some(a | b), None),
+            SymExpr::ShiftLeft { a, b } => {
+                self.binop(a, b, |a, b| a.checked_shl(b.try_into().ok()?), None)
+            }
+            SymExpr::LogicalShiftRight { a, b } => {
+                self.binop(a, b, |a, b| a.checked_shr(b.try_into().ok()?), None)
+            }
+            SymExpr::Equal { a, b } => self.cmp(a, b, CmpKind::Equal),
+            SymExpr::NotEqual { a, b } => self.cmp(a, b, CmpKind::NotEqual),
+            SymExpr::UnsignedLessThan { a, b } => self.cmp(a, b, CmpKind::UnsignedLessThan),
+            SymExpr::UnsignedLessEqual { a, b } => self.cmp(a, b, CmpKind::UnsignedLessEqual),
+            SymExpr::UnsignedGreaterThan { a, b } => self.cmp(a, b, CmpKind::UnsignedGreaterThan),
+            SymExpr::UnsignedGreaterEqual { a, b } => self.cmp(a, b, CmpKind::UnsignedGreaterEqual),
+            SymExpr::SignedLessThan { a, b } => self.cmp(a, b, CmpKind::SignedLessThan),
+            SymExpr::SignedLessEqual { a, b } => self.cmp(a, b, CmpKind::SignedLessEqual),
+            SymExpr::SignedGreaterThan { a, b } => self.cmp(a, b, CmpKind::SignedGreaterThan),
+            SymExpr::SignedGreaterEqual { a, b } => self.cmp(a, b, CmpKind::SignedGreaterEqual),
+            SymExpr::Zext { op, bits } => {
+                self.cast(op, self.width(op)? + u32::from(bits), Op::Zext)
+            }
+            SymExpr::Sext { op, bits } => {
+                self.cast(op, self.width(op)? + u32::from(bits), Op::Sext)
+            }
+            SymExpr::Trunc { op, bits } => self.cast(op, u32::from(bits), Op::Trunc),
+            SymExpr::Concat { a, b } => {
+                let mut bytes = self.bytes(a)?;
+                bytes.append(&mut self.bytes(b)?);
+                Some(Self::from_bytes(bytes))
+            }
+            SymExpr::Extract {
+                op,
+                first_bit,
+                last_bit,
+            } => self.extract(op, first_bit, last_bit),
+            _ => None,
+        }
+    }
+
+    fn neg(&self, op: SymExprRef) -> Option<Term> {
+        match self.terms.get(&op)? {
+            Term::Const { value, bits } => Some(Term::Const {
+                value: value.wrapping_neg() & mask(*bits),
+                bits: *bits,
+            }),
+            Term::Symbolic(sym) => Some(Term::Symbolic(sym.clone().push_op(Op::Neg, sym.bits))),
+            _ => None,
+        }
+    }
+
+    fn not(&self, op: SymExprRef) -> Option<Term> {
+        Some(match self.terms.get(&op)? {
+            Term::Const { value, bits } => Term::Const {
+                value: !value & mask(*bits),
+                bits: *bits,
+            },
+            Term::Symbolic(sym) => {
+                Term::Symbolic(sym.clone().push_op(Op::Xor(mask(sym.bits)), sym.bits))
+            }
+            Term::Bool(value) => Term::Bool(!value),
+            Term::Cmp { kind, lhs, rhs } => Term::Cmp {
+                kind: kind.negate(),
+                lhs: lhs.clone(),
+                rhs: *rhs,
+            },
+        })
+    }
+
+    /// The bits `first_bit..=last_bit` of `op`, on byte boundaries for symbolic bitvectors
+    fn extract(&self, op: SymExprRef, first_bit: usize, last_bit: usize) -> Option<Term> {
+        let bits = u32::try_from(first_bit.checked_sub(last_bit)? + 1).ok()?;
+        if let Some((value, _)) = self.constant(op) {
+            return Some(Term::Const {
+                value: value.checked_shr(last_bit as u32).unwrap_or(0) & mask(bits),
+                bits,
+            });
+        }
+        if last_bit % 8 != 0 || bits % 8 != 0 {
+            return None;
+        }
+        let bytes = self.bytes(op)?;
+        let width = bytes.len() * 8;
+        let start = width.checked_sub(first_bit + 1)? / 8;
+        let end = (width - 1 - last_bit) / 8;
+        Some(Self::from_bytes(bytes.get(start..=end)?.to_vec()))
+    }
+
+    fn width(&self, id: SymExprRef) -> Option<u32> {
+        match self.terms.get(&id)? {
+            Term::Const { bits, .. } | Term::Symbolic(Symbolic { bits, .. }) => Some(*bits),
+            _ => None,
+        }
+    }
+
+    /// A term from bytes, folded to a constant if none of them are input bytes
+    fn from_bytes(bytes: Vec<Byte>) -> Term {
+        let bits = bytes.len() as u32 * 8;
+        if bits <= MAX_BITS && bytes.iter().all(|b| matches!(b, Byte::Const(_))) {
+            let value = bytes.iter().fold(0_u64, |acc, b| match b {
+                Byte::Const(c) => (acc << 8) | u64::from(*c),
+                Byte::Input(_) => unreachable!(),
+            });
+            Term::Const { value, bits }
+        } else {
+            Term::Symbolic(Symbolic {
+                bytes,
+                ops: Vec::new(),
+                bits,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{SimpleSolution, SimpleSolver};
+    use crate::observers::concolic::{Location, SymExpr, SymExprRef};
+
+    fn run(exprs: Vec<SymExpr>) -> Vec<Option<SimpleSolution>> {
+        let mut solver = SimpleSolver::new();
+        exprs
+            .iter()
+            .enumerate()
+            .map(|(i, expr)| solver.add(SymExprRef::new(i + 1).unwrap(), expr))
+            .collect()
+    }
+
+    fn id(i: usize) -> SymExprRef {
+        SymExprRef::new(i).unwrap()
+    }
+
+    #[test]
+    fn test_simple_solver_magic_bytes() {
+        // (input[1] ++ input[0]) + 2 == 0x4143, not taken
+        let res = run(vec![
+            SymExpr::InputByte { offset: 0 },
+            SymExpr::InputByte { offset: 1 },
+            SymExpr::Concat { a: id(2), b: id(1) },
+            SymExpr::Integer { value: 2, bits: 16 },
+            SymExpr::Add { a: id(3), b: id(4) },
+            SymExpr::Integer {
+                value: 0x4143,
+                bits: 16,
+            },
+            SymExpr::Equal { a: id(5), b: id(6) },
+            SymExpr::PathConstraint {
+                constraint: id(7),
+                taken: false,
+                location: Location::from(0),
+            },
+        ]);
+        assert_eq!(
+            res[7],
+            Some(SimpleSolution::Solved(vec![(1, 0x41), (0, 0x41)]))
+        );
+    }
+
+    #[test]
+    fn test_simple_solver_casts() {
+        // zext(input[3]) <u 0x10, taken
+        let res = run(vec![
+            SymExpr::InputByte { offset: 3 },
+            SymExpr::Zext {
+                op: id(1),
+                bits: 24,
+            },
+            SymExpr::Integer {
+                value: 0x10,
+                bits: 32,
+            },
+            SymExpr::UnsignedLessThan { a: id(2), b: id(3) },
+            SymExpr::PathConstraint {
+                constraint: id(4),
+                taken: true,
+                location: Location::from(0),
+            },
+            // zext(input[3]) == 0x100 can never hold
+            SymExpr::Integer {
+                value: 0x100,
+                bits: 32,
+            },
+            SymExpr::Equal { a: id(2), b: id(6) },
+            SymExpr::PathConstraint {
+                constraint: id(7),
+                taken: false,
+                location: Location::from(0),
+            },
+            // floats are not supported
+            SymExpr::Float {
+                value: 1.0,
+                is_double: true,
+            },
+            SymExpr::FloatOrderedEqual { a: id(9), b: id(9) },
+            SymExpr::PathConstraint {
+                constraint: id(10),
+                taken: false,
+                location: Location::from(0),
+            },
+        ]);
+        assert_eq!(res[4], Some(SimpleSolution::Solved(vec![(3, 0x10)])));
+        assert_eq!(res[7], Some(SimpleSolution::Unsat));
+        assert_eq!(res[10], Some(SimpleSolution::Unsupported));
+    }
+
+    #[test]
+    fn test_simple_solver_witness_conflict() {
+        // zext(input[3]) + 0x100 >=u 0x10, not taken
+        let res = run(vec![
+            SymExpr::InputByte { offset: 3 },
+            SymExpr::Zext {
+                op: id(1),
+                bits: 24,
+            },
+            SymExpr::Integer {
+                value: 0x100,
+                bits: 32,
+            },
+            SymExpr::Add { a: id(2), b: id(3) },
+            SymExpr::Integer {
+                value: 0x10,
+                bits: 32,
+            },
+            SymExpr::UnsignedGreaterEqual { a: id(4), b: id(5) },
+            SymExpr::PathConstraint {
+                constraint: id(6),
+                taken: false,
+                location: Location::from(0),
+            },
+        ]);
+        // The witness 0x10 can't be reached, but other values can, so it is not unsat
+        assert_eq!(res[6], Some(SimpleSolution::Unsupported));
+    }
+}
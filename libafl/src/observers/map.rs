@@ -18,7 +18,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     bolts::{
-        ownedref::{OwnedRefMut, OwnedSliceMut},
+        ownedref::{OwnedPtrMut, OwnedRefMut, OwnedSliceMut},
+        simd::{classify_counts, COUNT_CLASS_LOOKUP},
         tuples::Named,
         AsMutIterator, AsMutSlice, AsRefIterator, AsSlice, HasLen,
     },
@@ -82,6 +83,21 @@ pub trait MapObserver: HasLen + Named + Serialize + serde::de::DeserializeOwned
 
     /// Get the number of set entries with the specified indexes
     fn how_many_set(&self, indexes: &[usize]) -> usize;
+
+    /// Get the indexes of the entries touched in the last run, if this observer tracks them.
+    /// If `Some`, all the other entries hold the initial value, so that feedbacks and
+    /// post-processing can skip them instead of scanning the whole map.
+    #[inline]
+    fn touched_indexes(&self) -> Option<&[usize]> {
+        None
+    }
+
+    /// Get the indexes of the entries touched in the last run together with the mutable map,
+    /// to post-process the touched entries in place. `None` if [`Self::touched_indexes`] is `None`.
+    #[inline]
+    fn touched_indexes_with_map_mut(&mut self) -> Option<(&[usize], &mut [Self::Entry])> {
+        None
+    }
 }
 
 /// A Simple iterator calling `MapObserver::get`
@@ -984,49 +1000,337 @@ where
     }
 }
 
-/// Map observer with hitcounts postprocessing
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(bound = "M: serde::de::DeserializeOwned")]
-pub struct HitcountsMapObserver<M>
+/// A map observer for maps whose instrumentation also records the indexes it touches,
+/// such as the `libafl_targets` edges map with the `touched_indexes` feature.
+/// The instrumentation appends each index to the `touched` list the first time the entry
+/// changes from the initial value, and increments `touched_len`. Each index must be recorded at most
+/// once per run, so hitcounts must not wrap around to the initial value. If more indexes are touched
+/// than fit into the list, `touched_len` grows beyond its length and the whole map is scanned.
+///
+/// Feedbacks, the [`HitcountsMapObserver`] and [`MapObserver::reset_map`] then only visit
+/// the touched entries, instead of the whole map, on each execution.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound = "T: serde::de::DeserializeOwned")]
+#[allow(clippy::unsafe_derive_deserialize)]
+pub struct TouchedMapObserver<'a, T>
 where
-    M: Serialize,
+    T: Default + Copy + 'static + Serialize,
 {
-    base: M,
+    map: OwnedSliceMut<'a, T>,
+    touched: OwnedSliceMut<'a, usize>,
+    /// Written by the instrumentation, so only accessed through a pointer
+    touched_len: OwnedPtrMut<usize>,
+    initial: T,
+    name: String,
 }
 
-static COUNT_CLASS_LOOKUP: [u8; 256] = [
-    0, 1, 2, 4, 8, 8, 8, 8, 16, 16, 16, 16, 16, 16, 16, 16, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32,
-    32, 32, 32, 32, 32, 32, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
-    64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
-    64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
-    64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
-    64, 64, 64, 64, 64, 64, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
-    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
-    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
-    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
-    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
-    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
-    128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128,
-];
-
-static mut COUNT_CLASS_LOOKUP_16: Vec<u16> = vec![];
-
-fn init_count_class_16() {
-    unsafe {
-        if !COUNT_CLASS_LOOKUP_16.is_empty() {
-            return;
+impl<'a, I, S, T> Observer<I, S> for TouchedMapObserver<'a, T>
+where
+    T: Bounded
+        + PartialEq
+        + Default
+        + Copy
+        + 'static
+        + Serialize
+        + serde::de::DeserializeOwned
+        + Debug,
+{
+    #[inline]
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.reset_map()
+    }
+}
+
+impl<'a, T> Named for TouchedMapObserver<'a, T>
+where
+    T: Default + Copy + 'static + Serialize + serde::de::DeserializeOwned,
+{
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl<'a, T> HasLen for TouchedMapObserver<'a, T>
+where
+    T: Default + Copy + 'static + Serialize + serde::de::DeserializeOwned,
+{
+    #[inline]
+    fn len(&self) -> usize {
+        self.map.as_slice().len()
+    }
+}
+
+impl<'a, 'it, T> AsRefIterator<'it> for TouchedMapObserver<'a, T>
+where
+    T: Bounded
+        + PartialEq
+        + Default
+        + Copy
+        + 'static
+        + Serialize
+        + serde::de::DeserializeOwned
+        + Debug,
+{
+    type Item = T;
+    type IntoIter = Iter<'it, T>;
+
+    fn as_ref_iter(&'it self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+impl<'a, 'it, T> AsMutIterator<'it> for TouchedMapObserver<'a, T>
+where
+    T: Bounded
+        + PartialEq
+        + Default
+        + Copy
+        + 'static
+        + Serialize
+        + serde::de::DeserializeOwned
+        + Debug,
+{
+    type Item = T;
+    type IntoIter = IterMut<'it, T>;
+
+    fn as_mut_iter(&'it mut self) -> Self::IntoIter {
+        self.as_mut_slice().iter_mut()
+    }
+}
+
+impl<'a, 'it, T> IntoIterator for &'it TouchedMapObserver<'a, T>
+where
+    T: Bounded
+        + PartialEq
+        + Default
+        + Copy
+        + 'static
+        + Serialize
+        + serde::de::DeserializeOwned
+        + Debug,
+{
+    type Item = <Iter<'it, T> as Iterator>::Item;
+    type IntoIter = Iter<'it, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+impl<'a, 'it, T> IntoIterator for &'it mut TouchedMapObserver<'a, T>
+where
+    T: Bounded
+        + PartialEq
+        + Default
+        + Copy
+        + 'static
+        + Serialize
+        + serde::de::DeserializeOwned
+        + Debug,
+{
+    type Item = <IterMut<'it, T> as Iterator>::Item;
+    type IntoIter = IterMut<'it, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_mut_slice().iter_mut()
+    }
+}
+
+impl<'a, T> MapObserver for TouchedMapObserver<'a, T>
+where
+    T: Bounded
+        + PartialEq
+        + Default
+        + Copy
+        + 'static
+        + Serialize
+        + serde::de::DeserializeOwned
+        + Debug,
+{
+    type Entry = T;
+
+    #[inline]
+    fn get(&self, pos: usize) -> &T {
+        &self.as_slice()[pos]
+    }
+
+    #[inline]
+    fn get_mut(&mut self, idx: usize) -> &mut T {
+        &mut self.as_mut_slice()[idx]
+    }
+
+    /// Count the set bytes in the map
+    fn count_bytes(&self) -> u64 {
+        let initial = self.initial();
+        let map = self.as_slice();
+        match self.touched_indexes() {
+            Some(touched) => touched.iter().filter(|i| map[**i] != initial).count() as u64,
+            None => map.iter().filter(|x| **x != initial).count() as u64,
+        }
+    }
+
+    #[inline]
+    fn usable_count(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn hash(&self) -> u64 {
+        hash_slice(self.as_slice())
+    }
+
+    #[inline]
+    fn initial(&self) -> T {
+        self.initial
+    }
+
+    #[inline]
+    fn initial_mut(&mut self) -> &mut T {
+        &mut self.initial
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.as_slice().to_vec()
+    }
+
+    /// Reset the touched entries, or the whole map if the touched list overflowed
+    #[inline]
+    fn reset_map(&mut self) -> Result<(), Error> {
+        let initial = self.initial();
+        let len = *self.touched_len.as_ref();
+        let touched = self.touched.as_slice();
+        let map = self.map.as_mut_slice();
+        if len <= touched.len() {
+            for i in &touched[..len] {
+                map[*i] = initial;
+            }
+        } else {
+            for x in map.iter_mut() {
+                *x = initial;
+            }
         }
+        *self.touched_len.as_mut() = 0;
+        Ok(())
+    }
 
-        COUNT_CLASS_LOOKUP_16 = vec![0; 65536];
-        for i in 0..256 {
-            for j in 0..256 {
-                COUNT_CLASS_LOOKUP_16[(i << 8) + j] =
-                    (u16::from(COUNT_CLASS_LOOKUP[i]) << 8) | u16::from(COUNT_CLASS_LOOKUP[j]);
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        let initial = self.initial();
+        let map = self.as_slice();
+        let cnt = map.len();
+        let mut res = 0;
+        for i in indexes {
+            if *i < cnt && map[*i] != initial {
+                res += 1;
             }
         }
+        res
+    }
+
+    #[inline]
+    fn touched_indexes(&self) -> Option<&[usize]> {
+        let len = *self.touched_len.as_ref();
+        let touched = self.touched.as_slice();
+        if len <= touched.len() {
+            Some(&touched[..len])
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn touched_indexes_with_map_mut(&mut self) -> Option<(&[usize], &mut [T])> {
+        let len = *self.touched_len.as_ref();
+        let touched = self.touched.as_slice();
+        if len <= touched.len() {
+            Some((&touched[..len], self.map.as_mut_slice()))
+        } else {
+            None
+        }
     }
 }
 
+impl<'a, T> AsSlice<T> for TouchedMapObserver<'a, T>
+where
+    T: Default + Copy + 'static + Serialize + serde::de::DeserializeOwned + Debug,
+{
+    #[must_use]
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self.map.as_slice()
+    }
+}
+impl<'a, T> AsMutSlice<T> for TouchedMapObserver<'a, T>
+where
+    T: Default + Copy + 'static + Serialize + serde::de::DeserializeOwned + Debug,
+{
+    #[must_use]
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self.map.as_mut_slice()
+    }
+}
+
+impl<'a, T> TouchedMapObserver<'a, T>
+where
+    T: Default + Copy + 'static + Serialize + serde::de::DeserializeOwned,
+{
+    /// Creates a new [`TouchedMapObserver`], for a `map` whose instrumentation writes the touched
+    /// indexes into `touched` and their count into `touched_len`
+    #[must_use]
+    pub fn new<S>(
+        name: S,
+        map: &'a mut [T],
+        touched: &'a mut [usize],
+        touched_len: &'a mut usize,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            map: OwnedSliceMut::from(map),
+            touched: OwnedSliceMut::from(touched),
+            touched_len: OwnedPtrMut::Ptr(touched_len),
+            name: name.into(),
+            initial: T::default(),
+        }
+    }
+
+    /// Creates a new [`TouchedMapObserver`] from raw pointers
+    ///
+    /// # Safety
+    /// Dereferences `map_ptr` with up to `len` elements, `touched_ptr` with up to `touched_max` elements,
+    /// and `touched_len`, which must stay valid for the lifetime of the observer.
+    pub unsafe fn new_from_ptr<S>(
+        name: S,
+        map_ptr: *mut T,
+        len: usize,
+        touched_ptr: *mut usize,
+        touched_max: usize,
+        touched_len: *mut usize,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            map: OwnedSliceMut::from_raw_parts_mut(map_ptr, len),
+            touched: OwnedSliceMut::from_raw_parts_mut(touched_ptr, touched_max),
+            touched_len: OwnedPtrMut::Ptr(touched_len),
+            name: name.into(),
+            initial: T::default(),
+        }
+    }
+}
+
+/// Map observer with hitcounts postprocessing
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound = "M: serde::de::DeserializeOwned")]
+pub struct HitcountsMapObserver<M>
+where
+    M: Serialize,
+{
+    base: M,
+}
+
 impl<I, S, M> Observer<I, S> for HitcountsMapObserver<M>
 where
     M: MapObserver<Entry = u8> + Observer<I, S> + AsMutSlice<u8>,
@@ -1038,24 +1342,16 @@ where
     }
 
     #[inline]
-    #[allow(clippy::cast_ptr_alignment)]
     fn post_exec(&mut self, state: &mut S, input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
-        let map = self.as_mut_slice();
-        let len = map.len();
-        if (len & 1) != 0 {
-            unsafe {
-                *map.get_unchecked_mut(len - 1) =
-                    *COUNT_CLASS_LOOKUP.get_unchecked(*map.get_unchecked(len - 1) as usize);
+        if let Some((touched, map)) = self.base.touched_indexes_with_map_mut() {
+            // Only classify the touched entries, the others are zero
+            for &idx in touched {
+                map[idx] = COUNT_CLASS_LOOKUP[map[idx] as usize];
             }
+            return self.base.post_exec(state, input, exit_kind);
         }
 
-        let cnt = len / 2;
-        let map16 = unsafe { core::slice::from_raw_parts_mut(map.as_mut_ptr() as *mut u16, cnt) };
-        for (_i, item) in map16[0..cnt].iter_mut().enumerate() {
-            unsafe {
-                *item = *COUNT_CLASS_LOOKUP_16.get_unchecked(*item as usize);
-            }
-        }
+        classify_counts(self.as_mut_slice());
         self.base.post_exec(state, input, exit_kind)
     }
 }
@@ -1133,6 +1429,16 @@ where
     fn how_many_set(&self, indexes: &[usize]) -> usize {
         self.base.how_many_set(indexes)
     }
+
+    #[inline]
+    fn touched_indexes(&self) -> Option<&[usize]> {
+        self.base.touched_indexes()
+    }
+
+    #[inline]
+    fn touched_indexes_with_map_mut(&mut self) -> Option<(&[usize], &mut [u8])> {
+        self.base.touched_indexes_with_map_mut()
+    }
 }
 
 impl<M> AsSlice<u8> for HitcountsMapObserver<M>
@@ -1160,7 +1466,6 @@ where
 {
     /// Creates a new [`MapObserver`]
     pub fn new(base: M) -> Self {
-        init_count_class_16();
         Self { base }
     }
 }
@@ -9,7 +9,7 @@ pub use cmp::*;
 #[cfg(feature = "std")]
 pub mod stdio;
 #[cfg(feature = "std")]
-pub use stdio::{StdErrObserver, StdOutObserver};
+pub use stdio::{ExitStatusObserver, StdErrObserver, StdOutObserver};
 
 #[cfg(feature = "std")]
 pub mod stacktrace;
@@ -183,6 +183,13 @@ pub trait ObserverWithHashField {
     /// clears the current value of the hash and sets it to None
     fn clear_hash(&mut self);
 }
+
+/// A trait for observers that check the target for memory leaks after each run
+pub trait ObserverWithLeakCheck {
+    /// `true` if memory leaked during the last run
+    fn leaked(&self) -> bool;
+}
+
 /// A simple observer, just overlooking the runtime of the target.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TimeObserver {
@@ -1,4 +1,5 @@
-//! The [`StdOutObserver`] and [`StdErrObserver`] observers look at the stdout of a program
+//! The [`StdOutObserver`] and [`StdErrObserver`] observers look at the stdout of a program,
+//! the [`ExitStatusObserver`] at how it exited.
 //! The executor must explicitely support these observers.
 //! For example, they are supported on the [`crate::executors::CommandExecutor`].
 
@@ -59,3 +60,35 @@ impl Named for StdErrObserver {
         &self.name
     }
 }
+
+/// An observer that captures the exit status of a target.
+/// Only works for supported executors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExitStatusObserver {
+    /// The name of the observer.
+    pub name: String,
+    /// The exit code of the target during its last execution, if it exited.
+    pub code: Option<i32>,
+    /// The signal that terminated the target during its last execution, if any.
+    pub signal: Option<i32>,
+}
+
+impl ExitStatusObserver {
+    /// Create a new [`ExitStatusObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            code: None,
+            signal: None,
+        }
+    }
+}
+
+impl<I, S> Observer<I, S> for ExitStatusObserver {}
+
+impl Named for ExitStatusObserver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -0,0 +1,435 @@
+//! The entropy based corpus scheduler from Entropic (`https://mboehme.github.io/paper/FSE20.Entropy.pdf`),
+//! as implemented in libFuzzer.
+//! Each corpus entry keeps track of how often the rare features have been hit by the inputs derived from it.
+//! Entries that reveal a lot of information about the rare features get a high entropy, and are fuzzed more often.
+//! The scheduler needs a map feedback that tracks indexes or novelties, i.e. adds
+//! [`MapIndexesMetadata`] or [`MapNoveltiesMetadata`] to new corpus entries, and the
+//! [`crate::feedbacks::EntropicFeedback`] to count the rare features hit by each execution.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::rands::Rand,
+    corpus::{Corpus, SchedulerTestcaseMetaData},
+    feedbacks::{MapIndexesMetadata, MapNoveltiesMetadata},
+    inputs::Input,
+    schedulers::{powersched::SchedulerMetadata, Scheduler},
+    state::{HasCorpus, HasExecutions, HasMetadata, HasRand},
+    Error,
+};
+
+/// The default maximum number of rare features tracked by the [`EntropicScheduler`], as in libFuzzer
+pub const DEFAULT_MAX_RARE_FEATURES: usize = 100;
+/// The default amount of corpus entries a feature may appear in before it's no longer considered rare
+pub const DEFAULT_RARE_FEATURE_THRESHOLD: u64 = 0xFF;
+
+/// The state metadata of the [`EntropicScheduler`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EntropicMetadata {
+    /// The global frequencies of the currently rare features
+    rare_features: HashMap<usize, u64>,
+    /// Features that have been rare once, but are abundant now
+    abundant_features: HashSet<usize>,
+    /// The energy of each corpus entry
+    energies: Vec<f64>,
+    /// The last selected corpus entry
+    last_idx: Option<usize>,
+    /// The executions of the fuzzer when the last corpus entry was selected
+    last_executions: usize,
+}
+
+crate::impl_serdeany!(EntropicMetadata);
+
+impl EntropicMetadata {
+    /// Creates a new [`struct@EntropicMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The global frequencies of the currently rare features
+    #[must_use]
+    pub fn rare_features(&self) -> &HashMap<usize, u64> {
+        &self.rare_features
+    }
+
+    /// The energy of each corpus entry
+    #[must_use]
+    pub fn energies(&self) -> &[f64] {
+        &self.energies
+    }
+}
+
+/// The testcase metadata of the [`EntropicScheduler`], the local feature frequency table of a corpus entry
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EntropicTestcaseMetadata {
+    /// How often the features have been hit by inputs derived from this entry
+    feature_freqs: HashMap<usize, u64>,
+    /// The amount of mutated inputs derived from this entry
+    executed_mutations: u64,
+}
+
+crate::impl_serdeany!(EntropicTestcaseMetadata);
+
+impl EntropicTestcaseMetadata {
+    /// Creates a new [`struct@EntropicTestcaseMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How often the features have been hit by inputs derived from this entry
+    #[must_use]
+    pub fn feature_freqs(&self) -> &HashMap<usize, u64> {
+        &self.feature_freqs
+    }
+
+    /// The amount of mutated inputs derived from this entry
+    #[must_use]
+    pub fn executed_mutations(&self) -> u64 {
+        self.executed_mutations
+    }
+
+    /// Records that an input derived from this entry hit the given feature
+    pub fn add_feature_hit(&mut self, feature: usize) {
+        *self.feature_freqs.entry(feature).or_insert(0) += 1;
+    }
+
+    /// Computes the entropy of this entry over the given rare features, see `InputInfo::UpdateEnergy` in libFuzzer
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn entropy(&self, rare_features: &HashMap<usize, u64>) -> f64 {
+        let mut energy = 0.0;
+        let mut sum_incidence = 0.0;
+        let mut local_features = 0;
+
+        for (feature, freq) in &self.feature_freqs {
+            if rare_features.contains_key(feature) {
+                let freq = (*freq + 1) as f64;
+                energy -= freq * freq.ln();
+                sum_incidence += freq;
+                local_features += 1;
+            }
+        }
+
+        // Rare features that were never hit from this entry count once
+        sum_incidence += (rare_features.len() - local_features) as f64;
+        // Each mutation that didn't hit a rare feature hit an abundant one
+        let abundance = (self.executed_mutations + 1) as f64;
+        energy -= abundance * abundance.ln();
+        sum_incidence += abundance;
+
+        energy / sum_incidence + sum_incidence.ln()
+    }
+}
+
+/// The Entropic scheduler, choosing corpus entries proportionally to the entropy of their rare feature frequencies.
+/// It attaches the [`SchedulerTestcaseMetaData`] to new entries, so it can be used with the `PowerMutationalStage`.
+/// The local feature frequencies are updated on each execution by the [`crate::feedbacks::EntropicFeedback`].
+#[derive(Debug, Clone)]
+pub struct EntropicScheduler<I, S> {
+    max_rare_features: usize,
+    rare_feature_threshold: u64,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<I, S> Default for EntropicScheduler<I, S>
+where
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand + HasExecutions,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, S> EntropicScheduler<I, S>
+where
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand + HasExecutions,
+{
+    /// Creates a new [`EntropicScheduler`] with the libFuzzer defaults
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_params(DEFAULT_MAX_RARE_FEATURES, DEFAULT_RARE_FEATURE_THRESHOLD)
+    }
+
+    /// Creates a new [`EntropicScheduler`], tracking up to `max_rare_features` features that appear in
+    /// less than `rare_feature_threshold` corpus entries
+    #[must_use]
+    pub fn with_params(max_rare_features: usize, rare_feature_threshold: u64) -> Self {
+        Self {
+            max_rare_features,
+            rare_feature_threshold,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Updates the global rare features with the features of a new corpus entry.
+    /// Returns if the set of rare features changed.
+    fn update_rare_features(&self, meta: &mut EntropicMetadata, features: &[usize]) -> bool {
+        let mut changed = false;
+        for feature in features {
+            if meta.abundant_features.contains(feature) {
+                continue;
+            }
+            let freq = meta.rare_features.entry(*feature).or_insert_with(|| {
+                changed = true;
+                0
+            });
+            *freq += 1;
+            if *freq >= self.rare_feature_threshold {
+                meta.rare_features.remove(feature);
+                meta.abundant_features.insert(*feature);
+                changed = true;
+            }
+        }
+
+        // Too many rare features, evict the most abundant ones
+        while meta.rare_features.len() > self.max_rare_features {
+            let feature = meta
+                .rare_features
+                .iter()
+                .max_by_key(|(feature, freq)| (**freq, **feature))
+                .map(|(feature, _)| *feature)
+                .unwrap();
+            meta.rare_features.remove(&feature);
+            meta.abundant_features.insert(feature);
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// Recomputes the energy of a corpus entry
+    fn update_energy(state: &mut S, idx: usize) -> Result<(), Error> {
+        let energy = {
+            let meta = state
+                .metadata()
+                .get::<EntropicMetadata>()
+                .ok_or_else(|| Error::key_not_found("EntropicMetadata not found".to_string()))?;
+            let testcase = state.corpus().get(idx)?.borrow();
+            match testcase.metadata().get::<EntropicTestcaseMetadata>() {
+                Some(tcmeta) => tcmeta.entropy(&meta.rare_features),
+                None => 0.0,
+            }
+        };
+
+        let meta = state
+            .metadata_mut()
+            .get_mut::<EntropicMetadata>()
+            .ok_or_else(|| Error::key_not_found("EntropicMetadata not found".to_string()))?;
+        if meta.energies.len() <= idx {
+            meta.energies.resize(idx + 1, 0.0);
+        }
+        meta.energies[idx] = energy;
+        Ok(())
+    }
+
+    /// Credits the mutations executed since the last selection to the last selected corpus entry
+    fn update_last_selected(state: &mut S) -> Result<(), Error> {
+        let executions = *state.executions();
+        let meta = state
+            .metadata_mut()
+            .get_mut::<EntropicMetadata>()
+            .ok_or_else(|| Error::key_not_found("EntropicMetadata not found".to_string()))?;
+        let last_executions = meta.last_executions;
+        meta.last_executions = executions;
+
+        if let Some(last_idx) = meta.last_idx.take() {
+            if last_idx < state.corpus().count() {
+                if let Some(tcmeta) = state
+                    .corpus()
+                    .get(last_idx)?
+                    .borrow_mut()
+                    .metadata_mut()
+                    .get_mut::<EntropicTestcaseMetadata>()
+                {
+                    tcmeta.executed_mutations += executions.saturating_sub(last_executions) as u64;
+                }
+                Self::update_energy(state, last_idx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<I, S> Scheduler<I, S> for EntropicScheduler<I, S>
+where
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand + HasExecutions,
+{
+    /// Add an entry to the corpus and return its index
+    fn on_add(&self, state: &mut S, idx: usize) -> Result<(), Error> {
+        if !state.has_metadata::<SchedulerMetadata>() {
+            state.add_metadata(SchedulerMetadata::new(None));
+        }
+
+        if !state.has_metadata::<EntropicMetadata>() {
+            state.add_metadata(EntropicMetadata::new());
+        }
+
+        let current_idx = *state.corpus().current();
+
+        let depth = match current_idx {
+            Some(parent_idx) => state
+                .corpus()
+                .get(parent_idx)?
+                .borrow()
+                .metadata()
+                .get::<SchedulerTestcaseMetaData>()
+                .map_or(0, SchedulerTestcaseMetaData::depth),
+            None => 0,
+        };
+
+        let features = {
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            testcase.add_metadata(SchedulerTestcaseMetaData::new(depth + 1));
+            testcase.add_metadata(EntropicTestcaseMetadata::new());
+            if let Some(meta) = testcase.metadata().get::<MapIndexesMetadata>() {
+                meta.list.clone()
+            } else if let Some(meta) = testcase.metadata().get::<MapNoveltiesMetadata>() {
+                meta.list.clone()
+            } else {
+                vec![]
+            }
+        };
+
+        let changed = {
+            let meta = state
+                .metadata_mut()
+                .get_mut::<EntropicMetadata>()
+                .ok_or_else(|| Error::key_not_found("EntropicMetadata not found".to_string()))?;
+            self.update_rare_features(meta, &features)
+        };
+
+        if changed {
+            // The entropy of all entries depends on the set of rare features
+            for i in 0..state.corpus().count() {
+                Self::update_energy(state, i)?;
+            }
+        } else {
+            Self::update_energy(state, idx)?;
+            if let Some(parent_idx) = current_idx {
+                if parent_idx < state.corpus().count() {
+                    Self::update_energy(state, parent_idx)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the next entry, sampled proportionally to its entropy
+    #[allow(clippy::cast_precision_loss, clippy::cast_sign_loss)]
+    fn next(&self, state: &mut S) -> Result<usize, Error> {
+        let corpus_counts = state.corpus().count();
+        if corpus_counts == 0 {
+            return Err(Error::empty(String::from("No entries in corpus")));
+        }
+        if !state.has_metadata::<EntropicMetadata>() {
+            state.add_metadata(EntropicMetadata::new());
+        }
+
+        Self::update_last_selected(state)?;
+
+        // Choose a random value between 0.000000000 and 1.000000000
+        let probability = state.rand_mut().between(0, 1000000000) as f64 / 1000000000_f64;
+
+        let meta = state
+            .metadata_mut()
+            .get_mut::<EntropicMetadata>()
+            .ok_or_else(|| Error::key_not_found("EntropicMetadata not found".to_string()))?;
+        let energies = &meta.energies[..corpus_counts.min(meta.energies.len())];
+        let total: f64 = energies.iter().map(|e| e.max(0.0)).sum();
+
+        let idx = if total > 0.0 {
+            let threshold = total * probability;
+            let mut acc = 0.0;
+            let mut idx = energies.len() - 1;
+            for (i, energy) in energies.iter().enumerate() {
+                acc += energy.max(0.0);
+                if acc >= threshold {
+                    idx = i;
+                    break;
+                }
+            }
+            idx
+        } else {
+            // No information yet, choose uniformly
+            ((probability * corpus_counts as f64) as usize).min(corpus_counts - 1)
+        };
+
+        meta.last_idx = Some(idx);
+        *state.corpus_mut().current_mut() = Some(idx);
+        Ok(idx)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use hashbrown::HashMap;
+
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::MapIndexesMetadata,
+        inputs::bytes::BytesInput,
+        schedulers::{
+            entropic::{EntropicMetadata, EntropicTestcaseMetadata},
+            EntropicScheduler, Scheduler,
+        },
+        state::{HasCorpus, HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_entropy() {
+        let mut rare = HashMap::new();
+        rare.insert(1, 1);
+        rare.insert(2, 1);
+
+        // A fresh entry has the maximum entropy
+        let fresh = EntropicTestcaseMetadata::new();
+        assert!((fresh.entropy(&rare) - 3.0_f64.ln()).abs() < 1e-9);
+
+        let mut fuzzed = EntropicTestcaseMetadata::new();
+        fuzzed.feature_freqs.insert(1, 100);
+        fuzzed.executed_mutations = 1000;
+        assert!(fuzzed.entropy(&rare) < fresh.entropy(&rare));
+    }
+
+    #[test]
+    fn test_entropic_scheduler() {
+        let scheduler = EntropicScheduler::new();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        for i in 0..3_usize {
+            let mut testcase = Testcase::new(BytesInput::new(vec![i as u8; 4]));
+            testcase.add_metadata(MapIndexesMetadata::new(vec![0, i + 1]));
+            let idx = state.corpus_mut().add(testcase).unwrap();
+            scheduler.on_add(&mut state, idx).unwrap();
+        }
+
+        let meta = state.metadata().get::<EntropicMetadata>().unwrap();
+        assert_eq!(meta.rare_features().len(), 4);
+        assert_eq!(meta.energies().len(), 3);
+
+        let idx = scheduler.next(&mut state).unwrap();
+        assert!(idx < 3);
+        assert_eq!(*state.corpus().current(), Some(idx));
+    }
+}
@@ -0,0 +1,245 @@
+//! The [`MaxHitCountScheduler`] favors the corpus entries holding the maximum hit count of an edge, as in `PerfFuzz`.
+//! It needs the [`struct@HitCountsMetadata`] of the `MaxHitCountFeedback`.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::rands::Rand,
+    corpus::{Corpus, Testcase},
+    feedbacks::perf::HitCountsMetadata,
+    inputs::Input,
+    schedulers::{minimizer::IsFavoredMetadata, Scheduler},
+    state::{HasCorpus, HasMetadata, HasRand},
+    Error,
+};
+
+/// Default probability to skip the non-favored values
+pub const DEFAULT_SKIP_NON_FAVORED_PROB: u64 = 95;
+
+/// The state metadata of the [`MaxHitCountScheduler`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MaxHitCountTopRatedMetadata {
+    /// The corpus entry with the maximum hit count, and the count, for each edge
+    pub top_rated: HashMap<usize, (usize, u64)>,
+    /// The amount of edges each favored entry holds the maximum of
+    pub favored: HashMap<usize, usize>,
+}
+
+crate::impl_serdeany!(MaxHitCountTopRatedMetadata);
+
+impl MaxHitCountTopRatedMetadata {
+    /// Creates a new [`struct@MaxHitCountTopRatedMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// The [`MaxHitCountScheduler`] marks each corpus entry that holds the maximum hit count of at least one edge
+/// as favored, and skips the other entries of the base scheduler with a high probability.
+#[derive(Debug, Clone)]
+pub struct MaxHitCountScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    base: CS,
+    skip_non_favored_prob: u64,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<CS, I, S> Scheduler<I, S> for MaxHitCountScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    /// Add an entry to the corpus and return its index
+    fn on_add(&self, state: &mut S, idx: usize) -> Result<(), Error> {
+        self.update_top_rated(state, idx)?;
+        self.base.on_add(state, idx)
+    }
+
+    /// Replaces the testcase at the given idx
+    fn on_replace(&self, state: &mut S, idx: usize, testcase: &Testcase<I>) -> Result<(), Error> {
+        self.base.on_replace(state, idx, testcase)
+    }
+
+    /// Removes an entry from the corpus, returning it if it was present.
+    fn on_remove(
+        &self,
+        state: &mut S,
+        idx: usize,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        if let Some(meta) = state
+            .metadata_mut()
+            .get_mut::<MaxHitCountTopRatedMetadata>()
+        {
+            meta.top_rated.retain(|_, (entry, _)| *entry != idx);
+            meta.favored.remove(&idx);
+        }
+        self.base.on_remove(state, idx, testcase)
+    }
+
+    /// Gets the next entry, skipping the non-favored ones with a high probability
+    fn next(&self, state: &mut S) -> Result<usize, Error> {
+        let mut idx = self.base.next(state)?;
+        while {
+            let has = !state
+                .corpus()
+                .get(idx)?
+                .borrow()
+                .has_metadata::<IsFavoredMetadata>();
+            has
+        } && state.rand_mut().below(100) < self.skip_non_favored_prob
+        {
+            idx = self.base.next(state)?;
+        }
+        Ok(idx)
+    }
+}
+
+impl<CS, I, S> MaxHitCountScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    /// Creates a new [`MaxHitCountScheduler`] wrapping the `base` scheduler
+    #[must_use]
+    pub fn new(base: CS) -> Self {
+        Self::with_skip_prob(base, DEFAULT_SKIP_NON_FAVORED_PROB)
+    }
+
+    /// Creates a new [`MaxHitCountScheduler`] with a specified probability to skip non-favored entries
+    #[must_use]
+    pub fn with_skip_prob(base: CS, skip_non_favored_prob: u64) -> Self {
+        Self {
+            base,
+            skip_non_favored_prob,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Updates the favored entries with the hit counts of a new corpus entry
+    pub fn update_top_rated(&self, state: &mut S, idx: usize) -> Result<(), Error> {
+        let counts = match state
+            .corpus()
+            .get(idx)?
+            .borrow()
+            .metadata()
+            .get::<HitCountsMetadata>()
+        {
+            Some(meta) => meta.counts.clone(),
+            None => return Ok(()),
+        };
+
+        if !state.has_metadata::<MaxHitCountTopRatedMetadata>() {
+            state.add_metadata(MaxHitCountTopRatedMetadata::new());
+        }
+        let meta = state
+            .metadata_mut()
+            .get_mut::<MaxHitCountTopRatedMetadata>()
+            .unwrap();
+
+        let mut unfavored = Vec::new();
+        let mut new_maxima = 0;
+        for (edge, count) in counts {
+            let old = meta.top_rated.get(&edge).copied();
+            if let Some((_, old_count)) = old {
+                if count <= old_count {
+                    continue;
+                }
+            }
+            meta.top_rated.insert(edge, (idx, count));
+            new_maxima += 1;
+
+            if let Some((old_idx, _)) = old {
+                if let Some(held) = meta.favored.get_mut(&old_idx) {
+                    *held -= 1;
+                    if *held == 0 {
+                        meta.favored.remove(&old_idx);
+                        unfavored.push(old_idx);
+                    }
+                }
+            }
+        }
+        if new_maxima > 0 {
+            *meta.favored.entry(idx).or_insert(0) += new_maxima;
+        }
+
+        for old_idx in unfavored {
+            drop(
+                state
+                    .corpus()
+                    .get(old_idx)?
+                    .borrow_mut()
+                    .metadata_mut()
+                    .remove::<IsFavoredMetadata>(),
+            );
+        }
+        if new_maxima > 0 {
+            state
+                .corpus()
+                .get(idx)?
+                .borrow_mut()
+                .add_metadata(IsFavoredMetadata {});
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::perf::HitCountsMetadata,
+        inputs::bytes::BytesInput,
+        schedulers::{
+            minimizer::IsFavoredMetadata, MaxHitCountScheduler, QueueScheduler, Scheduler,
+        },
+        state::{HasCorpus, HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_max_hitcount_favored() {
+        let scheduler = MaxHitCountScheduler::new(QueueScheduler::new());
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        for counts in [vec![(0, 1), (1, 10)], vec![(0, 2), (1, 20)], vec![(0, 5)]] {
+            let mut testcase = Testcase::new(BytesInput::new(vec![0; 4]));
+            testcase.add_metadata(HitCountsMetadata::new(counts));
+            let idx = state.corpus_mut().add(testcase).unwrap();
+            scheduler.on_add(&mut state, idx).unwrap();
+        }
+
+        let favored: Vec<bool> = (0..3)
+            .map(|idx| {
+                state
+                    .corpus()
+                    .get(idx)
+                    .unwrap()
+                    .borrow()
+                    .has_metadata::<IsFavoredMetadata>()
+            })
+            .collect();
+        assert_eq!(favored, vec![false, true, true]);
+    }
+}
@@ -23,6 +23,18 @@ pub use weighted::{StdWeightedScheduler, WeightedScheduler};
 pub mod powersched;
 pub use powersched::PowerQueueScheduler;
 
+pub mod entropic;
+pub use entropic::EntropicScheduler;
+
+pub mod rare_branch;
+pub use rare_branch::RareBranchScheduler;
+
+pub mod multi_objective;
+pub use multi_objective::MultiObjectiveScheduler;
+
+pub mod max_hitcount;
+pub use max_hitcount::MaxHitCountScheduler;
+
 use alloc::borrow::ToOwned;
 
 use crate::{
@@ -0,0 +1,539 @@
+//! The [`MultiObjectiveScheduler`] keeps a separate set of favored corpus entries for each feedback dimension,
+//! for example edge coverage, value profile, cmp coverage and slow inputs, and rotates between them in time slices.
+//! Unlike the `MinimizerScheduler`, a noisy dimension with many features can not starve the others.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt::Debug, marker::PhantomData, time::Duration};
+
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{current_time, rands::Rand, serdeany::SerdeAny, tuples::HasConstLen, AsSlice},
+    corpus::{Corpus, Testcase},
+    feedbacks::NamedMapIndexesMetadata,
+    inputs::Input,
+    schedulers::{Scheduler, TestcaseScore},
+    state::{HasCorpus, HasMetadata, HasRand},
+    Error,
+};
+
+/// Default probability to skip the non-favored values
+pub const DEFAULT_SKIP_NON_FAVORED_PROB: u64 = 95;
+
+/// The default time spent on each dimension before rotating to the next one
+pub const DEFAULT_TIME_SLICE: Duration = Duration::from_secs(10);
+
+/// A dimension of the [`MultiObjectiveScheduler`], extracting the features of a corpus entry.
+/// For each feature, the best scored entry is favored in this dimension.
+pub trait ObjectiveDimension<I, S>: Debug
+where
+    I: Input,
+{
+    /// The features of the given corpus entry in this dimension
+    fn features(&self, testcase: &Testcase<I>, state: &S) -> Vec<usize>;
+}
+
+/// A tuple of [`ObjectiveDimension`]s
+pub trait ObjectiveDimensionsTuple<I, S>: HasConstLen
+where
+    I: Input,
+{
+    /// The features of the given corpus entry in the dimension at `index`
+    fn features_at(&self, index: usize, testcase: &Testcase<I>, state: &S) -> Vec<usize>;
+}
+
+impl<I, S> ObjectiveDimensionsTuple<I, S> for ()
+where
+    I: Input,
+{
+    fn features_at(&self, _index: usize, _testcase: &Testcase<I>, _state: &S) -> Vec<usize> {
+        vec![]
+    }
+}
+
+impl<Head, Tail, I, S> ObjectiveDimensionsTuple<I, S> for (Head, Tail)
+where
+    Head: ObjectiveDimension<I, S>,
+    Tail: ObjectiveDimensionsTuple<I, S>,
+    I: Input,
+{
+    fn features_at(&self, index: usize, testcase: &Testcase<I>, state: &S) -> Vec<usize> {
+        if index == 0 {
+            self.0.features(testcase, state)
+        } else {
+            self.1.features_at(index - 1, testcase, state)
+        }
+    }
+}
+
+/// A dimension using the indexes stored in a testcase metadata, such as the `MapIndexesMetadata` for edges
+#[derive(Debug, Clone)]
+pub struct IndexesDimension<M> {
+    phantom: PhantomData<M>,
+}
+
+impl<I, M, S> ObjectiveDimension<I, S> for IndexesDimension<M>
+where
+    I: Input,
+    M: AsSlice<usize> + SerdeAny,
+{
+    fn features(&self, testcase: &Testcase<I>, _state: &S) -> Vec<usize> {
+        testcase
+            .metadata()
+            .get::<M>()
+            .map(|meta| meta.as_slice().to_vec())
+            .unwrap_or_default()
+    }
+}
+
+impl<M> IndexesDimension<M> {
+    /// Creates a new [`IndexesDimension`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M> Default for IndexesDimension<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A dimension using the indexes of the map feedback with the given name,
+/// created with `MapFeedback::with_named_indexes`, for example for value profile or cmp coverage
+#[derive(Debug, Clone)]
+pub struct NamedIndexesDimension {
+    feedback_name: String,
+}
+
+impl<I, S> ObjectiveDimension<I, S> for NamedIndexesDimension
+where
+    I: Input,
+{
+    fn features(&self, testcase: &Testcase<I>, _state: &S) -> Vec<usize> {
+        testcase
+            .metadata()
+            .get::<NamedMapIndexesMetadata>()
+            .and_then(|meta| meta.get(&self.feedback_name))
+            .map(<[usize]>::to_vec)
+            .unwrap_or_default()
+    }
+}
+
+impl NamedIndexesDimension {
+    /// Creates a new [`NamedIndexesDimension`] for the map feedback with the given name
+    #[must_use]
+    pub fn new(feedback_name: &str) -> Self {
+        Self {
+            feedback_name: feedback_name.into(),
+        }
+    }
+}
+
+/// A dimension for slow inputs: each power of two of the execution time in microseconds is a feature,
+/// so the best entry of each execution time bucket is favored, including the slowest ones.
+#[derive(Debug, Clone, Default)]
+pub struct ExecTimeDimension;
+
+impl<I, S> ObjectiveDimension<I, S> for ExecTimeDimension
+where
+    I: Input,
+{
+    fn features(&self, testcase: &Testcase<I>, _state: &S) -> Vec<usize> {
+        testcase.exec_time().map_or_else(Vec::new, |exec_time| {
+            let micros = exec_time.as_micros().max(1);
+            vec![(u128::BITS - micros.leading_zeros()) as usize]
+        })
+    }
+}
+
+impl ExecTimeDimension {
+    /// Creates a new [`ExecTimeDimension`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// A dimension for crashes or timeouts with distinct stack traces, using the hash of the `NewHashFeedback`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct BacktraceDimension;
+
+#[cfg(feature = "std")]
+impl<I, S> ObjectiveDimension<I, S> for BacktraceDimension
+where
+    I: Input,
+{
+    fn features(&self, testcase: &Testcase<I>, _state: &S) -> Vec<usize> {
+        testcase
+            .metadata()
+            .get::<crate::feedbacks::BacktraceHashMetadata>()
+            .map_or_else(Vec::new, |meta| vec![meta.hash as usize])
+    }
+}
+
+#[cfg(feature = "std")]
+impl BacktraceDimension {
+    /// Creates a new [`BacktraceDimension`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// The favored entries of a single dimension
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DimensionFavoreds {
+    /// The best scored entry and its score, for each feature
+    top_rated: HashMap<usize, (usize, f64)>,
+    /// The favored entries, covering all features
+    favored: HashSet<usize>,
+    /// If the favored entries need to be recomputed
+    dirty: bool,
+}
+
+impl DimensionFavoreds {
+    /// The favored entries of this dimension
+    #[must_use]
+    pub fn favored(&self) -> &HashSet<usize> {
+        &self.favored
+    }
+
+    /// The amount of features seen in this dimension
+    #[must_use]
+    pub fn features(&self) -> usize {
+        self.top_rated.len()
+    }
+}
+
+/// The state metadata of the [`MultiObjectiveScheduler`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MultiObjectiveMetadata {
+    /// The favored entries of each dimension
+    dimensions: Vec<DimensionFavoreds>,
+    /// The dimension of the current time slice
+    current: usize,
+    /// When the current time slice started
+    slice_start: Duration,
+    /// The last selected corpus entry, scored again on the next selection
+    last_idx: Option<usize>,
+}
+
+crate::impl_serdeany!(MultiObjectiveMetadata);
+
+impl MultiObjectiveMetadata {
+    /// Creates a new [`struct@MultiObjectiveMetadata`] for the given amount of dimensions
+    #[must_use]
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: vec![DimensionFavoreds::default(); dimensions],
+            current: 0,
+            slice_start: current_time(),
+            last_idx: None,
+        }
+    }
+
+    /// The favored entries of each dimension
+    #[must_use]
+    pub fn dimensions(&self) -> &[DimensionFavoreds] {
+        &self.dimensions
+    }
+
+    /// The dimension of the current time slice
+    #[must_use]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+}
+
+/// The [`MultiObjectiveScheduler`] computes a favored subset of the corpus for each [`ObjectiveDimension`],
+/// prioritizing entries by the [`TestcaseScore`] `F`. Each dimension gets a time slice in turn,
+/// during which the non-favored entries of this dimension are skipped with a high probability.
+#[derive(Debug, Clone)]
+pub struct MultiObjectiveScheduler<CS, D, F, I, S>
+where
+    CS: Scheduler<I, S>,
+    D: ObjectiveDimensionsTuple<I, S>,
+    F: TestcaseScore<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    base: CS,
+    dimensions: D,
+    skip_non_favored_prob: u64,
+    time_slice: Duration,
+    phantom: PhantomData<(F, I, S)>,
+}
+
+impl<CS, D, F, I, S> Scheduler<I, S> for MultiObjectiveScheduler<CS, D, F, I, S>
+where
+    CS: Scheduler<I, S>,
+    D: ObjectiveDimensionsTuple<I, S>,
+    F: TestcaseScore<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    /// Add an entry to the corpus and return its index
+    fn on_add(&self, state: &mut S, idx: usize) -> Result<(), Error> {
+        self.update_score(state, idx)?;
+        self.base.on_add(state, idx)
+    }
+
+    /// Replaces the testcase at the given idx
+    fn on_replace(&self, state: &mut S, idx: usize, testcase: &Testcase<I>) -> Result<(), Error> {
+        self.update_score(state, idx)?;
+        self.base.on_replace(state, idx, testcase)
+    }
+
+    /// Removes an entry from the corpus, returning it if it was present.
+    fn on_remove(
+        &self,
+        state: &mut S,
+        idx: usize,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        if let Some(meta) = state.metadata_mut().get_mut::<MultiObjectiveMetadata>() {
+            for dimension in &mut meta.dimensions {
+                let len = dimension.top_rated.len();
+                dimension.top_rated.retain(|_, (entry, _)| *entry != idx);
+                dimension.dirty |= dimension.top_rated.len() != len;
+            }
+        }
+        self.base.on_remove(state, idx, testcase)
+    }
+
+    /// Gets the next entry, preferring the favored entries of the current dimension
+    fn next(&self, state: &mut S) -> Result<usize, Error> {
+        // The last entry was executed since its last score, e.g. calibrated, so its score and features may have changed
+        if let Some(last_idx) = Self::metadata_mut(state).last_idx.take() {
+            if last_idx < state.corpus().count() {
+                self.update_score(state, last_idx)?;
+            }
+        }
+
+        let dimension = self.rotate(state)?;
+        let mut idx = self.base.next(state)?;
+        while {
+            let has = !state
+                .metadata()
+                .get::<MultiObjectiveMetadata>()
+                .and_then(|meta| meta.dimensions.get(dimension))
+                .map_or(true, |dim| {
+                    dim.favored.is_empty() || dim.favored.contains(&idx)
+                });
+            has
+        } && state.rand_mut().below(100) < self.skip_non_favored_prob
+        {
+            idx = self.base.next(state)?;
+        }
+        Self::metadata_mut(state).last_idx = Some(idx);
+        Ok(idx)
+    }
+}
+
+impl<CS, D, F, I, S> MultiObjectiveScheduler<CS, D, F, I, S>
+where
+    CS: Scheduler<I, S>,
+    D: ObjectiveDimensionsTuple<I, S>,
+    F: TestcaseScore<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    /// Creates a new [`MultiObjectiveScheduler`] wrapping the `base` scheduler, with a tuple of dimensions
+    #[must_use]
+    pub fn new(base: CS, dimensions: D) -> Self {
+        Self {
+            base,
+            dimensions,
+            skip_non_favored_prob: DEFAULT_SKIP_NON_FAVORED_PROB,
+            time_slice: DEFAULT_TIME_SLICE,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Creates a new [`MultiObjectiveScheduler`] with a specified probability to skip non-favored entries
+    #[must_use]
+    pub fn with_skip_prob(base: CS, dimensions: D, skip_non_favored_prob: u64) -> Self {
+        Self {
+            skip_non_favored_prob,
+            ..Self::new(base, dimensions)
+        }
+    }
+
+    /// Sets the time spent on each dimension. With a zero time slice, the dimensions rotate on each selection.
+    #[must_use]
+    pub fn with_time_slice(mut self, time_slice: Duration) -> Self {
+        self.time_slice = time_slice;
+        self
+    }
+
+    fn metadata_mut(state: &mut S) -> &mut MultiObjectiveMetadata {
+        if !state.has_metadata::<MultiObjectiveMetadata>() {
+            state.add_metadata(MultiObjectiveMetadata::new(D::LEN));
+        }
+        state
+            .metadata_mut()
+            .get_mut::<MultiObjectiveMetadata>()
+            .unwrap()
+    }
+
+    /// Update the favored entries of all dimensions with the current score and features of a corpus entry
+    pub fn update_score(&self, state: &mut S, idx: usize) -> Result<(), Error> {
+        let (score, features) = {
+            let mut entry = state.corpus().get(idx)?.borrow_mut();
+            let score = F::compute(&mut *entry, state)?;
+            let features: Vec<Vec<usize>> = (0..D::LEN)
+                .map(|dim| self.dimensions.features_at(dim, &entry, state))
+                .collect();
+            (score, features)
+        };
+
+        let meta = Self::metadata_mut(state);
+        for (dimension, features) in meta.dimensions.iter_mut().zip(features) {
+            // The features this entry lost since it was scored last
+            let features: HashSet<usize> = features.into_iter().collect();
+            let len = dimension.top_rated.len();
+            dimension
+                .top_rated
+                .retain(|feature, (entry, _)| *entry != idx || features.contains(feature));
+            dimension.dirty |= dimension.top_rated.len() != len;
+
+            for feature in features {
+                let better = match dimension.top_rated.get_mut(&feature) {
+                    // Scored again, the favored entries stay the same
+                    Some((entry, old_score)) if *entry == idx => {
+                        *old_score = score;
+                        false
+                    }
+                    Some((_, old_score)) => score < *old_score,
+                    None => true,
+                };
+                if better {
+                    dimension.top_rated.insert(feature, (idx, score));
+                    dimension.dirty = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves on to the next dimension with favored entries if the time slice is over,
+    /// and recomputes its favored entries if needed. Returns the current dimension.
+    fn rotate(&self, state: &mut S) -> Result<usize, Error> {
+        let now = current_time();
+        let time_slice = self.time_slice;
+        let meta = Self::metadata_mut(state);
+        if meta.dimensions.is_empty() {
+            return Ok(0);
+        }
+
+        if now.saturating_sub(meta.slice_start) >= time_slice {
+            let len = meta.dimensions.len();
+            for offset in 1..=len {
+                let next = (meta.current + offset) % len;
+                if !meta.dimensions[next].top_rated.is_empty() {
+                    meta.current = next;
+                    break;
+                }
+            }
+            meta.slice_start = now;
+        }
+
+        let current = meta.current;
+        if meta.dimensions[current].dirty {
+            self.cull(state, current)?;
+        }
+        Ok(current)
+    }
+
+    /// Greedily computes a favored subset of the entries covering all features of a dimension
+    fn cull(&self, state: &mut S, dimension: usize) -> Result<(), Error> {
+        let mut candidates: Vec<(usize, usize)> = Self::metadata_mut(state).dimensions[dimension]
+            .top_rated
+            .iter()
+            .map(|(feature, (idx, _))| (*feature, *idx))
+            .collect();
+        candidates.sort_unstable();
+
+        let mut covered = HashSet::new();
+        let mut favored = HashSet::new();
+        for (feature, idx) in candidates {
+            if covered.contains(&feature) || idx >= state.corpus().count() {
+                continue;
+            }
+            let entry = state.corpus().get(idx)?.borrow();
+            covered.extend(self.dimensions.features_at(dimension, &entry, state));
+            favored.insert(idx);
+        }
+
+        let meta = Self::metadata_mut(state);
+        meta.dimensions[dimension].favored = favored;
+        meta.dimensions[dimension].dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::{MapIndexesMetadata, NamedMapIndexesMetadata},
+        inputs::bytes::BytesInput,
+        schedulers::{
+            multi_objective::{IndexesDimension, MultiObjectiveMetadata, NamedIndexesDimension},
+            LenTimeMulTestcaseScore, MultiObjectiveScheduler, QueueScheduler, Scheduler,
+        },
+        state::{HasCorpus, HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_multi_objective() {
+        let scheduler = MultiObjectiveScheduler::<_, _, LenTimeMulTestcaseScore<_, _>, _, _>::new(
+            QueueScheduler::new(),
+            tuple_list!(
+                IndexesDimension::<MapIndexesMetadata>::new(),
+                NamedIndexesDimension::new("cmp")
+            ),
+        )
+        .with_time_slice(Duration::ZERO);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        // The short entry covers all edges, the long one all cmps
+        let mut short = Testcase::new(BytesInput::new(vec![0; 4]));
+        short.add_metadata(MapIndexesMetadata::new(vec![0, 1, 2]));
+        let mut long = Testcase::new(BytesInput::new(vec![0; 64]));
+        long.add_metadata(MapIndexesMetadata::new(vec![0, 1]));
+        let mut cmps = NamedMapIndexesMetadata::new();
+        cmps.map.insert("cmp".into(), vec![7]);
+        long.add_metadata(cmps);
+
+        for testcase in [short, long] {
+            let idx = state.corpus_mut().add(testcase).unwrap();
+            scheduler.on_add(&mut state, idx).unwrap();
+        }
+
+        for _ in 0..4 {
+            scheduler.next(&mut state).unwrap();
+        }
+        let meta = state.metadata().get::<MultiObjectiveMetadata>().unwrap();
+        assert_eq!(meta.dimensions()[0].favored().len(), 1);
+        assert!(meta.dimensions()[0].favored().contains(&0));
+        assert!(meta.dimensions()[1].favored().contains(&1));
+    }
+}
@@ -0,0 +1,239 @@
+//! The rare branch targeting scheduler from `FairFuzz` (`https://arxiv.org/abs/1709.07101`).
+//! It prefers the corpus entries that hit a branch which is rarely hit by the fuzzer,
+//! as counted by the [`BranchHitsFeedback`](crate::feedbacks::BranchHitsFeedback).
+//! Together with the [`MutationMaskStage`](crate::stages::MutationMaskStage), the mutations of the
+//! selected entry are restricted to the bytes that don't lose the rare branch.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, Testcase},
+    feedbacks::{BranchHitsMetadata, MapIndexesMetadata},
+    inputs::Input,
+    mutators::MutationMaskMetadata,
+    schedulers::Scheduler,
+    state::{HasCorpus, HasMetadata},
+    Error,
+};
+
+/// The default amount of corpus entries the [`RareBranchScheduler`] skips at most, looking for one that hits a rare branch
+pub const DEFAULT_MAX_SKIPS: usize = 64;
+
+/// The testcase metadata of the [`RareBranchScheduler`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RareBranchMetadata {
+    /// The map indexes hit by this entry
+    pub branches: Vec<usize>,
+    /// The rare branch currently targeted while fuzzing this entry, if any
+    pub target: Option<usize>,
+}
+
+crate::impl_serdeany!(RareBranchMetadata);
+
+impl RareBranchMetadata {
+    /// Creates a new [`struct@RareBranchMetadata`] for an entry hitting the given branches
+    #[must_use]
+    pub fn new(branches: Vec<usize>) -> Self {
+        Self {
+            branches,
+            target: None,
+        }
+    }
+
+    /// Returns the rarest branch hit by this entry, if it is below the `threshold`
+    #[must_use]
+    pub fn rarest_branch(&self, hits: &BranchHitsMetadata, threshold: u64) -> Option<usize> {
+        self.branches
+            .iter()
+            .copied()
+            .filter(|idx| hits.is_rare(*idx, threshold))
+            .min_by_key(|idx| hits.hits(*idx))
+    }
+}
+
+/// The [`RareBranchScheduler`] asks the base scheduler for corpus entries until it finds one that hits a rare branch,
+/// and marks this branch as the target of the entry.
+/// It relies on the [`MapIndexesMetadata`] of new entries, so the map feedback has to track indexes.
+#[derive(Debug, Clone)]
+pub struct RareBranchScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata,
+{
+    base: CS,
+    max_skips: usize,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<CS, I, S> Scheduler<I, S> for RareBranchScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata,
+{
+    /// Add an entry to the corpus and return its index
+    fn on_add(&self, state: &mut S, idx: usize) -> Result<(), Error> {
+        {
+            // Copy the indexes, the base scheduler may remove them
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            let branches = testcase
+                .metadata()
+                .get::<MapIndexesMetadata>()
+                .map(|meta| meta.list.clone())
+                .unwrap_or_default();
+            testcase.add_metadata(RareBranchMetadata::new(branches));
+        }
+        self.base.on_add(state, idx)
+    }
+
+    /// Replaces the testcase at the given idx
+    fn on_replace(&self, state: &mut S, idx: usize, testcase: &Testcase<I>) -> Result<(), Error> {
+        self.base.on_replace(state, idx, testcase)
+    }
+
+    /// Removes an entry from the corpus, returning it if it was present.
+    fn on_remove(
+        &self,
+        state: &mut S,
+        idx: usize,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        self.base.on_remove(state, idx, testcase)
+    }
+
+    /// Gets the next entry hitting a rare branch, if the base scheduler finds one
+    fn next(&self, state: &mut S) -> Result<usize, Error> {
+        let mut idx = self.base.next(state)?;
+
+        let threshold = state
+            .metadata()
+            .get::<BranchHitsMetadata>()
+            .and_then(BranchHitsMetadata::rare_threshold);
+
+        let mut target = None;
+        if let Some(threshold) = threshold {
+            let max_skips = self.max_skips.min(state.corpus().count());
+            for skip in 0..=max_skips {
+                target = Self::rarest_branch(state, idx, threshold)?;
+                if target.is_some() || skip == max_skips {
+                    break;
+                }
+                idx = self.base.next(state)?;
+            }
+        }
+
+        let mask = {
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            if let Some(meta) = testcase.metadata_mut().get_mut::<RareBranchMetadata>() {
+                meta.target = target;
+            }
+            // Only keep the mutation mask if it was computed for the current target
+            testcase
+                .metadata()
+                .get::<MutationMaskMetadata>()
+                .filter(|mask| Some(mask.target) == target)
+                .cloned()
+        };
+
+        drop(state.metadata_mut().remove::<MutationMaskMetadata>());
+        if let Some(mask) = mask {
+            state.add_metadata(mask);
+        }
+
+        Ok(idx)
+    }
+}
+
+impl<CS, I, S> RareBranchScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata,
+{
+    /// Creates a new [`RareBranchScheduler`] wrapping the `base` scheduler
+    #[must_use]
+    pub fn new(base: CS) -> Self {
+        Self::with_max_skips(base, DEFAULT_MAX_SKIPS)
+    }
+
+    /// Creates a new [`RareBranchScheduler`] wrapping the `base` scheduler,
+    /// skipping at most `max_skips` entries that hit no rare branch
+    #[must_use]
+    pub fn with_max_skips(base: CS, max_skips: usize) -> Self {
+        Self {
+            base,
+            max_skips,
+            phantom: PhantomData,
+        }
+    }
+
+    fn rarest_branch(state: &S, idx: usize, threshold: u64) -> Result<Option<usize>, Error> {
+        let hits = match state.metadata().get::<BranchHitsMetadata>() {
+            Some(hits) => hits,
+            None => return Ok(None),
+        };
+        Ok(state
+            .corpus()
+            .get(idx)?
+            .borrow()
+            .metadata()
+            .get::<RareBranchMetadata>()
+            .and_then(|meta| meta.rarest_branch(hits, threshold)))
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::{BranchHitsMetadata, MapIndexesMetadata},
+        inputs::bytes::BytesInput,
+        schedulers::{
+            rare_branch::RareBranchMetadata, QueueScheduler, RareBranchScheduler, Scheduler,
+        },
+        state::{HasCorpus, HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_rare_branch_scheduler() {
+        let scheduler = RareBranchScheduler::new(QueueScheduler::new());
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        for branches in [vec![0, 1], vec![0, 2], vec![0, 1]] {
+            let mut testcase = Testcase::new(BytesInput::new(vec![0; 4]));
+            testcase.add_metadata(MapIndexesMetadata::new(branches));
+            let idx = state.corpus_mut().add(testcase).unwrap();
+            scheduler.on_add(&mut state, idx).unwrap();
+        }
+
+        let mut hits = BranchHitsMetadata::new();
+        hits.hits = vec![1000, 500, 2];
+        state.add_metadata(hits);
+
+        // Only the second entry hits the rare branch 2
+        for _ in 0..3 {
+            assert_eq!(scheduler.next(&mut state).unwrap(), 1);
+        }
+        let testcase = state.corpus().get(1).unwrap().borrow();
+        assert_eq!(
+            testcase
+                .metadata()
+                .get::<RareBranchMetadata>()
+                .unwrap()
+                .target,
+            Some(2)
+        );
+    }
+}
@@ -1,10 +1,11 @@
 //! The calibration stage. The fuzzer measures the average exec time and the bitmap size.
+//! The [`TimeoutCalibrationStage`] uses the measured exec times to adapt the timeout of the executor.
 
 use crate::{
     bolts::{current_time, tuples::Named, AsRefIterator},
     corpus::{Corpus, SchedulerTestcaseMetaData},
     events::{EventFirer, LogSeverity},
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
     feedbacks::{
         map::{IsNovel, MapFeedback, MapFeedbackMetadata, Reducer},
         HasObserverName,
@@ -17,8 +18,11 @@ use crate::{
     state::{HasClientPerfMonitor, HasCorpus, HasMetadata, HasNamedMetadata},
     Error,
 };
-use alloc::string::{String, ToString};
-use core::{fmt::Debug, marker::PhantomData, time::Duration};
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::{cmp::max, fmt::Debug, marker::PhantomData, time::Duration};
 use num_traits::Bounded;
 use serde::{Deserialize, Serialize};
 
@@ -40,6 +44,56 @@ where
 const CAL_STAGE_START: usize = 4;
 const CAL_STAGE_MAX: usize = 16;
 
+crate::impl_serdeany!(ExecTimeMetadata);
+
+/// The exec times of the corpus entries, measured by the [`CalibrationStage`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ExecTimeMetadata {
+    /// Sum of the average exec times of all calibrated entries
+    total_exec_time: Duration,
+    /// The highest average exec time of a calibrated entry
+    max_exec_time: Duration,
+    /// Number of calibrated entries
+    entries: u32,
+}
+
+impl ExecTimeMetadata {
+    /// Creates a new [`struct@ExecTimeMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the average exec time of a freshly calibrated entry
+    pub fn add_exec_time(&mut self, exec_time: Duration) {
+        self.total_exec_time += exec_time;
+        self.max_exec_time = max(self.max_exec_time, exec_time);
+        self.entries += 1;
+    }
+
+    /// The mean exec time over all calibrated entries, if any
+    #[must_use]
+    pub fn mean_exec_time(&self) -> Option<Duration> {
+        if self.entries == 0 {
+            None
+        } else {
+            Some(self.total_exec_time / self.entries)
+        }
+    }
+
+    /// The highest exec time of a calibrated entry
+    #[must_use]
+    pub fn max_exec_time(&self) -> Duration {
+        self.max_exec_time
+    }
+
+    /// The number of calibrated entries
+    #[must_use]
+    pub fn entries(&self) -> u32 {
+        self.entries
+    }
+}
+
 impl<E, EM, I, O, OT, S, Z> Stage<E, EM, S, Z> for CalibrationStage<I, O, OT, S>
 where
     E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
@@ -165,6 +219,18 @@ where
             i += 1;
         }
 
+        // Only remember exec times of entries that never errored, a timeout would skew the mean
+        if exit_kind == ExitKind::Ok && !has_errors {
+            let exec_time = total_time / (iter as u32);
+            if let Some(meta) = state.metadata_mut().get_mut::<ExecTimeMetadata>() {
+                meta.add_exec_time(exec_time);
+            } else {
+                let mut meta = ExecTimeMetadata::new();
+                meta.add_exec_time(exec_time);
+                state.add_metadata(meta);
+            }
+        }
+
         #[allow(clippy::cast_precision_loss)]
         if unstable_entries != 0 {
             *state.stability_mut() = Some((map_len - unstable_entries) as f32 / (map_len as f32));
@@ -245,3 +311,149 @@ where
         }
     }
 }
+
+/// Timeouts are rounded up to a multiple of this, like in AFL
+const EXEC_TIMEOUT_ROUND: Duration = Duration::from_millis(20);
+/// The default factor between the mean exec time and the timeout
+const DEFAULT_TIMEOUT_MULTIPLIER: u32 = 5;
+/// The default upper bound for calibrated timeouts
+const DEFAULT_MAX_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// The timeout calibration stage derives the timeout of the executor from the exec times measured
+/// by the [`CalibrationStage`], similar to the timeout AFL computes from its calibration runs.
+/// The timeout is `multiplier` times the mean exec time (but at least the highest exec time), clamped to `[min_timeout, max_timeout]`.
+/// Add it after the [`CalibrationStage`] and wrap the executor in a [`crate::executors::HangConfirmExecutor`]
+/// to re-run inputs that hit the calibrated timeout with a larger timeout.
+#[derive(Clone, Debug)]
+pub struct TimeoutCalibrationStage<I> {
+    multiplier: u32,
+    min_timeout: Duration,
+    max_timeout: Duration,
+    phantom: PhantomData<I>,
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for TimeoutCalibrationStage<I>
+where
+    E: HasTimeout,
+    EM: EventFirer<I>,
+    I: Input,
+    S: HasMetadata,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        _corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let timeout = match state.metadata().get::<ExecTimeMetadata>() {
+            Some(meta) => match self.calibrated_timeout(meta) {
+                Some(timeout) => timeout,
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        if timeout != executor.timeout() {
+            executor.set_timeout(timeout);
+            mgr.log(
+                state,
+                LogSeverity::Info,
+                format!("Calibrated the executor timeout to {:?}", timeout),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<I> TimeoutCalibrationStage<I>
+where
+    I: Input,
+{
+    /// Create a new [`TimeoutCalibrationStage`], using five times the mean exec time, but at most one second.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_bounds(
+            DEFAULT_TIMEOUT_MULTIPLIER,
+            EXEC_TIMEOUT_ROUND,
+            DEFAULT_MAX_TIMEOUT,
+        )
+    }
+
+    /// Create a new [`TimeoutCalibrationStage`] with a custom `multiplier` for the mean exec time,
+    /// and custom bounds for the resulting timeout.
+    #[must_use]
+    pub fn with_bounds(multiplier: u32, min_timeout: Duration, max_timeout: Duration) -> Self {
+        Self {
+            multiplier,
+            min_timeout,
+            max_timeout: max(min_timeout, max_timeout),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Computes the timeout for the exec times in the given [`struct@ExecTimeMetadata`].
+    /// Returns `None` if no entry was calibrated yet.
+    #[must_use]
+    pub fn calibrated_timeout(&self, meta: &ExecTimeMetadata) -> Option<Duration> {
+        let mean = meta.mean_exec_time()?;
+        let timeout = max(mean * self.multiplier, meta.max_exec_time());
+
+        // Round up, so that the timeout does not change for every new corpus entry
+        let round = EXEC_TIMEOUT_ROUND.as_micros();
+        let rounded = (timeout.as_micros() + round - 1) / round * round;
+        let timeout = Duration::from_micros(rounded as u64);
+
+        Some(timeout.clamp(self.min_timeout, self.max_timeout))
+    }
+}
+
+impl<I> Default for TimeoutCalibrationStage<I>
+where
+    I: Input,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        inputs::BytesInput,
+        stages::calibrate::{ExecTimeMetadata, TimeoutCalibrationStage},
+    };
+
+    #[test]
+    fn test_timeout_calibration() {
+        let stage = TimeoutCalibrationStage::<BytesInput>::new();
+        let mut meta = ExecTimeMetadata::new();
+        assert_eq!(stage.calibrated_timeout(&meta), None);
+
+        meta.add_exec_time(Duration::from_millis(1));
+        meta.add_exec_time(Duration::from_millis(3));
+        // 5 * 2ms, rounded up to 20ms
+        assert_eq!(
+            stage.calibrated_timeout(&meta),
+            Some(Duration::from_millis(20))
+        );
+
+        meta.add_exec_time(Duration::from_millis(41));
+        // 5 * 15ms, rounded up to 80ms
+        assert_eq!(
+            stage.calibrated_timeout(&meta),
+            Some(Duration::from_millis(80))
+        );
+
+        meta.add_exec_time(Duration::from_secs(5));
+        assert_eq!(
+            stage.calibrated_timeout(&meta),
+            Some(Duration::from_millis(1000))
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! The [`CheckpointStage`] periodically writes the complete state to disk,
+//! so that a campaign can be resumed after a reboot or a crash of the broker.
+
+use core::time::Duration;
+use serde::Serialize;
+
+use crate::{
+    bolts::{checkpoint::StateCheckpoint, current_time},
+    stages::Stage,
+    Error,
+};
+
+/// A stage that saves the whole state (corpus, metadata, rand, executions, scheduler metadata, ...)
+/// to a [`StateCheckpoint`] on disk every `interval`.
+/// To resume from it, pass the same checkpoint to the [`crate::events::RestartingMgr`],
+/// or a checkpoint directory to the [`crate::bolts::launcher::Launcher`].
+#[derive(Debug)]
+pub struct CheckpointStage {
+    checkpoint: StateCheckpoint,
+    interval: Duration,
+    last_time: Duration,
+}
+
+impl<E, EM, S, Z> Stage<E, EM, S, Z> for CheckpointStage
+where
+    S: Serialize,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+        _corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let cur = current_time();
+        // default to 0 here to avoid crashes on clock skew
+        if cur.checked_sub(self.last_time).unwrap_or_default() >= self.interval {
+            self.checkpoint.save(state)?;
+            self.last_time = cur;
+        }
+        Ok(())
+    }
+}
+
+impl CheckpointStage {
+    /// Create a new [`CheckpointStage`], saving the state to `checkpoint` every `interval`.
+    #[must_use]
+    pub fn new(checkpoint: StateCheckpoint, interval: Duration) -> Self {
+        Self {
+            checkpoint,
+            interval,
+            last_time: current_time(),
+        }
+    }
+
+    /// The checkpoint this stage writes to
+    #[must_use]
+    pub fn checkpoint(&self) -> &StateCheckpoint {
+        &self.checkpoint
+    }
+}
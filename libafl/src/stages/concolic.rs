@@ -4,9 +4,9 @@
 
 use core::marker::PhantomData;
 
-use alloc::string::String;
 #[cfg(feature = "concolic_mutation")]
-use alloc::{borrow::ToOwned, string::ToString, vec::Vec};
+use alloc::string::ToString;
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
 
 use crate::{
     corpus::Corpus,
@@ -85,20 +85,28 @@ where
     }
 }
 
-#[cfg(feature = "concolic_mutation")]
 use crate::{
     inputs::HasBytesVec,
     mark_feature_time,
-    observers::concolic::{ConcolicMetadata, SymExpr, SymExprRef},
+    observers::concolic::{ConcolicMetadata, SimpleSolver, SymExpr, SymExprRef},
     start_timer, Evaluator,
 };
 
-#[cfg(all(feature = "concolic_mutation", feature = "introspection"))]
+#[cfg(feature = "introspection")]
 use crate::monitors::PerfFeature;
 
+/// Solves the negated path constraints of a trace with the lightweight [`SimpleSolver`] only.
+#[cfg(not(feature = "concolic_mutation"))]
+fn generate_mutations(iter: impl Iterator<Item = (SymExprRef, SymExpr)>) -> Vec<Vec<(usize, u8)>> {
+    SimpleSolver::new().generate_mutations(iter)
+}
+
+/// Solves the negated path constraints of a trace with the lightweight [`SimpleSolver`],
+/// falling back to Z3 for the constraints it does not support.
 #[cfg(feature = "concolic_mutation")]
 #[allow(clippy::too_many_lines)]
 fn generate_mutations(iter: impl Iterator<Item = (SymExprRef, SymExpr)>) -> Vec<Vec<(usize, u8)>> {
+    use crate::observers::concolic::SimpleSolution;
     use hashbrown::HashMap;
     use z3::{
         ast::{Ast, Bool, Dynamic, BV},
@@ -143,6 +151,7 @@ fn generate_mutations(iter: impl Iterator<Item = (SymExprRef, SymExpr)>) -> Vec<
     let solver = Solver::new(&ctx);
 
     let mut translation = HashMap::<SymExprRef, Dynamic>::new();
+    let mut simple_solver = SimpleSolver::new();
 
     macro_rules! bool {
         ($op:ident) => {
@@ -163,6 +172,7 @@ fn generate_mutations(iter: impl Iterator<Item = (SymExprRef, SymExpr)>) -> Vec<
     }
 
     for (id, msg) in iter {
+        let simple_solution = simple_solver.add(id, &msg);
         let z3_expr: Option<Dynamic> = match msg {
             SymExpr::InputByte { offset } => {
                 Some(BV::new_const(&ctx, Symbol::Int(offset as u32), 8).into())
@@ -289,6 +299,12 @@ fn generate_mutations(iter: impl Iterator<Item = (SymExprRef, SymExpr)>) -> Vec<
             let op = if taken { op } else { op.not() }.simplify();
             if op.as_bool().is_some() {
                 // this constraint is useless, as it is always sat or unsat
+            } else if let Some(SimpleSolution::Solved(replacements)) = simple_solution {
+                res.push(replacements);
+                solver.assert(&op);
+            } else if let Some(SimpleSolution::Unsat) = simple_solution {
+                // the negation is unsat even without the previous constraints
+                solver.assert(&op);
             } else {
                 let negated_constraint = op.not().simplify();
                 solver.push();
@@ -341,7 +357,8 @@ fn generate_mutations(iter: impl Iterator<Item = (SymExprRef, SymExpr)>) -> Vec<
     res
 }
 
-/// A mutational stage that uses Z3 to solve concolic constraints attached to the [`crate::corpus::Testcase`] by the [`ConcolicTracingStage`].
+/// A mutational stage that solves concolic constraints attached to the [`crate::corpus::Testcase`] by the [`ConcolicTracingStage`].
+/// Simple constraints are solved by the lightweight [`SimpleSolver`], all others by Z3 if the `concolic_mutation` feature is enabled.
 #[derive(Clone, Debug)]
 pub struct SimpleConcolicMutationalStage<EM, I, S, Z>
 where
@@ -351,7 +368,6 @@ where
     _phantom: PhantomData<(EM, I, S, Z)>,
 }
 
-#[cfg(feature = "concolic_mutation")]
 impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for SimpleConcolicMutationalStage<EM, I, S, Z>
 where
     I: Input + HasBytesVec,
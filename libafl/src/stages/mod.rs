@@ -14,7 +14,7 @@ pub mod tracing;
 pub use tracing::{ShadowTracingStage, TracingStage};
 
 pub mod calibrate;
-pub use calibrate::CalibrationStage;
+pub use calibrate::{CalibrationStage, TimeoutCalibrationStage};
 
 pub mod power;
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
@@ -22,6 +22,9 @@ pub use power::{PowerMutationalStage, StdPowerMutationalStage};
 pub mod generalization;
 pub use generalization::GeneralizationStage;
 
+pub mod mutation_mask;
+pub use mutation_mask::MutationMaskStage;
+
 pub mod owned;
 pub use owned::StagesOwnedList;
 
@@ -32,6 +35,16 @@ pub use concolic::ConcolicTracingStage;
 #[cfg(feature = "std")]
 pub use concolic::SimpleConcolicMutationalStage;
 
+#[cfg(feature = "std")]
+pub mod checkpoint;
+#[cfg(feature = "std")]
+pub use checkpoint::CheckpointStage;
+
+#[cfg(feature = "std")]
+pub mod testcase_info;
+#[cfg(feature = "std")]
+pub use testcase_info::TestcaseInfoStage;
+
 #[cfg(feature = "std")]
 pub mod sync;
 #[cfg(feature = "std")]
@@ -0,0 +1,173 @@
+//! The mutation mask stage from `FairFuzz` computes which bytes of a corpus entry can be mutated
+//! without losing the rare branch targeted by the `RareBranchScheduler`.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData};
+
+use crate::{
+    corpus::Corpus,
+    executors::{Executor, HasObservers},
+    inputs::{HasBytesVec, Input},
+    mark_feature_time,
+    mutators::MutationMaskMetadata,
+    observers::{MapObserver, ObserversTuple},
+    schedulers::rare_branch::RareBranchMetadata,
+    stages::Stage,
+    start_timer,
+    state::{HasClientPerfMonitor, HasCorpus, HasExecutions, HasMetadata},
+    Error,
+};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+
+/// The default maximum input length for which the mask is computed, as each byte costs one execution
+pub const DEFAULT_MAX_MASK_LEN: usize = 4096;
+
+/// The [`MutationMaskStage`] flips each byte of the current corpus entry, and checks if the rare branch
+/// it targets is still hit. The result is stored as [`MutationMaskMetadata`] in the entry and in the state,
+/// so that a [`crate::mutators::MaskedMutator`] leaves the bytes alone that are needed to hit the rare branch.
+#[derive(Clone, Debug)]
+pub struct MutationMaskStage<EM, I, O, OT, S, Z>
+where
+    I: Input + HasBytesVec,
+    O: MapObserver,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasExecutions + HasMetadata + HasCorpus<I>,
+{
+    map_observer_name: String,
+    max_len: usize,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(EM, I, O, OT, S, Z)>,
+}
+
+impl<E, EM, I, O, OT, S, Z> Stage<E, EM, S, Z> for MutationMaskStage<EM, I, O, OT, S, Z>
+where
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    I: Input + HasBytesVec,
+    O: MapObserver,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasExecutions + HasMetadata + HasCorpus<I>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let (target, original) = {
+            let mut testcase = state.corpus().get(corpus_idx)?.borrow_mut();
+            let target = match testcase
+                .metadata()
+                .get::<RareBranchMetadata>()
+                .and_then(|meta| meta.target)
+            {
+                Some(target) => target,
+                None => return Ok(()),
+            };
+            // The mask for this target is known already
+            if testcase
+                .metadata()
+                .get::<MutationMaskMetadata>()
+                .map_or(false, |mask| mask.target == target)
+            {
+                return Ok(());
+            }
+            (target, testcase.load_input()?.clone())
+        };
+
+        let len = original.bytes().len();
+        if len > self.max_len {
+            return Ok(());
+        }
+
+        let mut mask = Vec::with_capacity(len);
+        for i in 0..len {
+            let mut input = original.clone();
+            input.bytes_mut()[i] ^= 0xff;
+            mask.push(self.hits_target(fuzzer, executor, state, manager, &input, target)?);
+        }
+
+        let mask = MutationMaskMetadata::new(target, mask);
+        state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .add_metadata(mask.clone());
+        drop(state.metadata_mut().remove::<MutationMaskMetadata>());
+        state.add_metadata(mask);
+        Ok(())
+    }
+}
+
+impl<EM, I, O, OT, S, Z> MutationMaskStage<EM, I, O, OT, S, Z>
+where
+    I: Input + HasBytesVec,
+    O: MapObserver,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasExecutions + HasMetadata + HasCorpus<I>,
+{
+    /// Create a new [`MutationMaskStage`].
+    #[must_use]
+    pub fn new(map_observer: &O) -> Self {
+        Self::from_name(map_observer.name())
+    }
+
+    /// Create a new [`MutationMaskStage`] from name
+    #[must_use]
+    pub fn from_name(map_observer_name: &str) -> Self {
+        Self {
+            map_observer_name: map_observer_name.to_string(),
+            max_len: DEFAULT_MAX_MASK_LEN,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the maximum input length for which the mask is computed
+    #[must_use]
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    fn hits_target<E>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+        target: usize,
+    ) -> Result<bool, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    {
+        start_timer!(state);
+        executor.observers_mut().pre_exec_all(state, input)?;
+        mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+        start_timer!(state);
+        let exit_kind = executor.run_target(fuzzer, state, manager, input)?;
+        mark_feature_time!(state, PerfFeature::TargetExecution);
+
+        *state.executions_mut() += 1;
+
+        start_timer!(state);
+        executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+        let observer = executor
+            .observers()
+            .match_name::<O>(&self.map_observer_name)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found".to_string()))?;
+        Ok(target < observer.usable_count() && *observer.get(target) != observer.initial())
+    }
+}
@@ -0,0 +1,167 @@
+//! The [`TestcaseInfoStage`] reports the corpus entries and the solutions to the monitor,
+//! so that monitors such as the `TuiMonitor` can show what the fuzzer found, without access to the disk.
+
+use ahash::AHasher;
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+use hashbrown::HashMap;
+
+use crate::{
+    bolts::AsSlice,
+    corpus::{Corpus, SchedulerTestcaseMetaData, Testcase},
+    events::{Event, EventFirer},
+    feedbacks::BacktraceHashMetadata,
+    inputs::{HasTargetBytes, Input},
+    monitors::{TestcaseInfo, TestcaseKind},
+    mutators::LogMutationMetadata,
+    schedulers::minimizer::IsFavoredMetadata,
+    stages::Stage,
+    state::{HasCorpus, HasMetadata, HasSolutions},
+    Error,
+};
+
+/// The default amount of input bytes sent to the monitor for each testcase
+pub const DEFAULT_MAX_INFO_BYTES: usize = 4096;
+
+/// A stage that sends a [`TestcaseInfo`] to the monitor for every new solution and corpus entry,
+/// and again whenever the information about the currently fuzzed corpus entry changed, for example after calibration.
+#[derive(Debug)]
+pub struct TestcaseInfoStage<I>
+where
+    I: Input + HasTargetBytes,
+{
+    max_bytes: usize,
+    /// The amount of corpus entries and solutions that were reported already
+    reported_corpus: usize,
+    reported_solutions: usize,
+    /// The hash of the last reported info (without the input) of each corpus entry, to only send changes
+    last_infos: HashMap<usize, u64>,
+    phantom: PhantomData<I>,
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for TestcaseInfoStage<I>
+where
+    EM: EventFirer<I>,
+    I: Input + HasTargetBytes,
+    S: HasCorpus<I> + HasSolutions<I>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let mut infos = vec![];
+
+        for idx in self.reported_solutions..state.solutions().count() {
+            let mut testcase = state.solutions().get(idx)?.borrow_mut();
+            let mut info = Self::info(TestcaseKind::Solution, idx, &testcase);
+            self.load_bytes(&mut info, &mut testcase)?;
+            infos.push(info);
+        }
+        self.reported_solutions = state.solutions().count();
+
+        let corpus_count = state.corpus().count();
+        for idx in (self.reported_corpus..corpus_count).chain(Some(corpus_idx)) {
+            if idx >= corpus_count {
+                continue;
+            }
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            let mut info = Self::info(TestcaseKind::Corpus, idx, &testcase);
+            let mut hasher = AHasher::new_with_keys(0, 0);
+            info.hash(&mut hasher);
+            let hash = hasher.finish();
+            // The input of an entry does not change, so it is only loaded if the metadata did
+            if self.last_infos.insert(idx, hash) != Some(hash) {
+                self.load_bytes(&mut info, &mut testcase)?;
+                infos.push(info);
+            }
+        }
+        self.reported_corpus = corpus_count;
+
+        for info in infos {
+            manager.fire(
+                state,
+                Event::UpdateTestcaseInfo {
+                    info: Box::new(info),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<I> TestcaseInfoStage<I>
+where
+    I: Input + HasTargetBytes,
+{
+    /// Creates a new [`TestcaseInfoStage`], sending up to [`DEFAULT_MAX_INFO_BYTES`] bytes of each input
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_bytes(DEFAULT_MAX_INFO_BYTES)
+    }
+
+    /// Creates a new [`TestcaseInfoStage`], sending up to `max_bytes` bytes of each input
+    #[must_use]
+    pub fn with_max_bytes(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            reported_corpus: 0,
+            reported_solutions: 0,
+            last_infos: HashMap::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Collects the [`TestcaseInfo`] of a testcase from its metadata, without the input
+    fn info(kind: TestcaseKind, idx: usize, testcase: &Testcase<I>) -> TestcaseInfo {
+        let scheduler_meta = testcase.metadata().get::<SchedulerTestcaseMetaData>();
+        TestcaseInfo {
+            kind,
+            idx,
+            executions: *testcase.executions(),
+            exec_time: *testcase.exec_time(),
+            bitmap_size: scheduler_meta
+                .map(SchedulerTestcaseMetaData::bitmap_size)
+                .filter(|size| *size > 0),
+            favored: testcase.has_metadata::<IsFavoredMetadata>(),
+            depth: scheduler_meta.map(SchedulerTestcaseMetaData::depth),
+            mutations: testcase
+                .metadata()
+                .get::<LogMutationMetadata>()
+                .map(|meta| meta.list.clone())
+                .unwrap_or_default(),
+            crash_bucket: testcase
+                .metadata()
+                .get::<BacktraceHashMetadata>()
+                .map(|meta| meta.hash),
+            len: 0,
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Loads the input of the testcase and adds up to `max_bytes` of it to the info
+    fn load_bytes(&self, info: &mut TestcaseInfo, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        let target_bytes = testcase.load_input()?.target_bytes();
+        let target_bytes = target_bytes.as_slice();
+        info.len = target_bytes.len();
+        info.bytes = target_bytes[..info.len.min(self.max_bytes)].to_vec();
+        Ok(())
+    }
+}
+
+impl<I> Default for TestcaseInfoStage<I>
+where
+    I: Input + HasTargetBytes,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -12,6 +12,7 @@ use frida_gum::{instruction_writer::InstructionWriter, stalker::StalkerOutput};
 
 use crate::helper::FridaRuntime;
 use libafl::bolts::xxh3_rrmxmx_mixer;
+use libafl_targets::{CallContext, NgramHistory};
 
 /// (Default) map size for frida coverage reporting
 pub const MAP_SIZE: usize = 64 * 1024;
@@ -23,6 +24,8 @@ pub struct CoverageRuntime {
     previous_pc: u64,
     current_log_impl: u64,
     blob_maybe_log: Option<Box<[u8]>>,
+    ngram: Option<NgramHistory>,
+    context: Option<CallContext>,
 }
 
 impl Default for CoverageRuntime {
@@ -46,6 +49,12 @@ impl FridaRuntime for CoverageRuntime {
         &mut self,
         _input: &I,
     ) -> Result<(), libafl::Error> {
+        if let Some(ngram) = &mut self.ngram {
+            ngram.reset();
+        }
+        if let Some(context) = &mut self.context {
+            context.reset();
+        }
         Ok(())
     }
 
@@ -66,6 +75,88 @@ impl CoverageRuntime {
             previous_pc: 0,
             current_log_impl: 0,
             blob_maybe_log: None,
+            ngram: None,
+            context: None,
+        }
+    }
+
+    /// Create a new coverage runtime for n-gram coverage of the last `size` blocks
+    #[must_use]
+    pub fn with_ngram(size: usize) -> Self {
+        Self {
+            ngram: Some(NgramHistory::new(size)),
+            ..Self::new()
+        }
+    }
+
+    /// Create a new coverage runtime for calling context sensitive coverage
+    #[must_use]
+    pub fn with_calling_context() -> Self {
+        Self {
+            context: Some(CallContext::new()),
+            ..Self::new()
+        }
+    }
+
+    /// If the coverage is n-gram or calling context sensitive.
+    /// In this case, the blocks are logged with [`Self::log_block`] callouts instead of the inline `maybe_log` blob.
+    #[must_use]
+    pub fn uses_history(&self) -> bool {
+        self.ngram.is_some() || self.context.is_some()
+    }
+
+    /// If the calls and returns need to be tracked with [`Self::on_call`] and [`Self::on_ret`]
+    #[must_use]
+    pub fn uses_calling_context(&self) -> bool {
+        self.context.is_some()
+    }
+
+    /// Logs the execution of the block at `address`, mixed with the n-gram history or the calling context
+    pub fn log_block(&mut self, address: u64) {
+        let mut loc = xxh3_rrmxmx_mixer(address);
+        if let Some(context) = &self.context {
+            loc ^= context.ctx();
+        }
+        let idx = match &mut self.ngram {
+            Some(ngram) => ngram.index(loc, MAP_SIZE),
+            None => (loc as usize) % MAP_SIZE,
+        };
+        self.map[idx] = self.map[idx].wrapping_add(1);
+    }
+
+    /// Called before a call instruction at `call_site` is executed
+    pub fn on_call(&mut self, call_site: u64) {
+        if let Some(context) = &mut self.context {
+            context.enter(call_site);
+        }
+    }
+
+    /// Called before a return instruction is executed
+    pub fn on_ret(&mut self) {
+        if let Some(context) = &mut self.context {
+            context.exit();
+        }
+    }
+
+    /// Checks if the instruction with the given `mnemonic` is a call (`Some(true)`) or a return (`Some(false)`)
+    #[must_use]
+    pub fn is_call_or_ret(mnemonic: &str) -> Option<bool> {
+        #[cfg(target_arch = "x86_64")]
+        match mnemonic {
+            "call" => Some(true),
+            "ret" | "retf" => Some(false),
+            _ => None,
+        }
+        #[cfg(target_arch = "aarch64")]
+        match mnemonic {
+            "bl" | "blr" | "blraa" | "blraaz" | "blrab" | "blrabz" => Some(true),
+            "ret" | "retaa" | "retab" => Some(false),
+            _ => None,
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            let _ = mnemonic;
+            None
         }
     }
 
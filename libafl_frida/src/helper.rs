@@ -267,7 +267,11 @@ where
                             first = false;
                             //println!("block @ {:x} transformed to {:x}", address, output.writer().pc());
                             if let Some(rt) = helper.runtime_mut::<CoverageRuntime>() {
-                                rt.emit_coverage_mapping(address, &output);
+                                if rt.uses_history() {
+                                    instruction.put_callout(|_context| rt.log_block(address));
+                                } else {
+                                    rt.emit_coverage_mapping(address, &output);
+                                }
                             }
 
                             #[cfg(unix)]
@@ -284,6 +288,20 @@ where
                             }
                         }
 
+                        if let Some(rt) = helper.runtime_mut::<CoverageRuntime>() {
+                            if rt.uses_calling_context() {
+                                match instr.mnemonic().and_then(CoverageRuntime::is_call_or_ret) {
+                                    Some(true) => {
+                                        instruction.put_callout(|_context| rt.on_call(address));
+                                    }
+                                    Some(false) => {
+                                        instruction.put_callout(|_context| rt.on_ret());
+                                    }
+                                    None => (),
+                                }
+                            }
+                        }
+
                         #[cfg(unix)]
                         let res = if let Some(_rt) = helper.runtime::<AsanRuntime>() {
                             AsanRuntime::asan_is_interesting_instruction(
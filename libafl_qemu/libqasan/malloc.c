@@ -184,7 +184,6 @@ void *__libqasan_malloc(size_t size) {
   p->aligned_orig = NULL;
   p->next = p->prev = NULL;
 
-  QASAN_ALLOC(&p[1], (char *)&p[1] + size);
   QASAN_POISON(p->redzone, REDZONE_SIZE, ASAN_HEAP_LEFT_RZ);
   if (size & (ALLOC_ALIGN_SIZE - 1))
     QASAN_POISON((char *)&p[1] + size,
@@ -195,6 +194,9 @@ void *__libqasan_malloc(size_t size) {
 
   __builtin_memset(&p[1], 0xff, size);
 
+  // after the memset, so that the chunk starts uninitialized
+  QASAN_ALLOC(&p[1], (char *)&p[1] + size);
+
   return &p[1];
 }
 
@@ -298,7 +300,6 @@ int __libqasan_posix_memalign(void **ptr, size_t align, size_t len) {
   p->requested_size = len;
   p->aligned_orig = orig;
 
-  QASAN_ALLOC(data, data + len);
   QASAN_POISON(p->redzone, REDZONE_SIZE, ASAN_HEAP_LEFT_RZ);
   if (len & (ALLOC_ALIGN_SIZE - 1))
     QASAN_POISON(
@@ -310,6 +311,9 @@ int __libqasan_posix_memalign(void **ptr, size_t align, size_t len) {
 
   __builtin_memset(data, 0xff, len);
 
+  // after the memset, so that the chunk starts uninitialized
+  QASAN_ALLOC(data, data + len);
+
   *ptr = data;
 
   return 0;
@@ -26,6 +26,7 @@ pub enum Regs {
     R13 = 13,
     R14 = 14,
     R15 = 15,
+    Cpsr = 25,
 }
 
 /// alias registers
@@ -1,12 +1,13 @@
 use libafl::{inputs::Input, state::HasMetadata};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use std::{env, fs, pin::Pin, ptr};
+use std::{cell::Cell, collections::BTreeMap, env, fs, pin::Pin, ptr};
 
 use crate::{
+    cmplog::QemuCmpLogHelper,
     emu::{Emulator, SyscallHookResult},
     helper::{QemuHelper, QemuHelperTuple, QemuInstrumentationFilter},
     hooks::QemuHooks,
-    GuestAddr, Regs,
+    GuestAddr, Regs, SYS_pwrite64, SYS_sendto, SYS_write,
 };
 
 // TODO at some point, merge parts with libafl_frida
@@ -113,6 +114,104 @@ unsafe extern "C" fn asan_giovese_populate_context(ctx: *mut CallContext, _pc: u
 
 static mut ASAN_INITED: bool = false;
 
+/// The last load of the current thread, if it read uninitialized memory
+#[derive(Debug, Clone, Copy)]
+struct UninitLoad {
+    addr: GuestAddr,
+    size: usize,
+    value: u64,
+}
+
+/// The byte written to the uninitialized byte at `addr` before it is loaded, for the compare checks.
+/// It is odd and in `0x81..=0xbf`, so it differs from the usual constants like `0`, `-1` and the ASCII characters,
+/// and it only depends on the address, so that the runs are reproducible.
+fn uninit_pattern(addr: u64) -> u8 {
+    let hash = (addr.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 56) as u8;
+    0x81 | (hash & 0x3e)
+}
+
+thread_local!(static LAST_UNINIT_LOAD: Cell<Option<UninitLoad>> = Cell::new(None));
+
+/// The initialization state of each byte of the heap allocations made by the QASan allocator
+#[derive(Debug, Default)]
+struct UninitShadow {
+    chunks: BTreeMap<u64, Vec<bool>>,
+}
+
+impl UninitShadow {
+    fn alloc(&mut self, start: u64, end: u64) {
+        self.chunks
+            .insert(start, vec![false; end.saturating_sub(start) as usize]);
+    }
+
+    fn dealloc(&mut self, start: u64) {
+        self.chunks.remove(&start);
+    }
+
+    /// The chunk containing `addr`, and the offset of `addr` in it
+    fn chunk_mut(&mut self, addr: u64) -> Option<(&mut Vec<bool>, usize)> {
+        let (start, init) = self.chunks.range_mut(..=addr).next_back()?;
+        let offset = (addr - start) as usize;
+        if offset < init.len() {
+            Some((init, offset))
+        } else {
+            None
+        }
+    }
+
+    /// Marks the bytes as initialized
+    fn store(&mut self, addr: u64, size: usize) {
+        if let Some((init, offset)) = self.chunk_mut(addr) {
+            let end = init.len().min(offset + size);
+            init[offset..end].iter_mut().for_each(|b| *b = true);
+        }
+    }
+
+    fn is_uninit(&self, addr: u64) -> bool {
+        self.first_uninit(addr, 1).is_some()
+    }
+
+    /// The address of the first uninitialized byte in the range, if any
+    fn first_uninit(&self, addr: u64, size: usize) -> Option<u64> {
+        self.chunks
+            .range(..addr + size as u64)
+            .rev()
+            .take_while(|(start, init)| **start + init.len() as u64 > addr)
+            .filter_map(|(start, init)| {
+                let from = addr.saturating_sub(*start) as usize;
+                let to = init.len().min((addr + size as u64 - start) as usize);
+                init[from..to]
+                    .iter()
+                    .position(|b| !*b)
+                    .map(|i| start + (from + i) as u64)
+            })
+            .min()
+    }
+
+    fn clear(&mut self) {
+        self.chunks.clear();
+    }
+}
+
+/// Reports the use of uninitialized memory and aborts, like the ASan reports
+fn report_uninit(emulator: &Emulator, addr: u64, size: usize, what: &str) -> ! {
+    eprintln!(
+        "=================================================================\n\
+         =={}==ERROR: AddressSanitizer: use-of-uninitialized-value on address {:#x} in {} at pc {:#x} sp {:#x}\n\
+         The {} bytes at {:#x} were allocated but never written\n\
+         =={}==ABORTING",
+        std::process::id(),
+        addr,
+        what,
+        emulator.read_reg(Regs::Pc).unwrap_or(u64::MAX),
+        emulator.read_reg(Regs::Sp).unwrap_or(u64::MAX),
+        size,
+        addr,
+        std::process::id()
+    );
+    unsafe { libc::abort() }
+}
+
 pub fn init_with_asan(args: &mut Vec<String>, env: &mut [(String, String)]) -> Emulator {
     assert!(!args.is_empty());
     let current = env::current_exe().unwrap();
@@ -168,6 +267,8 @@ pub type QemuAsanChildHelper = QemuAsanHelper;
 pub struct QemuAsanHelper {
     enabled: bool,
     filter: QemuInstrumentationFilter,
+    uninit: Option<UninitShadow>,
+    uninit_cmp: bool,
 }
 
 impl QemuAsanHelper {
@@ -177,6 +278,117 @@ impl QemuAsanHelper {
         Self {
             enabled: true,
             filter,
+            uninit: None,
+            uninit_cmp: false,
+        }
+    }
+
+    /// Also track which bytes of the heap allocations were written, and report the syscalls
+    /// (`write`, `pwrite64`, `sendto`) that use uninitialized heap memory, like `MSan`.
+    /// Copies of uninitialized memory are considered initialized.
+    /// Use-after-return is not detected, it needs fake stack frames that cannot be added to a binary-only target.
+    #[must_use]
+    pub fn with_uninit_checks(mut self) -> Self {
+        self.uninit = Some(UninitShadow::default());
+        self
+    }
+
+    /// Like [`Self::with_uninit_checks`], and also report the compares using a value loaded from uninitialized heap memory.
+    ///
+    /// Without taint propagation, this is a heuristic: before an uninitialized byte is loaded, it is filled
+    /// with a pattern that constant operands are very unlikely to have, and a compare is considered to use
+    /// the last load of the thread if no other load happened in between and one of its operands has the loaded value.
+    /// It uses the compare hooks, so it cannot be used together with the [`QemuCmpLogHelper`].
+    #[must_use]
+    pub fn with_uninit_cmp_checks(mut self) -> Self {
+        self.uninit = Some(UninitShadow::default());
+        self.uninit_cmp = true;
+        self
+    }
+
+    #[must_use]
+    pub fn uninit_checks(&self) -> bool {
+        self.uninit.is_some()
+    }
+
+    #[must_use]
+    pub fn uninit_cmp_checks(&self) -> bool {
+        self.uninit_cmp
+    }
+
+    /// Records the load for the compare checks, if it reads uninitialized memory
+    fn uninit_load(&mut self, emulator: &Emulator, addr: GuestAddr, size: usize) {
+        if !self.uninit_cmp {
+            return;
+        }
+        if let Some(uninit) = &self.uninit {
+            let start: u64 = addr.into();
+            let load = if self.enabled && size <= 8 && uninit.first_uninit(start, size).is_some() {
+                let mut buf = [0; 8];
+                unsafe { emulator.read_mem(addr, &mut buf[..size]) };
+                // The content of the uninitialized bytes is undefined, give them a recognizable value
+                for (i, byte) in buf[..size].iter_mut().enumerate() {
+                    if uninit.is_uninit(start + i as u64) {
+                        *byte = uninit_pattern(start + i as u64);
+                    }
+                }
+                unsafe { emulator.write_mem(addr, &buf[..size]) };
+                Some(UninitLoad {
+                    addr,
+                    size,
+                    value: u64::from_le_bytes(buf),
+                })
+            } else {
+                None
+            };
+            LAST_UNINIT_LOAD.with(|last| last.set(load));
+        }
+    }
+
+    fn uninit_store(&mut self, addr: GuestAddr, size: usize) {
+        if let Some(uninit) = &mut self.uninit {
+            uninit.store(addr.into(), size);
+        }
+    }
+
+    /// Reports a compare of `size` bytes using the value of the last load, if it read uninitialized memory.
+    /// The loaded value can be zero or sign extended to the size of the compare.
+    pub fn check_uninit_cmp(&mut self, emulator: &Emulator, size: usize, v0: u64, v1: u64) {
+        if !self.enabled || !self.uninit_cmp {
+            return;
+        }
+        if let Some(load) = LAST_UNINIT_LOAD.with(Cell::get) {
+            let mask = |size: usize| {
+                if size >= 8 {
+                    u64::MAX
+                } else {
+                    (1 << (size * 8)) - 1
+                }
+            };
+            let zext = load.value & mask(load.size);
+            let sign = 1 << (load.size * 8 - 1);
+            let sext = if zext & sign == 0 {
+                zext
+            } else {
+                zext | !mask(load.size)
+            };
+            let (zext, sext) = (zext & mask(size), sext & mask(size));
+            let matches = |v: u64| v & mask(size) == zext || v & mask(size) == sext;
+            if matches(v0) || matches(v1) {
+                report_uninit(emulator, load.addr.into(), load.size, "a compare");
+            }
+        }
+    }
+
+    /// Reports a syscall reading uninitialized memory from the buffer
+    pub fn check_uninit_syscall(&mut self, emulator: &Emulator, buf: GuestAddr, size: usize) {
+        if !self.enabled || size == 0 {
+            return;
+        }
+        if let Some(uninit) = &self.uninit {
+            if let Some(addr) = uninit.first_uninit(buf.into(), size) {
+                report_uninit(emulator, addr, size, "a syscall");
+            }
         }
     }
 
@@ -194,8 +406,10 @@ impl QemuAsanHelper {
         self.enabled = enabled;
     }
 
-    #[allow(clippy::unused_self)]
     pub fn alloc(&mut self, _emulator: &Emulator, start: u64, end: u64) {
+        if let Some(uninit) = &mut self.uninit {
+            uninit.alloc(start, end);
+        }
         unsafe {
             let ctx: *const CallContext =
                 libc::calloc(core::mem::size_of::<CallContext>(), 1) as *const _;
@@ -203,8 +417,10 @@ impl QemuAsanHelper {
         }
     }
 
-    #[allow(clippy::unused_self)]
     pub fn dealloc(&mut self, emulator: &Emulator, addr: u64) {
+        if let Some(uninit) = &mut self.uninit {
+            uninit.dealloc(addr);
+        }
         unsafe {
             let ckinfo = asan_giovese_alloc_search(addr);
             if let Some(ck) = ckinfo.as_mut() {
@@ -222,6 +438,18 @@ impl QemuAsanHelper {
         }
     }
 
+    /// The start and end addresses of the live heap allocation containing `addr`, if any
+    #[allow(clippy::unused_self)]
+    #[must_use]
+    pub fn allocation(&self, addr: GuestAddr) -> Option<(u64, u64)> {
+        unsafe {
+            asan_giovese_alloc_search(addr.into())
+                .as_ref()
+                .filter(|ck| ck.free_ctx.is_null())
+                .map(|ck| (ck.start, ck.end))
+        }
+    }
+
     #[allow(clippy::unused_self)]
     #[must_use]
     pub fn is_poisoned(&self, emulator: &Emulator, addr: GuestAddr, size: usize) -> bool {
@@ -241,6 +469,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_load(emulator, addr, 1);
     }
 
     pub fn read_2(&mut self, emulator: &Emulator, addr: GuestAddr) {
@@ -256,6 +485,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_load(emulator, addr, 2);
     }
 
     pub fn read_4(&mut self, emulator: &Emulator, addr: GuestAddr) {
@@ -271,6 +501,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_load(emulator, addr, 4);
     }
 
     pub fn read_8(&mut self, emulator: &Emulator, addr: GuestAddr) {
@@ -286,6 +517,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_load(emulator, addr, 8);
     }
 
     pub fn read_n(&mut self, emulator: &Emulator, addr: GuestAddr, size: usize) {
@@ -301,6 +533,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_load(emulator, addr, size);
     }
 
     pub fn write_1(&mut self, emulator: &Emulator, addr: GuestAddr) {
@@ -316,6 +549,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_store(addr, 1);
     }
 
     pub fn write_2(&mut self, emulator: &Emulator, addr: GuestAddr) {
@@ -331,6 +565,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_store(addr, 2);
     }
 
     pub fn write_4(&mut self, emulator: &Emulator, addr: GuestAddr) {
@@ -346,6 +581,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_store(addr, 4);
     }
 
     pub fn write_8(&mut self, emulator: &Emulator, addr: GuestAddr) {
@@ -361,6 +597,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_store(addr, 8);
     }
 
     pub fn write_n(&mut self, emulator: &Emulator, addr: GuestAddr, size: usize) {
@@ -376,6 +613,7 @@ impl QemuAsanHelper {
                 );
             }
         }
+        self.uninit_store(addr, size);
     }
 
     #[allow(clippy::unused_self)]
@@ -394,9 +632,12 @@ impl QemuAsanHelper {
         unsafe { asan_giovese_unpoison_region(emulator.g2h(addr), size) };
     }
 
-    #[allow(clippy::unused_self)]
     pub fn reset(&mut self) {
         unsafe { asan_giovese_alloc_remove(0, u64::MAX) };
+        if let Some(uninit) = &mut self.uninit {
+            uninit.clear();
+        }
+        LAST_UNINIT_LOAD.with(|last| last.set(None));
     }
 }
 
@@ -432,6 +673,18 @@ where
         hooks.write_n_execution(trace_write_n_asan::<I, QT, S>);
 
         hooks.syscalls(qasan_fake_syscall::<I, QT, S>);
+
+        if self.uninit_cmp_checks() {
+            assert!(
+                hooks.helpers().match_first_type::<QemuCmpLogHelper>().is_none(),
+                "The uninitialized memory compare checks of QemuAsanHelper cannot be used with QemuCmpLogHelper"
+            );
+            hooks.cmp_generation(gen_cmp_uninit::<I, QT, S>);
+            hooks.cmp8_execution(trace_cmp8_uninit::<I, QT, S>);
+            hooks.cmp4_execution(trace_cmp4_uninit::<I, QT, S>);
+            hooks.cmp2_execution(trace_cmp2_uninit::<I, QT, S>);
+            hooks.cmp1_execution(trace_cmp1_uninit::<I, QT, S>);
+        }
     }
 
     fn post_exec(&mut self, _emulator: &Emulator, _input: &I) {
@@ -669,6 +922,92 @@ where
         }
         SyscallHookResult::new(Some(r))
     } else {
+        match i64::from(sys_num) {
+            SYS_write | SYS_pwrite64 | SYS_sendto => {
+                let h = helpers.match_first_type_mut::<QemuAsanHelper>().unwrap();
+                h.check_uninit_syscall(emulator, a1 as GuestAddr, a2 as usize);
+            }
+            _ => (),
+        }
         SyscallHookResult::new(None)
     }
 }
+
+pub fn gen_cmp_uninit<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    pc: u64,
+    _size: usize,
+) -> Option<u64>
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type_mut::<QemuAsanHelper>().unwrap();
+    if h.must_instrument(pc) {
+        Some(pc)
+    } else {
+        None
+    }
+}
+
+pub fn trace_cmp1_uninit<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    v0: u8,
+    v1: u8,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type_mut::<QemuAsanHelper>().unwrap();
+    h.check_uninit_cmp(emulator, 1, v0.into(), v1.into());
+}
+
+pub fn trace_cmp2_uninit<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    v0: u16,
+    v1: u16,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type_mut::<QemuAsanHelper>().unwrap();
+    h.check_uninit_cmp(emulator, 2, v0.into(), v1.into());
+}
+
+pub fn trace_cmp4_uninit<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    v0: u32,
+    v1: u32,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type_mut::<QemuAsanHelper>().unwrap();
+    h.check_uninit_cmp(emulator, 4, v0.into(), v1.into());
+}
+
+pub fn trace_cmp8_uninit<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    v0: u64,
+    v1: u64,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type_mut::<QemuAsanHelper>().unwrap();
+    h.check_uninit_cmp(emulator, 8, v0, v1);
+}
@@ -0,0 +1,389 @@
+//! Call and return tracing for `libafl_qemu`.
+//!
+//! The [`QemuCallTracerHelper`] decodes the calls and returns of each translated block and hooks them,
+//! to keep a shadow call stack for every guest thread.
+//! The [`QemuCallStackObserver`] hashes the call stack of a crashing run for crash deduplication,
+//! while [`crate::edges::QemuEdgeHistory::call_context`] uses it for calling context sensitive coverage.
+
+use capstone::{arch::BuildsCapstone, Capstone};
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Debug, Formatter},
+    pin::Pin,
+};
+use hashbrown::HashSet;
+use libafl::{
+    bolts::tuples::Named,
+    executors::ExitKind,
+    inputs::Input,
+    observers::{Observer, ObserverWithHashField},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emu::{Emulator, GuestAddr},
+    helper::{hash_me, QemuHelper, QemuHelperTuple, QemuInstrumentationFilter},
+    hooks::QemuHooks,
+    Regs,
+};
+
+/// The guest page size, decoding a block never reads across a page boundary
+pub(crate) const PAGE_SIZE: u64 = 4096;
+
+/// A frame of the shadow call stack
+#[derive(Debug, Clone, Copy)]
+struct CallFrame {
+    ret_addr: u64,
+    ctx: u64,
+}
+
+thread_local!(static SHADOW_STACK: UnsafeCell<Vec<CallFrame>> = UnsafeCell::new(vec![]));
+
+/// The return addresses on the shadow call stack of the current guest thread, outermost first
+#[must_use]
+pub fn call_stack() -> Vec<u64> {
+    SHADOW_STACK.with(|stack| {
+        let stack = unsafe { &*stack.get() };
+        stack.iter().map(|frame| frame.ret_addr).collect()
+    })
+}
+
+/// The depth of the shadow call stack of the current guest thread
+#[must_use]
+pub fn call_depth() -> usize {
+    SHADOW_STACK.with(|stack| unsafe { &*stack.get() }.len())
+}
+
+/// A hash of the current calling context, `0` outside of any traced call
+#[must_use]
+pub fn call_context() -> u64 {
+    SHADOW_STACK.with(|stack| unsafe { &*stack.get() }.last().map_or(0, |frame| frame.ctx))
+}
+
+/// A hash of the innermost `max_depth` frames of the shadow call stack, or of all frames if `None`
+#[must_use]
+pub fn call_stack_hash(max_depth: Option<usize>) -> u64 {
+    SHADOW_STACK.with(|stack| {
+        let stack = unsafe { &*stack.get() };
+        let skip = max_depth.map_or(0, |depth| stack.len().saturating_sub(depth));
+        stack[skip..].iter().fold(0_u64, |hash, frame| {
+            hash.rotate_left(1) ^ hash_me(frame.ret_addr)
+        })
+    })
+}
+
+/// Clears the shadow call stack of the current guest thread
+pub fn reset_call_stack() {
+    SHADOW_STACK.with(|stack| unsafe { &mut *stack.get() }.clear());
+}
+
+extern "C" fn on_call(ret_addr: u64) {
+    SHADOW_STACK.with(|stack| {
+        let stack = unsafe { &mut *stack.get() };
+        let ctx = stack.last().map_or(0, |frame| frame.ctx).rotate_left(1) ^ hash_me(ret_addr);
+        stack.push(CallFrame { ret_addr, ctx });
+    });
+}
+
+extern "C" fn on_ret(_pc: u64) {
+    let emu = Emulator::new_empty();
+    let target = return_target(&emu);
+    SHADOW_STACK.with(|stack| {
+        let stack = unsafe { &mut *stack.get() };
+        match target {
+            // unwind up to the frame we return to, this also handles longjmp and exceptions
+            Some(target) => {
+                if let Some(idx) = stack.iter().rposition(|frame| frame.ret_addr == target) {
+                    stack.truncate(idx);
+                }
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    });
+}
+
+/// The address the return instruction at the current pc returns to, if it can be known before executing it
+#[cfg(cpu_target = "x86_64")]
+fn return_target(emu: &Emulator) -> Option<u64> {
+    let sp: GuestAddr = emu.read_reg(Regs::Sp).ok()?;
+    let mut buf = [0; 8];
+    unsafe { emu.read_mem(sp, &mut buf) };
+    Some(u64::from_le_bytes(buf))
+}
+
+#[cfg(cpu_target = "i386")]
+fn return_target(emu: &Emulator) -> Option<u64> {
+    let sp: GuestAddr = emu.read_reg(Regs::Sp).ok()?;
+    let mut buf = [0; 4];
+    unsafe { emu.read_mem(sp, &mut buf) };
+    Some(u32::from_le_bytes(buf).into())
+}
+
+// `ret` and `bx lr` return to the link register, for `pop {pc}` and the like we just pop a frame
+#[cfg(any(cpu_target = "arm", cpu_target = "aarch64"))]
+fn return_target(emu: &Emulator) -> Option<u64> {
+    let pc: GuestAddr = emu.read_reg(Regs::Pc).ok()?;
+    let mut insn = [0; 4];
+    unsafe { emu.read_mem(pc, &mut insn) };
+    let insn = u32::from_le_bytes(insn);
+    #[cfg(cpu_target = "aarch64")]
+    let to_lr = insn == 0xd65f_03c0; // ret
+    #[cfg(cpu_target = "arm")]
+    let to_lr = insn & 0x0fff_ffff == 0x012f_ff1e; // bx lr
+    if to_lr {
+        let lr: GuestAddr = emu.read_reg(Regs::Lr).ok()?;
+        Some(lr.into())
+    } else {
+        None
+    }
+}
+
+/// Builds a [`Capstone`] disassembler for the guest architecture, with the instruction details (such as the groups) enabled
+#[cfg(cpu_target = "x86_64")]
+pub(crate) fn build_capstone() -> Capstone {
+    Capstone::new()
+        .x86()
+        .mode(capstone::arch::x86::ArchMode::Mode64)
+        .detail(true)
+        .build()
+        .expect("Failed to create Capstone object")
+}
+
+#[cfg(cpu_target = "i386")]
+pub(crate) fn build_capstone() -> Capstone {
+    Capstone::new()
+        .x86()
+        .mode(capstone::arch::x86::ArchMode::Mode32)
+        .detail(true)
+        .build()
+        .expect("Failed to create Capstone object")
+}
+
+#[cfg(cpu_target = "arm")]
+pub(crate) fn build_capstone() -> Capstone {
+    Capstone::new()
+        .arm()
+        .mode(capstone::arch::arm::ArchMode::Arm)
+        .detail(true)
+        .build()
+        .expect("Failed to create Capstone object")
+}
+
+#[cfg(cpu_target = "aarch64")]
+pub(crate) fn build_capstone() -> Capstone {
+    Capstone::new()
+        .arm64()
+        .mode(capstone::arch::arm64::ArchMode::Arm)
+        .detail(true)
+        .build()
+        .expect("Failed to create Capstone object")
+}
+
+/// Decides if an instruction is a call (`Some(true)`), a return (`Some(false)`) or neither
+#[must_use]
+pub fn is_call_or_ret(mnemonic: &str, op_str: &str) -> Option<bool> {
+    #[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+    {
+        let _ = op_str;
+        match mnemonic {
+            "call" => Some(true),
+            "ret" | "retf" => Some(false),
+            _ => None,
+        }
+    }
+    #[cfg(cpu_target = "arm")]
+    match mnemonic {
+        "bl" | "blx" => Some(true),
+        "bx" if op_str == "lr" => Some(false),
+        "pop" | "ldm" | "ldmia" if op_str.contains("pc") => Some(false),
+        "mov" if op_str == "pc, lr" => Some(false),
+        _ => None,
+    }
+    #[cfg(cpu_target = "aarch64")]
+    {
+        let _ = op_str;
+        match mnemonic {
+            "bl" | "blr" | "blraa" | "blraaz" | "blrab" | "blrabz" => Some(true),
+            "ret" | "retaa" | "retab" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps a shadow call stack per guest thread, hooking the calls and returns of the translated code.
+/// On ARM, only code in ARM mode is decoded, Thumb code is not traced.
+pub struct QemuCallTracerHelper {
+    filter: QemuInstrumentationFilter,
+    cs: Capstone,
+    hooked: HashSet<u64>,
+}
+
+impl Debug for QemuCallTracerHelper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QemuCallTracerHelper")
+            .field("filter", &self.filter)
+            .field("hooked", &self.hooked.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl QemuCallTracerHelper {
+    #[must_use]
+    pub fn new(filter: QemuInstrumentationFilter) -> Self {
+        Self {
+            filter,
+            cs: build_capstone(),
+            hooked: HashSet::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: u64) -> bool {
+        self.filter.allowed(addr)
+    }
+
+    /// Decodes the block starting at `pc` up to its first call or return, and hooks it
+    fn decode_block(&mut self, emulator: &Emulator, pc: u64) {
+        let len = (PAGE_SIZE - (pc % PAGE_SIZE)) as usize;
+        let code = unsafe { std::slice::from_raw_parts(emulator.g2h::<u8>(pc as GuestAddr), len) };
+        let insns = match self.cs.disasm_all(code, pc) {
+            Ok(insns) => insns,
+            Err(_) => return,
+        };
+        for insn in insns.iter() {
+            let kind = is_call_or_ret(insn.mnemonic().unwrap_or(""), insn.op_str().unwrap_or(""));
+            if let Some(is_call) = kind {
+                let addr = insn.address();
+                if self.hooked.insert(addr) {
+                    if is_call {
+                        let ret_addr = addr + insn.bytes().len() as u64;
+                        emulator.set_hook(addr as GuestAddr, on_call, ret_addr);
+                    } else {
+                        emulator.set_hook(addr as GuestAddr, on_ret, addr);
+                    }
+                }
+                // a call or return ends the block
+                break;
+            }
+        }
+    }
+}
+
+impl Default for QemuCallTracerHelper {
+    fn default() -> Self {
+        Self::new(QemuInstrumentationFilter::None)
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuCallTracerHelper
+where
+    I: Input,
+{
+    fn init_hooks<'a, QT>(&self, hooks: Pin<&QemuHooks<'a, I, QT, S>>)
+    where
+        QT: QemuHelperTuple<I, S>,
+    {
+        hooks.block_generation(gen_call_tracer_blocks::<I, QT, S>);
+    }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        reset_call_stack();
+    }
+}
+
+pub fn gen_call_tracer_blocks<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    pc: u64,
+) -> Option<u64>
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    if let Some(h) = helpers.match_first_type_mut::<QemuCallTracerHelper>() {
+        if h.must_instrument(pc) {
+            h.decode_block(emulator, pc);
+        }
+    }
+    // the calls and returns are hooked directly, there is nothing to do when the block executes
+    None
+}
+
+/// An observer hashing the shadow call stack of the [`QemuCallTracerHelper`] when the target crashes,
+/// for crash deduplication with the `NewHashFeedback`, like the `BacktraceObserver` does for native targets.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QemuCallStackObserver {
+    observer_name: String,
+    max_depth: Option<usize>,
+    hash: Option<u64>,
+    call_stack: Vec<u64>,
+}
+
+impl QemuCallStackObserver {
+    /// Creates a new [`QemuCallStackObserver`] hashing the whole call stack
+    #[must_use]
+    pub fn new(observer_name: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            max_depth: None,
+            hash: None,
+            call_stack: vec![],
+        }
+    }
+
+    /// Creates a new [`QemuCallStackObserver`] hashing only the innermost `max_depth` frames
+    #[must_use]
+    pub fn with_max_depth(observer_name: &str, max_depth: usize) -> Self {
+        Self {
+            max_depth: Some(max_depth),
+            ..Self::new(observer_name)
+        }
+    }
+
+    /// The return addresses on the call stack of the last crash, outermost first
+    #[must_use]
+    pub fn call_stack(&self) -> &[u64] {
+        &self.call_stack
+    }
+}
+
+impl ObserverWithHashField for QemuCallStackObserver {
+    #[must_use]
+    fn hash(&self) -> &Option<u64> {
+        &self.hash
+    }
+
+    fn update_hash(&mut self, hash: u64) {
+        self.hash = Some(hash);
+    }
+
+    fn clear_hash(&mut self) {
+        self.hash = None;
+    }
+}
+
+impl<I, S> Observer<I, S> for QemuCallStackObserver
+where
+    I: Input,
+{
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        if exit_kind == &ExitKind::Crash {
+            self.call_stack = call_stack();
+            self.update_hash(call_stack_hash(self.max_depth));
+        } else {
+            self.call_stack.clear();
+            self.clear_hash();
+        }
+        Ok(())
+    }
+}
+
+impl Named for QemuCallStackObserver {
+    fn name(&self) -> &str {
+        &self.observer_name
+    }
+}
@@ -0,0 +1,290 @@
+//! [`DrCov`](https://dynamorio.org/page_drcov.html) coverage for `libafl_qemu`, to load the blocks
+//! executed by an input into coverage analysis tools such as [Lighthouse](https://github.com/gaasedelen/lighthouse)
+//! or [bncov](https://github.com/ForAllSecure/bncov).
+//!
+//! The [`QemuDrCovHelper`] records the basic blocks executed in each run, the [`QemuDrCovFeedback`]
+//! writes them to a drcov file for each new corpus entry or objective it is part of.
+
+use capstone::{Capstone, InsnGroupId, InsnGroupType};
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Debug, Formatter},
+    pin::Pin,
+};
+use hashbrown::HashMap;
+use libafl::{
+    bolts::tuples::Named, corpus::Testcase, events::EventFirer, executors::ExitKind,
+    feedbacks::Feedback, inputs::Input, observers::ObserversTuple, state::HasClientPerfMonitor,
+    Error,
+};
+pub use libafl_targets::drcov::{DrCovBasicBlock, DrCovWriter};
+use rangemap::RangeMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    calls::{build_capstone, PAGE_SIZE},
+    emu::{Emulator, GuestAddr, GuestMaps},
+    helper::{QemuHelper, QemuHelperTuple, QemuInstrumentationFilter},
+    hooks::QemuHooks,
+};
+
+/// The start and end addresses of the translated blocks, indexed by block id
+static mut DRCOV_BLOCKS: Vec<(u64, u64)> = vec![];
+
+/// The ids of the blocks executed in the current run, and if a block id was already recorded.
+/// Only the blocks executed by the thread running the fuzzer (the main guest thread) are recorded.
+thread_local!(
+    static DRCOV_TRACE: UnsafeCell<(Vec<usize>, Vec<bool>)> = UnsafeCell::new((vec![], vec![]))
+);
+
+/// Records the basic blocks executed in each run, for the [`QemuDrCovFeedback`].
+///
+/// QEMU does not report the size of the blocks it translates, so the helper decodes each new block
+/// up to its first jump, call, return or interrupt, which is where QEMU ends the block as well.
+/// On ARM, only code in ARM mode is decoded, Thumb blocks are recorded with the size of a single byte.
+pub struct QemuDrCovHelper {
+    filter: QemuInstrumentationFilter,
+    cs: Capstone,
+}
+
+impl Debug for QemuDrCovHelper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QemuDrCovHelper")
+            .field("filter", &self.filter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl QemuDrCovHelper {
+    /// Creates a new [`QemuDrCovHelper`], recording the blocks allowed by the `filter`
+    #[must_use]
+    pub fn new(filter: QemuInstrumentationFilter) -> Self {
+        Self {
+            filter,
+            cs: build_capstone(),
+        }
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: u64) -> bool {
+        self.filter.allowed(addr)
+    }
+
+    /// The end address of the block starting at `pc`, after its first control flow instruction.
+    /// Decoding stops at the end of the page, if nothing can be decoded the block is one byte long.
+    fn block_end(&self, emulator: &Emulator, pc: u64) -> u64 {
+        let len = (PAGE_SIZE - (pc % PAGE_SIZE)) as usize;
+        let code = unsafe { std::slice::from_raw_parts(emulator.g2h::<u8>(pc as GuestAddr), len) };
+        let insns = match self.cs.disasm_all(code, pc) {
+            Ok(insns) => insns,
+            Err(_) => return pc + 1,
+        };
+        let mut end = pc + 1;
+        for insn in insns.iter() {
+            end = insn.address() + insn.bytes().len() as u64;
+            if self.ends_block(insn) {
+                break;
+            }
+        }
+        end
+    }
+
+    /// If the instruction changes the control flow, which ends a basic block
+    fn ends_block(&self, insn: &capstone::Insn) -> bool {
+        let detail = match self.cs.insn_detail(insn) {
+            Ok(detail) => detail,
+            Err(_) => return false,
+        };
+        detail.groups().iter().any(|InsnGroupId(group)| {
+            matches!(
+                u32::from(*group),
+                InsnGroupType::CS_GRP_JUMP
+                    | InsnGroupType::CS_GRP_CALL
+                    | InsnGroupType::CS_GRP_RET
+                    | InsnGroupType::CS_GRP_INT
+                    | InsnGroupType::CS_GRP_IRET
+            )
+        })
+    }
+}
+
+impl Default for QemuDrCovHelper {
+    fn default() -> Self {
+        Self::new(QemuInstrumentationFilter::None)
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuDrCovHelper
+where
+    I: Input,
+{
+    fn init_hooks<'a, QT>(&self, hooks: Pin<&QemuHooks<'a, I, QT, S>>)
+    where
+        QT: QemuHelperTuple<I, S>,
+    {
+        hooks.blocks(
+            gen_drcov_block_ids::<I, QT, S>,
+            trace_drcov_block::<I, QT, S>,
+        );
+    }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        DRCOV_TRACE.with(|trace| {
+            let (ids, recorded) = unsafe { &mut *trace.get() };
+            for id in ids.drain(..) {
+                recorded[id] = false;
+            }
+        });
+    }
+}
+
+pub fn gen_drcov_block_ids<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    pc: u64,
+) -> Option<u64>
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let end = match helpers.match_first_type::<QemuDrCovHelper>() {
+        Some(h) if !h.must_instrument(pc) => return None,
+        Some(h) => h.block_end(emulator, pc),
+        None => pc + 1,
+    };
+    unsafe {
+        let id = DRCOV_BLOCKS.len();
+        DRCOV_BLOCKS.push((pc, end));
+        Some(id as u64)
+    }
+}
+
+pub fn trace_drcov_block<I, QT, S>(
+    _emulator: &Emulator,
+    _helpers: &mut QT,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let id = id as usize;
+    DRCOV_TRACE.with(|trace| {
+        let (ids, recorded) = unsafe { &mut *trace.get() };
+        if recorded.len() <= id {
+            recorded.resize(unsafe { DRCOV_BLOCKS.len() }.max(id + 1), false);
+        }
+        if !recorded[id] {
+            recorded[id] = true;
+            ids.push(id);
+        }
+    });
+}
+
+/// The modules currently mapped in the guest, as expected by the [`DrCovWriter`]
+#[must_use]
+pub fn drcov_module_mapping() -> RangeMap<usize, (u16, String)> {
+    let mut ids = HashMap::<String, u16>::new();
+    let mut module_mapping = RangeMap::new();
+    for map in GuestMaps::new() {
+        let path = match map.path() {
+            // skip anonymous mappings and [stack], [heap], etc.
+            Some(path) if !path.is_empty() && !path.starts_with('[') => path.to_string(),
+            _ => continue,
+        };
+        let next_id = ids.len() as u16;
+        let id = *ids.entry(path.clone()).or_insert(next_id);
+        let start = u64::from(map.start()) as usize;
+        let end = u64::from(map.end()) as usize;
+        if start < end {
+            module_mapping.insert(start..end, (id, path));
+        }
+    }
+    module_mapping
+}
+
+/// Writes the blocks executed in the last run to a drcov file
+pub fn write_drcov_trace<P>(path: P) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let module_mapping = drcov_module_mapping();
+
+    let mut executed: Vec<(u64, u64)> = DRCOV_TRACE.with(|trace| {
+        let (ids, _) = unsafe { &*trace.get() };
+        ids.iter().map(|id| unsafe { DRCOV_BLOCKS[*id] }).collect()
+    });
+    executed.sort_unstable();
+    executed.dedup();
+
+    let basic_blocks: Vec<DrCovBasicBlock> = executed
+        .into_iter()
+        .filter_map(|(pc, end)| {
+            let (range, _) = module_mapping.get_key_value(&(pc as usize))?;
+            Some(DrCovBasicBlock::new(
+                pc as usize,
+                end.min(range.end as u64) as usize,
+            ))
+        })
+        .collect();
+
+    DrCovWriter::new(&module_mapping).write(path, &basic_blocks)
+}
+
+/// Writes a drcov file of the last run for each testcase added to the corpus or to the objectives,
+/// named after the input. It never considers an input interesting on its own.
+/// Requires a [`QemuDrCovHelper`] and the in-process [`crate::QemuExecutor`].
+#[derive(Debug)]
+pub struct QemuDrCovFeedback {
+    dir: PathBuf,
+}
+
+impl QemuDrCovFeedback {
+    /// Creates a new [`QemuDrCovFeedback`], writing the drcov files to the directory `dir`
+    pub fn new<P>(dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+}
+
+impl<I, S> Feedback<I, S> for QemuDrCovFeedback
+where
+    I: Input,
+    S: HasClientPerfMonitor,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        Ok(false)
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(input) = testcase.input() {
+            let path = self.dir.join(format!("{}.drcov", input.generate_name(0)));
+            write_drcov_trace(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Named for QemuDrCovFeedback {
+    #[inline]
+    fn name(&self) -> &str {
+        "QemuDrCovFeedback"
+    }
+}
@@ -1,5 +1,6 @@
 use hashbrown::{hash_map::Entry, HashMap};
 use libafl::{inputs::Input, state::HasMetadata};
+use libafl_targets::NgramHistory;
 pub use libafl_targets::{
     edges_max_num, EDGES_MAP, EDGES_MAP_PTR, EDGES_MAP_PTR_SIZE, EDGES_MAP_SIZE, MAX_EDGES_NUM,
 };
@@ -7,11 +8,71 @@ use serde::{Deserialize, Serialize};
 use std::{cell::UnsafeCell, cmp::max, pin::Pin};
 
 use crate::{
+    calls::call_context,
     emu::Emulator,
     helper::{hash_me, QemuHelper, QemuHelperTuple, QemuInstrumentationFilter},
     hooks::QemuHooks,
 };
 
+/// The execution history mixed into the edge ids, for n-gram and calling context sensitive coverage
+#[derive(Debug, Default)]
+pub struct QemuEdgeHistory {
+    ngram: Option<NgramHistory>,
+    call_context: bool,
+}
+
+impl QemuEdgeHistory {
+    /// No history, plain edge coverage
+    #[must_use]
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// N-gram coverage of the last `size` edges
+    #[must_use]
+    pub fn ngram(size: usize) -> Self {
+        Self {
+            ngram: Some(NgramHistory::new(size)),
+            call_context: false,
+        }
+    }
+
+    /// Calling context sensitive coverage, using the shadow call stack of the
+    /// [`crate::calls::QemuCallTracerHelper`], which must be part of the helpers.
+    #[must_use]
+    pub fn call_context() -> Self {
+        Self {
+            ngram: None,
+            call_context: true,
+        }
+    }
+
+    /// If the edge ids are mixed with a history at all
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.ngram.is_some() || self.call_context
+    }
+
+    /// Forgets the history, before each execution
+    pub fn reset(&mut self) {
+        if let Some(ngram) = &mut self.ngram {
+            ngram.reset();
+        }
+    }
+
+    /// The map index of the edge `id` in the current history, in a map of `map_size` entries
+    pub fn index(&mut self, id: u64, map_size: usize) -> usize {
+        let mut loc = id;
+        if self.call_context {
+            loc ^= call_context();
+        }
+        match &mut self.ngram {
+            Some(ngram) => ngram.index(loc, map_size),
+            None => (loc as usize) % map_size,
+        }
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct QemuEdgesMapMetadata {
     pub map: HashMap<(u64, u64), u64>,
@@ -34,6 +95,7 @@ libafl::impl_serdeany!(QemuEdgesMapMetadata);
 pub struct QemuEdgeCoverageHelper {
     filter: QemuInstrumentationFilter,
     use_hitcounts: bool,
+    history: QemuEdgeHistory,
 }
 
 impl QemuEdgeCoverageHelper {
@@ -42,6 +104,7 @@ impl QemuEdgeCoverageHelper {
         Self {
             filter,
             use_hitcounts: true,
+            history: QemuEdgeHistory::none(),
         }
     }
 
@@ -50,9 +113,34 @@ impl QemuEdgeCoverageHelper {
         Self {
             filter,
             use_hitcounts: false,
+            history: QemuEdgeHistory::none(),
         }
     }
 
+    /// Edge coverage mixed with a [`QemuEdgeHistory`], i.e. n-gram or calling context sensitive coverage.
+    /// The ids are hashed over the whole `EDGES_MAP`.
+    #[must_use]
+    pub fn with_history(filter: QemuInstrumentationFilter, history: QemuEdgeHistory) -> Self {
+        Self {
+            filter,
+            use_hitcounts: true,
+            history,
+        }
+    }
+
+    /// N-gram coverage of the last `size` edges
+    #[must_use]
+    pub fn with_ngram(filter: QemuInstrumentationFilter, size: usize) -> Self {
+        Self::with_history(filter, QemuEdgeHistory::ngram(size))
+    }
+
+    /// Calling context sensitive edge coverage, see [`QemuEdgeHistory::call_context`].
+    /// The [`crate::calls::QemuCallTracerHelper`] must be part of the helpers to track the calls.
+    #[must_use]
+    pub fn with_calling_context(filter: QemuInstrumentationFilter) -> Self {
+        Self::with_history(filter, QemuEdgeHistory::call_context())
+    }
+
     #[must_use]
     pub fn must_instrument(&self, addr: u64) -> bool {
         self.filter.allowed(addr)
@@ -75,12 +163,22 @@ where
         QT: QemuHelperTuple<I, S>,
     {
         hooks.edge_generation(gen_unique_edge_ids::<I, QT, S>);
-        if self.use_hitcounts {
+        if self.history.is_enabled() {
+            if self.use_hitcounts {
+                hooks.edge_execution(trace_edge_history_hitcount::<I, QT, S>);
+            } else {
+                hooks.edge_execution(trace_edge_history_single::<I, QT, S>);
+            }
+        } else if self.use_hitcounts {
             hooks.emulator().set_exec_edge_hook(trace_edge_hitcount);
         } else {
             hooks.emulator().set_exec_edge_hook(trace_edge_single);
         }
     }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        self.history.reset();
+    }
 }
 
 pub type QemuCollidingEdgeCoverageHelper = QemuEdgeCoverageChildHelper;
@@ -89,6 +187,7 @@ pub type QemuCollidingEdgeCoverageHelper = QemuEdgeCoverageChildHelper;
 pub struct QemuEdgeCoverageChildHelper {
     filter: QemuInstrumentationFilter,
     use_hitcounts: bool,
+    history: QemuEdgeHistory,
 }
 
 impl QemuEdgeCoverageChildHelper {
@@ -97,6 +196,7 @@ impl QemuEdgeCoverageChildHelper {
         Self {
             filter,
             use_hitcounts: true,
+            history: QemuEdgeHistory::none(),
         }
     }
 
@@ -105,9 +205,33 @@ impl QemuEdgeCoverageChildHelper {
         Self {
             filter,
             use_hitcounts: false,
+            history: QemuEdgeHistory::none(),
+        }
+    }
+
+    /// Edge coverage mixed with a [`QemuEdgeHistory`], i.e. n-gram or calling context sensitive coverage
+    #[must_use]
+    pub fn with_history(filter: QemuInstrumentationFilter, history: QemuEdgeHistory) -> Self {
+        Self {
+            filter,
+            use_hitcounts: true,
+            history,
         }
     }
 
+    /// N-gram coverage of the last `size` edges
+    #[must_use]
+    pub fn with_ngram(filter: QemuInstrumentationFilter, size: usize) -> Self {
+        Self::with_history(filter, QemuEdgeHistory::ngram(size))
+    }
+
+    /// Calling context sensitive edge coverage, see [`QemuEdgeHistory::call_context`].
+    /// The [`crate::calls::QemuCallTracerHelper`] must be part of the helpers to track the calls.
+    #[must_use]
+    pub fn with_calling_context(filter: QemuInstrumentationFilter) -> Self {
+        Self::with_history(filter, QemuEdgeHistory::call_context())
+    }
+
     #[must_use]
     pub fn must_instrument(&self, addr: u64) -> bool {
         self.filter.allowed(addr)
@@ -132,12 +256,22 @@ where
         QT: QemuHelperTuple<I, S>,
     {
         hooks.edge_generation(gen_hashed_edge_ids::<I, QT, S>);
-        if self.use_hitcounts {
+        if self.history.is_enabled() {
+            if self.use_hitcounts {
+                hooks.edge_execution(trace_edge_history_hitcount_ptr::<I, QT, S>);
+            } else {
+                hooks.edge_execution(trace_edge_history_single_ptr::<I, QT, S>);
+            }
+        } else if self.use_hitcounts {
             hooks.emulator().set_exec_edge_hook(trace_edge_hitcount_ptr);
         } else {
             hooks.emulator().set_exec_edge_hook(trace_edge_single_ptr);
         }
     }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        self.history.reset();
+    }
 }
 
 thread_local!(static PREV_LOC : UnsafeCell<u64> = UnsafeCell::new(0));
@@ -154,10 +288,12 @@ where
     I: Input,
     QT: QemuHelperTuple<I, S>,
 {
+    let mut use_history = false;
     if let Some(h) = helpers.match_first_type::<QemuEdgeCoverageHelper>() {
         if !h.must_instrument(src) && !h.must_instrument(dest) {
             return None;
         }
+        use_history = h.history.is_enabled();
     }
     let state = state.expect("The gen_unique_edge_ids hook works only for in-process fuzzing");
     if state.metadata().get::<QemuEdgesMapMetadata>().is_none() {
@@ -168,14 +304,14 @@ where
         .get_mut::<QemuEdgesMapMetadata>()
         .unwrap();
 
-    match meta.map.entry((src, dest)) {
+    let id = match meta.map.entry((src, dest)) {
         Entry::Occupied(e) => {
             let id = *e.get();
             let nxt = (id as usize + 1) & (EDGES_MAP_SIZE - 1);
             unsafe {
                 MAX_EDGES_NUM = max(MAX_EDGES_NUM, nxt);
             }
-            Some(id)
+            id
         }
         Entry::Vacant(e) => {
             let id = meta.current_id;
@@ -184,9 +320,16 @@ where
             unsafe {
                 MAX_EDGES_NUM = meta.current_id as usize;
             }
-            Some(id as u64)
+            id
+        }
+    };
+    if use_history {
+        // The ids mixed with the history are spread over the whole map
+        unsafe {
+            MAX_EDGES_NUM = EDGES_MAP_SIZE;
         }
     }
+    Some(id)
 }
 
 pub extern "C" fn trace_edge_hitcount(id: u64) {
@@ -201,6 +344,40 @@ pub extern "C" fn trace_edge_single(id: u64) {
     }
 }
 
+pub fn trace_edge_history_hitcount<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    if let Some(h) = helpers.match_first_type_mut::<QemuEdgeCoverageHelper>() {
+        let idx = h.history.index(id, EDGES_MAP_SIZE);
+        unsafe {
+            EDGES_MAP[idx] = EDGES_MAP[idx].wrapping_add(1);
+        }
+    }
+}
+
+pub fn trace_edge_history_single<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    if let Some(h) = helpers.match_first_type_mut::<QemuEdgeCoverageHelper>() {
+        let idx = h.history.index(id, EDGES_MAP_SIZE);
+        unsafe {
+            EDGES_MAP[idx] = 1;
+        }
+    }
+}
+
 pub fn gen_hashed_edge_ids<I, QT, S>(
     _emulator: &Emulator,
     helpers: &mut QT,
@@ -234,6 +411,40 @@ pub extern "C" fn trace_edge_single_ptr(id: u64) {
     }
 }
 
+pub fn trace_edge_history_hitcount_ptr<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    if let Some(h) = helpers.match_first_type_mut::<QemuEdgeCoverageChildHelper>() {
+        unsafe {
+            let ptr = EDGES_MAP_PTR.add(h.history.index(id, EDGES_MAP_PTR_SIZE));
+            *ptr = (*ptr).wrapping_add(1);
+        }
+    }
+}
+
+pub fn trace_edge_history_single_ptr<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    if let Some(h) = helpers.match_first_type_mut::<QemuEdgeCoverageChildHelper>() {
+        unsafe {
+            let ptr = EDGES_MAP_PTR.add(h.history.index(id, EDGES_MAP_PTR_SIZE));
+            *ptr = 1;
+        }
+    }
+}
+
 pub fn gen_addr_block_ids<I, QT, S>(
     _emulator: &Emulator,
     _helpers: &mut QT,
@@ -1,5 +1,6 @@
 //! Utilities to parse and process ELFs
 
+use core::ops::Range;
 use goblin::elf::{header::ET_DYN, Elf};
 use std::{convert::AsRef, fs::File, io::Read, path::Path, str};
 
@@ -9,6 +10,8 @@ use crate::GuestAddr;
 
 pub struct EasyElf<'a> {
     elf: Elf<'a>,
+    #[cfg_attr(not(feature = "dwarf"), allow(dead_code))]
+    data: &'a [u8],
 }
 
 impl<'a> EasyElf<'a> {
@@ -16,17 +19,14 @@ impl<'a> EasyElf<'a> {
     where
         P: AsRef<Path>,
     {
-        let elf = {
-            let mut binary_file = File::open(path)?;
-            binary_file.read_to_end(buffer)?;
-            Elf::parse(buffer).map_err(|e| Error::unknown(format!("{}", e)))
-        }?;
-        Ok(Self { elf })
+        let mut binary_file = File::open(path)?;
+        binary_file.read_to_end(buffer)?;
+        Self::from_slice(buffer)
     }
 
     pub fn from_slice(buffer: &'a [u8]) -> Result<Self, Error> {
         let elf = Elf::parse(buffer).map_err(|e| Error::unknown(format!("{}", e)))?;
-        Ok(Self { elf })
+        Ok(Self { elf, data: buffer })
     }
 
     #[must_use]
@@ -57,7 +57,221 @@ impl<'a> EasyElf<'a> {
         None
     }
 
+    /// The address range of the symbol `name`, such as a global variable, using its size in the symbol table
+    #[must_use]
+    pub fn resolve_symbol_range(
+        &self,
+        name: &str,
+        load_addr: GuestAddr,
+    ) -> Option<Range<GuestAddr>> {
+        let start = self.resolve_symbol(name, load_addr)?;
+        let size = self
+            .elf
+            .syms
+            .iter()
+            .find(|sym| self.elf.strtab.get_at(sym.st_name) == Some(name))?
+            .st_size;
+        Some(start..start + (size as GuestAddr).max(1))
+    }
+
+    /// The address ranges of the functions whose symbol name matches the glob `pattern`,
+    /// looking at both the static and the dynamic symbol table
+    #[must_use]
+    pub fn function_ranges_matching(
+        &self,
+        pattern: &str,
+        load_addr: GuestAddr,
+    ) -> Vec<Range<GuestAddr>> {
+        let offset = if self.is_pic() { load_addr } else { 0 };
+        let mut ranges: Vec<Range<GuestAddr>> = self
+            .elf
+            .syms
+            .iter()
+            .filter_map(|sym| Some((sym, self.elf.strtab.get_at(sym.st_name)?)))
+            .chain(
+                self.elf
+                    .dynsyms
+                    .iter()
+                    .filter_map(|sym| Some((sym, self.elf.dynstrtab.get_at(sym.st_name)?))),
+            )
+            .filter(|(sym, name)| {
+                sym.is_function() && sym.st_value != 0 && glob_match(pattern, name)
+            })
+            .map(|(sym, _)| {
+                let start = sym.st_value as GuestAddr + offset;
+                start..start + (sym.st_size as GuestAddr).max(1)
+            })
+            .collect();
+        ranges.sort_unstable_by_key(|r| r.start);
+        ranges.dedup();
+        ranges
+    }
+
+    /// The address ranges of the code compiled from the source files whose path matches the glob `pattern`,
+    /// according to the DWARF line tables of this ELF
+    #[cfg(feature = "dwarf")]
+    pub fn source_file_ranges_matching(
+        &self,
+        pattern: &str,
+        load_addr: GuestAddr,
+    ) -> Result<Vec<Range<GuestAddr>>, Error> {
+        use gimli::{EndianSlice, RunTimeEndian};
+        use hashbrown::HashMap;
+        use std::{borrow::Cow, path::PathBuf};
+
+        let endian = if self.elf.little_endian {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let load_section = |id: gimli::SectionId| -> Result<Cow<[u8]>, gimli::Error> {
+            let data = self
+                .elf
+                .section_headers
+                .iter()
+                .find(|sh| self.elf.shdr_strtab.get_at(sh.sh_name) == Some(id.name()))
+                .and_then(|sh| {
+                    self.data
+                        .get(sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize)
+                })
+                .unwrap_or(&[]);
+            Ok(Cow::Borrowed(data))
+        };
+        let dwarf_cow =
+            gimli::Dwarf::load(&load_section).map_err(|e| Error::unknown(format!("{}", e)))?;
+        let dwarf = dwarf_cow.borrow(|section| EndianSlice::new(section, endian));
+
+        let offset = if self.is_pic() { load_addr } else { 0 };
+        let mut ranges = vec![];
+        let mut units = dwarf.units();
+        while let Some(header) = units.next().map_err(|e| Error::unknown(format!("{}", e)))? {
+            let unit = dwarf
+                .unit(header)
+                .map_err(|e| Error::unknown(format!("{}", e)))?;
+            let program = match unit.line_program.clone() {
+                Some(program) => program,
+                None => continue,
+            };
+            // If the file with the given index matches the pattern, per unit
+            let mut matching = HashMap::<u64, bool>::new();
+            let mut current: Option<u64> = None;
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows
+                .next_row()
+                .map_err(|e| Error::unknown(format!("{}", e)))?
+            {
+                let addr = row.address();
+                if let Some(start) = current.take() {
+                    if addr > start {
+                        ranges.push(start as GuestAddr + offset..addr as GuestAddr + offset);
+                    }
+                }
+                if row.end_sequence() {
+                    continue;
+                }
+                let is_match = *matching.entry(row.file_index()).or_insert_with(|| {
+                    row.file(header).map_or(false, |file| {
+                        let mut path = PathBuf::new();
+                        if let Some(dir) = file.directory(header) {
+                            if let Ok(dir) = dwarf.attr_string(&unit, dir) {
+                                path.push(&*dir.to_string_lossy());
+                            }
+                        }
+                        if let Ok(name) = dwarf.attr_string(&unit, file.path_name()) {
+                            path.push(&*name.to_string_lossy());
+                        }
+                        glob_match(pattern, &path.to_string_lossy())
+                    })
+                });
+                if is_match {
+                    current = Some(addr);
+                }
+            }
+        }
+        ranges.sort_unstable_by_key(|r| r.start);
+        Ok(merge_ranges(ranges))
+    }
+
     fn is_pic(&self) -> bool {
         self.elf.header.e_type == ET_DYN
     }
 }
+
+/// Merges overlapping and adjacent ranges, the ranges must be sorted by their start
+#[must_use]
+pub fn merge_ranges<T>(ranges: Vec<Range<T>>) -> Vec<Range<T>>
+where
+    T: Ord + Copy,
+{
+    let mut merged: Vec<Range<T>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Matches `text` against a glob `pattern`, where `*` matches any sequence of characters and `?` any single one
+#[must_use]
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let text = text.as_bytes();
+    let (mut p, mut t) = (0, 0);
+    // the position after the last `*` in the pattern, and where it started matching in the text
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(c) if *c == b'?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, merge_ranges};
+
+    #[test]
+    fn test_merge_ranges() {
+        assert_eq!(merge_ranges::<u64>(vec![]), vec![]);
+        assert_eq!(merge_ranges(vec![0..2, 4..6]), vec![0..2, 4..6]);
+        // overlapping, adjacent and contained ranges
+        assert_eq!(
+            merge_ranges(vec![0..4, 2..6, 6..8, 10..20, 12..14]),
+            vec![0..8, 10..20]
+        );
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("libc.so.6", "libc.so.6"));
+        assert!(!glob_match("libc.so.6", "libc.so"));
+        assert!(glob_match("libc*", "libc.so.6"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.so*", "libpng.so.16"));
+        assert!(!glob_match("*.so", "libpng.so.16"));
+        assert!(glob_match("lib?ng*", "libpng.so.16"));
+        assert!(!glob_match("lib?", "lib"));
+        // backtracking over a partial match
+        assert!(glob_match("*ab", "aab"));
+        assert!(glob_match("a*b*c", "axbxbyc"));
+        assert!(!glob_match("a*b*c", "axbxby"));
+    }
+}
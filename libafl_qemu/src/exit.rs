@@ -0,0 +1,287 @@
+//! Exit status and final state of the guest for `libafl_qemu` in user mode.
+//!
+//! The [`QemuExitHelper`] catches the `exit_group` syscall of the guest, used by the libc `exit` and when
+//! returning from `main`: instead of terminating the fuzzer with the guest, it records the exit code
+//! and stops the emulator right after the syscall.
+//! The [`QemuExitObserver`] reports the exit code of the last run, and the [`QemuExitSnapshotObserver`]
+//! the registers and the memory ranges of the guest where the run stopped.
+//!
+//! With a [`libafl::executors::DiffExecutor`] running the same target natively in a
+//! [`libafl::executors::CommandExecutor`], that exposes a `StdOutObserver`, a `StdErrObserver` and
+//! an `ExitStatusObserver`, these observers and the [`crate::QemuFdOutputObserver`] of the captured
+//! file descriptors 1 and 2 can be compared by [`libafl::feedbacks::DiffFeedback`]s,
+//! to find emulator bugs or architecture-dependent behavior.
+
+use core::pin::Pin;
+use libafl::{
+    bolts::tuples::Named, executors::ExitKind, inputs::Input, observers::Observer, Error,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emu::{Emulator, GuestAddr, GuestUsize, SyscallHookResult},
+    helper::{QemuHelper, QemuHelperTuple},
+    hooks::QemuHooks,
+    Regs, SYS_exit_group,
+};
+
+/// The exit code of the guest in the current run, if it exited
+static mut EXIT_CODE: Option<i32> = None;
+/// The breakpoint stopping the guest after its exit syscall
+static mut EXIT_BREAKPOINT: Option<GuestAddr> = None;
+
+/// The exit code of the guest in the current run, if it exited
+#[must_use]
+pub fn exit_code() -> Option<i32> {
+    unsafe { EXIT_CODE }
+}
+
+/// Stops the guest when it exits, see the [module-level documentation](self).
+/// Threads exiting with the `exit` syscall are not affected.
+#[derive(Debug, Default)]
+pub struct QemuExitHelper;
+
+impl QemuExitHelper {
+    /// Creates a new [`QemuExitHelper`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuExitHelper
+where
+    I: Input,
+{
+    fn init_hooks<'a, QT>(&self, hooks: Pin<&QemuHooks<'a, I, QT, S>>)
+    where
+        QT: QemuHelperTuple<I, S>,
+    {
+        hooks.syscalls(exit_syscalls::<I, QT, S>);
+    }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        unsafe { EXIT_CODE = None };
+    }
+
+    fn post_exec(&mut self, emulator: &Emulator, _input: &I) {
+        if let Some(addr) = unsafe { EXIT_BREAKPOINT.take() } {
+            emulator.remove_breakpoint(addr);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exit_syscalls<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: u64,
+    _a1: u64,
+    _a2: u64,
+    _a3: u64,
+    _a4: u64,
+    _a5: u64,
+    _a6: u64,
+    _a7: u64,
+) -> SyscallHookResult
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    if i64::from(sys_num) != SYS_exit_group
+        || helpers.match_first_type::<QemuExitHelper>().is_none()
+    {
+        return SyscallHookResult::new(None);
+    }
+    // The program counter is already past the syscall instruction, the guest stops there
+    let pc: GuestAddr = emulator.read_reg(Regs::Pc).unwrap();
+    emulator.set_breakpoint(pc);
+    unsafe {
+        // The status seen by the parent of a native process
+        EXIT_CODE = Some((a0 & 0xff) as i32);
+        if let Some(addr) = EXIT_BREAKPOINT.replace(pc) {
+            if addr != pc {
+                emulator.remove_breakpoint(addr);
+            }
+        }
+    }
+    SyscallHookResult::new(Some(0))
+}
+
+/// Reports the exit code of the guest caught by the [`QemuExitHelper`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QemuExitObserver {
+    observer_name: String,
+    code: Option<i32>,
+}
+
+impl QemuExitObserver {
+    /// Creates a new [`QemuExitObserver`]
+    #[must_use]
+    pub fn new(observer_name: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            code: None,
+        }
+    }
+
+    /// The exit code of the last run, `None` if the guest did not exit
+    #[must_use]
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+}
+
+impl<I, S> Observer<I, S> for QemuExitObserver
+where
+    I: Input,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.code = None;
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.code = exit_code();
+        Ok(())
+    }
+
+    fn post_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.code = exit_code();
+        Ok(())
+    }
+}
+
+impl Named for QemuExitObserver {
+    fn name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+/// Takes a snapshot of the chosen registers and memory ranges of the guest where the run stopped
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QemuExitSnapshotObserver {
+    observer_name: String,
+    /// The ids of the registers, as [`Regs`] is not serializable
+    regs: Vec<i32>,
+    ranges: Vec<(GuestAddr, usize)>,
+    reg_values: Vec<GuestUsize>,
+    memory: Vec<Vec<u8>>,
+}
+
+impl QemuExitSnapshotObserver {
+    /// Creates a new [`QemuExitSnapshotObserver`], without registers or memory ranges
+    #[must_use]
+    pub fn new(observer_name: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            regs: vec![],
+            ranges: vec![],
+            reg_values: vec![],
+            memory: vec![],
+        }
+    }
+
+    /// Adds a register to the snapshot
+    #[must_use]
+    pub fn reg(mut self, reg: Regs) -> Self {
+        self.regs.push(reg.into());
+        self
+    }
+
+    /// Adds registers to the snapshot
+    #[must_use]
+    pub fn regs<IT>(mut self, regs: IT) -> Self
+    where
+        IT: IntoIterator<Item = Regs>,
+    {
+        self.regs.extend(regs.into_iter().map(i32::from));
+        self
+    }
+
+    /// Adds `len` bytes of memory at `addr` to the snapshot.
+    /// The range must be mapped in the guest when the run stops.
+    #[must_use]
+    pub fn mem(mut self, addr: GuestAddr, len: usize) -> Self {
+        self.ranges.push((addr, len));
+        self
+    }
+
+    /// The values of the registers at the end of the last run, in the order they were added
+    #[must_use]
+    pub fn reg_values(&self) -> &[GuestUsize] {
+        &self.reg_values
+    }
+
+    /// The content of the memory ranges at the end of the last run, in the order they were added
+    #[must_use]
+    pub fn memory(&self) -> &[Vec<u8>] {
+        &self.memory
+    }
+
+    fn snapshot(&mut self) -> Result<(), Error> {
+        let emulator = Emulator::new_empty();
+        self.reg_values = self
+            .regs
+            .iter()
+            .map(|reg| emulator.read_reg(*reg).map_err(Error::unknown))
+            .collect::<Result<Vec<GuestUsize>, Error>>()?;
+        self.memory = self
+            .ranges
+            .iter()
+            .map(|(addr, len)| {
+                let mut buf = vec![0; *len];
+                unsafe { emulator.read_mem(*addr, &mut buf) };
+                buf
+            })
+            .collect();
+        Ok(())
+    }
+}
+
+impl<I, S> Observer<I, S> for QemuExitSnapshotObserver
+where
+    I: Input,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.reg_values.clear();
+        self.memory.clear();
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.snapshot()
+    }
+
+    fn post_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.snapshot()
+    }
+}
+
+impl Named for QemuExitSnapshotObserver {
+    fn name(&self) -> &str {
+        &self.observer_name
+    }
+}
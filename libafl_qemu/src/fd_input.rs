@@ -0,0 +1,646 @@
+//! Syscall-level input injection for `libafl_qemu` in user mode.
+//!
+//! The [`QemuFdInputHelper`] virtualizes selected file descriptors: the guest reads the current input
+//! from stdin, from a file opened at a chosen path, or from an accepted socket connection,
+//! without any change to the target binary.
+//! The writes to the virtual file descriptors, and to the file descriptors chosen with
+//! [`QemuFdInputHelper::capture_fd`], are collected by the [`QemuFdOutputObserver`].
+
+use core::pin::Pin;
+use hashbrown::{HashMap, HashSet};
+use libafl::{
+    bolts::{tuples::Named, AsSlice},
+    executors::ExitKind,
+    inputs::{HasTargetBytes, Input},
+    observers::Observer,
+    Error,
+};
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+
+#[cfg(not(cpu_target = "i386"))]
+use crate::SYS_accept;
+#[cfg(not(cpu_target = "aarch64"))]
+use crate::SYS_open;
+use crate::{
+    emu::{Emulator, GuestAddr, SyscallHookResult},
+    helper::{QemuHelper, QemuHelperTuple},
+    hooks::QemuHooks,
+    snapshot::QemuSnapshotHelper,
+    SYS_accept4, SYS_close, SYS_lseek, SYS_openat, SYS_pread64, SYS_read, SYS_recvfrom, SYS_sendto,
+    SYS_write,
+};
+#[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64"))]
+use crate::{SYS_fstat, SYS_newfstatat};
+#[cfg(any(cpu_target = "arm", cpu_target = "i386"))]
+use crate::{SYS_fstat64, SYS_fstatat64};
+#[cfg(cpu_target = "arm")]
+use crate::{SYS_recv, SYS_send};
+
+const AT_EMPTY_PATH: u64 = 0x1000;
+const S_IFREG: u32 = 0o100_000;
+
+// The layout of the `stat` struct filled by `fstat` on 64 bit targets, and of `stat64` on 32 bit targets
+#[cfg(not(cpu_target = "i386"))]
+const STAT_SIZE_OFFSET: usize = 48;
+#[cfg(cpu_target = "i386")]
+const STAT_SIZE_OFFSET: usize = 44;
+#[cfg(cpu_target = "x86_64")]
+const STAT_LEN: usize = 144;
+#[cfg(cpu_target = "x86_64")]
+const STAT_MODE_OFFSET: usize = 24;
+#[cfg(cpu_target = "aarch64")]
+const STAT_LEN: usize = 128;
+#[cfg(cpu_target = "aarch64")]
+const STAT_MODE_OFFSET: usize = 16;
+#[cfg(cpu_target = "arm")]
+const STAT_LEN: usize = 104;
+#[cfg(cpu_target = "i386")]
+const STAT_LEN: usize = 96;
+#[cfg(any(cpu_target = "arm", cpu_target = "i386"))]
+const STAT_MODE_OFFSET: usize = 16;
+
+/// The default maximum amount of output captured in each run, see [`QemuFdInputHelper::max_output`]
+pub const FD_OUTPUT_DEFAULT_MAX_LEN: usize = 1 << 20;
+
+/// The output written to the captured file descriptors in the current run
+static mut FD_OUTPUT: Vec<u8> = vec![];
+/// The same output, split by file descriptor
+static mut FD_OUTPUT_BY_FD: Vec<(i32, Vec<u8>)> = vec![];
+
+/// The output written to the captured file descriptors in the current run
+#[must_use]
+pub fn captured_output() -> &'static [u8] {
+    unsafe { &FD_OUTPUT }
+}
+
+/// The output written to the captured file descriptor `fd` in the current run
+#[must_use]
+pub fn captured_fd_output(fd: i32) -> &'static [u8] {
+    unsafe {
+        FD_OUTPUT_BY_FD
+            .iter()
+            .find(|(f, _)| *f == fd)
+            .map_or(&[], |(_, output)| output.as_slice())
+    }
+}
+
+/// The return value of a syscall failing with `errno`
+fn errno_result(errno: i32) -> SyscallHookResult {
+    SyscallHookResult::new(Some(-i64::from(errno) as u64))
+}
+
+/// How a syscall on a virtual file descriptor is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QemuFdBehavior {
+    /// Behave as if the input was a regular file: `lseek` moves the read offset, `fstat` reports a
+    /// regular file of the input length, `close` forgets the file descriptor.
+    Emulate,
+    /// Run the real syscall on the `/dev/null` file descriptor backing the virtual one
+    Passthrough,
+    /// Fail with this `errno`, for example `ESPIPE` for `lseek` on a stream
+    Fail(i32),
+}
+
+/// A file descriptor returning the input on read
+#[derive(Debug, Clone, Copy)]
+struct VirtualFd {
+    offset: usize,
+    /// The fd is backed by a host fd opened by the helper, stdin is not
+    backed: bool,
+}
+
+/// Virtualizes file descriptors to feed the input to the guest, see the [module-level documentation](self)
+#[derive(Debug)]
+pub struct QemuFdInputHelper {
+    stdin: bool,
+    paths: Vec<String>,
+    sockets: bool,
+    captured: HashSet<i32>,
+    max_output: usize,
+    lseek: QemuFdBehavior,
+    fstat: QemuFdBehavior,
+    close: QemuFdBehavior,
+    input: Vec<u8>,
+    fds: HashMap<i32, VirtualFd>,
+    accepted: bool,
+}
+
+impl QemuFdInputHelper {
+    /// Creates a new [`QemuFdInputHelper`], that does not virtualize any file descriptor yet
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            stdin: false,
+            paths: vec![],
+            sockets: false,
+            captured: HashSet::new(),
+            max_output: FD_OUTPUT_DEFAULT_MAX_LEN,
+            lseek: QemuFdBehavior::Emulate,
+            fstat: QemuFdBehavior::Emulate,
+            close: QemuFdBehavior::Emulate,
+            input: vec![],
+            fds: HashMap::new(),
+            accepted: false,
+        }
+    }
+
+    /// Reads on stdin return the input
+    #[must_use]
+    pub fn stdin(mut self) -> Self {
+        self.stdin = true;
+        self
+    }
+
+    /// Opening this path returns a file descriptor reading the input
+    #[must_use]
+    pub fn file(mut self, path: &str) -> Self {
+        self.paths.push(path.to_string());
+        self
+    }
+
+    /// The first `accept` of each run returns a connection receiving the input, the following ones fail with `EAGAIN`
+    #[must_use]
+    pub fn sockets(mut self) -> Self {
+        self.sockets = true;
+        self
+    }
+
+    /// Capture the writes to this real file descriptor too, e.g. 1 for stdout.
+    /// The writes still reach the file descriptor.
+    #[must_use]
+    pub fn capture_fd(mut self, fd: i32) -> Self {
+        self.captured.insert(fd);
+        self
+    }
+
+    /// The maximum amount of output captured in each run, [`FD_OUTPUT_DEFAULT_MAX_LEN`] by default.
+    /// The guest chooses the length of its writes, the bytes beyond this limit are dropped.
+    #[must_use]
+    pub fn max_output(mut self, max_output: usize) -> Self {
+        self.max_output = max_output;
+        self
+    }
+
+    /// How `lseek` on a virtual file descriptor is handled, [`QemuFdBehavior::Emulate`] by default
+    #[must_use]
+    pub fn lseek(mut self, behavior: QemuFdBehavior) -> Self {
+        self.lseek = behavior;
+        self
+    }
+
+    /// How `fstat` on a virtual file descriptor is handled, [`QemuFdBehavior::Emulate`] by default
+    #[must_use]
+    pub fn fstat(mut self, behavior: QemuFdBehavior) -> Self {
+        self.fstat = behavior;
+        self
+    }
+
+    /// How `close` on a virtual file descriptor is handled, [`QemuFdBehavior::Emulate`] by default
+    #[must_use]
+    pub fn close(mut self, behavior: QemuFdBehavior) -> Self {
+        self.close = behavior;
+        self
+    }
+
+    /// The file descriptor is virtual
+    #[must_use]
+    pub fn is_virtual(&self, fd: i32) -> bool {
+        self.fds.contains_key(&fd)
+    }
+
+    /// Opens a new virtual file descriptor, backed by `/dev/null` so that the guest gets a valid fd
+    fn open_virtual(&mut self) -> SyscallHookResult {
+        let fd = unsafe { libc::open(b"/dev/null\0".as_ptr().cast(), libc::O_RDWR) };
+        if fd < 0 {
+            return errno_result(libc::EMFILE);
+        }
+        self.fds.insert(
+            fd,
+            VirtualFd {
+                offset: 0,
+                backed: true,
+            },
+        );
+        SyscallHookResult::new(Some(fd as u64))
+    }
+
+    /// Returns the virtual connection on the first `accept` of the run
+    fn accept_virtual(&mut self) -> SyscallHookResult {
+        if self.accepted {
+            return errno_result(libc::EAGAIN);
+        }
+        self.accepted = true;
+        self.open_virtual()
+    }
+
+    fn seek(&mut self, fd: i32, offset: i64, whence: i32) -> SyscallHookResult {
+        let len = self.input.len() as i64;
+        let vfd = self.fds.get_mut(&fd).unwrap();
+        let base = match whence {
+            libc::SEEK_SET => 0,
+            libc::SEEK_CUR => vfd.offset as i64,
+            libc::SEEK_END => len,
+            _ => return errno_result(libc::EINVAL),
+        };
+        match base.checked_add(offset) {
+            Some(pos) if pos >= 0 => {
+                vfd.offset = pos as usize;
+                SyscallHookResult::new(Some(pos as u64))
+            }
+            _ => errno_result(libc::EINVAL),
+        }
+    }
+
+    /// Fills a `stat` struct describing a regular file of the input length
+    fn stat(&self, emulator: &Emulator, statbuf: GuestAddr) -> SyscallHookResult {
+        let mut stat = [0_u8; STAT_LEN];
+        stat[STAT_MODE_OFFSET..STAT_MODE_OFFSET + 4]
+            .copy_from_slice(&(S_IFREG | 0o644).to_le_bytes());
+        stat[STAT_SIZE_OFFSET..STAT_SIZE_OFFSET + 8]
+            .copy_from_slice(&(self.input.len() as i64).to_le_bytes());
+        unsafe { emulator.write_mem(statbuf, &stat) };
+        SyscallHookResult::new(Some(0))
+    }
+
+    /// Closes the host file descriptors backing the virtual ones
+    fn close_all(&mut self) {
+        for (fd, vfd) in self.fds.drain() {
+            if vfd.backed {
+                unsafe { libc::close(fd) };
+            }
+        }
+    }
+}
+
+impl Default for QemuFdInputHelper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuFdInputHelper
+where
+    I: Input + HasTargetBytes,
+{
+    fn init_hooks<'a, QT>(&self, hooks: Pin<&QemuHooks<'a, I, QT, S>>)
+    where
+        QT: QemuHelperTuple<I, S>,
+    {
+        hooks.syscalls(fd_input_syscalls::<I, QT, S>);
+    }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, input: &I) {
+        self.input.clear();
+        self.input
+            .extend_from_slice(input.target_bytes().as_slice());
+        // The guest state of the last run is gone, so are its virtual file descriptors
+        self.close_all();
+        if self.stdin {
+            self.fds.insert(
+                0,
+                VirtualFd {
+                    offset: 0,
+                    backed: false,
+                },
+            );
+        }
+        self.accepted = false;
+        unsafe {
+            FD_OUTPUT.clear();
+            FD_OUTPUT_BY_FD.clear();
+        }
+    }
+}
+
+impl Drop for QemuFdInputHelper {
+    fn drop(&mut self) {
+        self.close_all();
+    }
+}
+
+/// Reads the NUL-terminated path at `addr` in the guest, and checks if it is virtualized
+fn is_virtual_path(emulator: &Emulator, h: &QemuFdInputHelper, addr: u64) -> bool {
+    if h.paths.is_empty() || addr == 0 {
+        return false;
+    }
+    let path = unsafe { CStr::from_ptr(emulator.g2h(addr as GuestAddr)) };
+    let path = path.to_string_lossy();
+    h.paths.iter().any(|p| *p == path)
+}
+
+/// Reads the input from a virtual file descriptor, at its offset if `offset` is `None`
+fn read_virtual<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    fd: i32,
+    buf: GuestAddr,
+    len: usize,
+    offset: Option<usize>,
+) -> SyscallHookResult
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type_mut::<QemuFdInputHelper>().unwrap();
+    let vfd = h.fds.get_mut(&fd).unwrap();
+    let data = h.input.get(offset.unwrap_or(vfd.offset)..).unwrap_or(&[]);
+    let n = data.len().min(len);
+    unsafe { emulator.write_mem(buf, &data[..n]) };
+    if offset.is_none() {
+        vfd.offset += n;
+    }
+    // The memory written by the hook is not seen by the snapshot write hooks
+    if n > 0 {
+        if let Some(s) = helpers.match_first_type_mut::<QemuSnapshotHelper>() {
+            s.access(buf, n);
+        }
+    }
+    SyscallHookResult::new(Some(n as u64))
+}
+
+/// The file offset of `pread64` from its syscall arguments after the count.
+/// On 32 bit targets, the offset is split in a pair of registers.
+#[allow(unused_variables)]
+fn pread64_offset(a3: u64, a4: u64, a5: u64) -> u64 {
+    #[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64"))]
+    {
+        a3
+    }
+    #[cfg(cpu_target = "i386")]
+    {
+        (a3 & 0xffff_ffff) | (a4 << 32)
+    }
+    // The pair starts at an even register with the ARM EABI
+    #[cfg(cpu_target = "arm")]
+    {
+        (a4 & 0xffff_ffff) | (a5 << 32)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[allow(non_upper_case_globals)]
+pub fn fd_input_syscalls<I, QT, S>(
+    emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: u64,
+    a1: u64,
+    a2: u64,
+    a3: u64,
+    a4: u64,
+    a5: u64,
+    _a6: u64,
+    _a7: u64,
+) -> SyscallHookResult
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let passthrough = SyscallHookResult::new(None);
+    let h = match helpers.match_first_type_mut::<QemuFdInputHelper>() {
+        Some(h) => h,
+        None => return passthrough,
+    };
+    let fd = a0 as i32;
+    let sys_num = i64::from(sys_num);
+
+    // Output first, as it can be captured from real file descriptors too
+    let is_write = sys_num == SYS_write || sys_num == SYS_sendto;
+    #[cfg(cpu_target = "arm")]
+    let is_write = is_write || sys_num == SYS_send;
+    if is_write {
+        let virt = h.is_virtual(fd);
+        if virt || h.captured.contains(&fd) {
+            let len = a2 as usize;
+            unsafe {
+                let captured = len.min(h.max_output.saturating_sub(FD_OUTPUT.len()));
+                let mut buf = vec![0; captured];
+                emulator.read_mem(a1 as GuestAddr, &mut buf);
+                FD_OUTPUT.extend_from_slice(&buf);
+                match FD_OUTPUT_BY_FD.iter_mut().find(|(f, _)| *f == fd) {
+                    Some((_, output)) => output.extend_from_slice(&buf),
+                    None => FD_OUTPUT_BY_FD.push((fd, buf)),
+                }
+            }
+            if virt {
+                return SyscallHookResult::new(Some(len as u64));
+            }
+        }
+        return passthrough;
+    }
+
+    match sys_num {
+        #[cfg(not(cpu_target = "aarch64"))]
+        SYS_open => {
+            if is_virtual_path(emulator, h, a0) {
+                return h.open_virtual();
+            }
+        }
+        SYS_openat => {
+            if is_virtual_path(emulator, h, a1) {
+                return h.open_virtual();
+            }
+        }
+        #[cfg(not(cpu_target = "i386"))]
+        SYS_accept => {
+            if h.sockets {
+                return h.accept_virtual();
+            }
+        }
+        SYS_accept4 => {
+            if h.sockets {
+                return h.accept_virtual();
+            }
+        }
+        #[cfg(cpu_target = "arm")]
+        SYS_recv => {
+            if h.is_virtual(fd) {
+                return read_virtual::<I, QT, S>(
+                    emulator,
+                    helpers,
+                    fd,
+                    a1 as GuestAddr,
+                    a2 as usize,
+                    None,
+                );
+            }
+        }
+        SYS_read | SYS_recvfrom => {
+            if h.is_virtual(fd) {
+                return read_virtual::<I, QT, S>(
+                    emulator,
+                    helpers,
+                    fd,
+                    a1 as GuestAddr,
+                    a2 as usize,
+                    None,
+                );
+            }
+        }
+        SYS_pread64 => {
+            if h.is_virtual(fd) {
+                let offset = Some(pread64_offset(a3, a4, a5) as usize);
+                return read_virtual::<I, QT, S>(
+                    emulator,
+                    helpers,
+                    fd,
+                    a1 as GuestAddr,
+                    a2 as usize,
+                    offset,
+                );
+            }
+        }
+        SYS_lseek => {
+            if h.is_virtual(fd) {
+                match h.lseek {
+                    QemuFdBehavior::Emulate => return h.seek(fd, a1 as i64, a2 as i32),
+                    QemuFdBehavior::Passthrough => (),
+                    QemuFdBehavior::Fail(errno) => return errno_result(errno),
+                }
+            }
+        }
+        #[cfg(any(cpu_target = "arm", cpu_target = "i386"))]
+        SYS_fstat64 => {
+            if h.is_virtual(fd) {
+                match h.fstat {
+                    QemuFdBehavior::Emulate => return h.stat(emulator, a1 as GuestAddr),
+                    QemuFdBehavior::Passthrough => (),
+                    QemuFdBehavior::Fail(errno) => return errno_result(errno),
+                }
+            }
+        }
+        // On 32 bit targets, the legacy `fstat` is not used by the libcs and left to the host
+        #[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64"))]
+        SYS_fstat => {
+            if h.is_virtual(fd) {
+                match h.fstat {
+                    QemuFdBehavior::Emulate => return h.stat(emulator, a1 as GuestAddr),
+                    QemuFdBehavior::Passthrough => (),
+                    QemuFdBehavior::Fail(errno) => return errno_result(errno),
+                }
+            }
+        }
+        // fstat is implemented as fstatat(fd, "", statbuf, AT_EMPTY_PATH) by recent libcs
+        #[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64"))]
+        SYS_newfstatat => {
+            if h.is_virtual(fd) && a3 & AT_EMPTY_PATH != 0 {
+                match h.fstat {
+                    QemuFdBehavior::Emulate => return h.stat(emulator, a2 as GuestAddr),
+                    QemuFdBehavior::Passthrough => (),
+                    QemuFdBehavior::Fail(errno) => return errno_result(errno),
+                }
+            }
+        }
+        #[cfg(any(cpu_target = "arm", cpu_target = "i386"))]
+        SYS_fstatat64 => {
+            if h.is_virtual(fd) && a3 & AT_EMPTY_PATH != 0 {
+                match h.fstat {
+                    QemuFdBehavior::Emulate => return h.stat(emulator, a2 as GuestAddr),
+                    QemuFdBehavior::Passthrough => (),
+                    QemuFdBehavior::Fail(errno) => return errno_result(errno),
+                }
+            }
+        }
+        SYS_close => {
+            if let Some(vfd) = h.fds.get(&fd).copied() {
+                match h.close {
+                    QemuFdBehavior::Emulate => {
+                        h.fds.remove(&fd);
+                        if vfd.backed {
+                            unsafe { libc::close(fd) };
+                        }
+                        return SyscallHookResult::new(Some(0));
+                    }
+                    QemuFdBehavior::Passthrough => {
+                        h.fds.remove(&fd);
+                    }
+                    QemuFdBehavior::Fail(errno) => return errno_result(errno),
+                }
+            }
+        }
+        _ => (),
+    }
+    passthrough
+}
+
+/// Collects the output written to the file descriptors captured by the [`QemuFdInputHelper`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QemuFdOutputObserver {
+    observer_name: String,
+    fd: Option<i32>,
+    output: Vec<u8>,
+}
+
+impl QemuFdOutputObserver {
+    /// Creates a new [`QemuFdOutputObserver`]
+    #[must_use]
+    pub fn new(observer_name: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            fd: None,
+            output: vec![],
+        }
+    }
+
+    /// Creates a new [`QemuFdOutputObserver`] collecting only the output written to `fd`,
+    /// for example 1 to compare the stdout of the guest with a `StdOutObserver`
+    #[must_use]
+    pub fn with_fd(observer_name: &str, fd: i32) -> Self {
+        Self {
+            fd: Some(fd),
+            ..Self::new(observer_name)
+        }
+    }
+
+    fn collect(&mut self) {
+        let output = match self.fd {
+            Some(fd) => captured_fd_output(fd),
+            None => captured_output(),
+        };
+        self.output.extend_from_slice(output);
+    }
+
+    /// The output of the last run
+    #[must_use]
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+}
+
+impl<I, S> Observer<I, S> for QemuFdOutputObserver
+where
+    I: Input,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.output.clear();
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.collect();
+        Ok(())
+    }
+
+    fn post_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.collect();
+        Ok(())
+    }
+}
+
+impl Named for QemuFdOutputObserver {
+    fn name(&self) -> &str {
+        &self.observer_name
+    }
+}
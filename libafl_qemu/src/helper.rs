@@ -1,7 +1,12 @@
 use core::{fmt::Debug, ops::Range, pin::Pin};
-use libafl::{bolts::tuples::MatchFirstType, inputs::Input};
+use libafl::{bolts::tuples::MatchFirstType, inputs::Input, Error};
+use std::path::Path;
 
-use crate::{emu::Emulator, hooks::QemuHooks};
+use crate::{
+    elf::{glob_match, merge_ranges, EasyElf},
+    emu::{Emulator, GuestAddr},
+    hooks::QemuHooks,
+};
 
 /// A helper for `libafl_qemu`.
 // TODO remove 'static when specialization will be stable
@@ -113,6 +118,216 @@ impl QemuInstrumentationFilter {
     }
 }
 
+/// The modules mapped in the guest, with their path and the whole address range they span
+#[must_use]
+pub fn guest_modules(emulator: &Emulator) -> Vec<(String, Range<u64>)> {
+    let mut modules: Vec<(String, Range<u64>)> = vec![];
+    for map in emulator.mappings() {
+        let path = match map.path() {
+            Some(path) if !path.is_empty() && !path.starts_with('[') => path,
+            _ => continue,
+        };
+        let (start, end) = (u64::from(map.start()), u64::from(map.end()));
+        match modules.iter_mut().find(|(p, _)| p == path) {
+            Some((_, range)) => {
+                range.start = range.start.min(start);
+                range.end = range.end.max(end);
+            }
+            None => modules.push((path.to_string(), start..end)),
+        }
+    }
+    modules
+}
+
+/// If the module at `path` is matched by `pattern`, a glob on either its file name or its whole path
+fn module_matches(pattern: &str, path: &str) -> bool {
+    let file_name = Path::new(path)
+        .file_name()
+        .map_or(path, |name| name.to_str().unwrap_or(path));
+    glob_match(pattern, file_name) || glob_match(pattern, path)
+}
+
+/// Removes the (sorted, merged) `deny` ranges from the (sorted, merged) `allow` ranges
+fn subtract_ranges(allow: Vec<Range<u64>>, deny: &[Range<u64>]) -> Vec<Range<u64>> {
+    let mut res = vec![];
+    for mut range in allow {
+        for d in deny {
+            if d.end <= range.start || d.start >= range.end {
+                continue;
+            }
+            if d.start > range.start {
+                res.push(range.start..d.start);
+            }
+            range.start = d.end;
+            if range.start >= range.end {
+                break;
+            }
+        }
+        if range.start < range.end {
+            res.push(range);
+        }
+    }
+    res
+}
+
+/// Builds a [`QemuInstrumentationFilter`] from the modules, functions and source files
+/// to (not) instrument, resolved to addresses in the running guest.
+/// Denied addresses take precedence over allowed ones, so a module can be allowed except for some functions.
+#[derive(Debug, Default)]
+pub struct QemuInstrumentationFilterBuilder {
+    allow: Vec<Range<u64>>,
+    deny: Vec<Range<u64>>,
+}
+
+impl QemuInstrumentationFilterBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn allow_range(mut self, range: Range<u64>) -> Self {
+        self.allow.push(range);
+        self
+    }
+
+    #[must_use]
+    pub fn deny_range(mut self, range: Range<u64>) -> Self {
+        self.deny.push(range);
+        self
+    }
+
+    /// Allows the modules loaded in the guest whose file name or path matches the glob `pattern`
+    pub fn allow_module(mut self, emulator: &Emulator, pattern: &str) -> Result<Self, Error> {
+        let mut ranges = Self::module_ranges(emulator, pattern)?;
+        self.allow.append(&mut ranges);
+        Ok(self)
+    }
+
+    /// Denies the modules loaded in the guest whose file name or path matches the glob `pattern`
+    pub fn deny_module(mut self, emulator: &Emulator, pattern: &str) -> Result<Self, Error> {
+        let mut ranges = Self::module_ranges(emulator, pattern)?;
+        self.deny.append(&mut ranges);
+        Ok(self)
+    }
+
+    /// Allows the functions whose symbol name matches the glob `pattern`, in any module loaded in the guest
+    pub fn allow_functions(mut self, emulator: &Emulator, pattern: &str) -> Result<Self, Error> {
+        let mut ranges = Self::function_ranges(emulator, pattern)?;
+        self.allow.append(&mut ranges);
+        Ok(self)
+    }
+
+    /// Denies the functions whose symbol name matches the glob `pattern`, in any module loaded in the guest
+    pub fn deny_functions(mut self, emulator: &Emulator, pattern: &str) -> Result<Self, Error> {
+        let mut ranges = Self::function_ranges(emulator, pattern)?;
+        self.deny.append(&mut ranges);
+        Ok(self)
+    }
+
+    /// Allows the code compiled from the source files whose path matches the glob `pattern`,
+    /// in any module loaded in the guest that has DWARF debug info
+    #[cfg(feature = "dwarf")]
+    pub fn allow_source_files(mut self, emulator: &Emulator, pattern: &str) -> Result<Self, Error> {
+        let mut ranges = Self::source_file_ranges(emulator, pattern)?;
+        self.allow.append(&mut ranges);
+        Ok(self)
+    }
+
+    /// Denies the code compiled from the source files whose path matches the glob `pattern`,
+    /// in any module loaded in the guest that has DWARF debug info
+    #[cfg(feature = "dwarf")]
+    pub fn deny_source_files(mut self, emulator: &Emulator, pattern: &str) -> Result<Self, Error> {
+        let mut ranges = Self::source_file_ranges(emulator, pattern)?;
+        self.deny.append(&mut ranges);
+        Ok(self)
+    }
+
+    #[must_use]
+    pub fn build(self) -> QemuInstrumentationFilter {
+        let mut allow = self.allow;
+        let mut deny = self.deny;
+        allow.sort_unstable_by_key(|r| r.start);
+        deny.sort_unstable_by_key(|r| r.start);
+        let deny = merge_ranges(deny);
+        if allow.is_empty() {
+            if deny.is_empty() {
+                QemuInstrumentationFilter::None
+            } else {
+                QemuInstrumentationFilter::DenyList(deny)
+            }
+        } else {
+            QemuInstrumentationFilter::AllowList(subtract_ranges(merge_ranges(allow), &deny))
+        }
+    }
+
+    fn module_ranges(emulator: &Emulator, pattern: &str) -> Result<Vec<Range<u64>>, Error> {
+        let ranges: Vec<Range<u64>> = guest_modules(emulator)
+            .into_iter()
+            .filter(|(path, _)| module_matches(pattern, path))
+            .map(|(_, range)| range)
+            .collect();
+        if ranges.is_empty() {
+            Err(Error::key_not_found(format!(
+                "No module matching {} is loaded in the guest",
+                pattern
+            )))
+        } else {
+            Ok(ranges)
+        }
+    }
+
+    /// Resolves the ranges of the matching symbols in the ELF of each module
+    fn resolve_in_modules<F>(
+        emulator: &Emulator,
+        pattern: &str,
+        resolve: F,
+    ) -> Result<Vec<Range<u64>>, Error>
+    where
+        F: Fn(&EasyElf<'_>, GuestAddr) -> Result<Vec<Range<GuestAddr>>, Error>,
+    {
+        let mut ranges = vec![];
+        for (path, range) in guest_modules(emulator) {
+            let mut buffer = vec![];
+            // not every file mapped in the guest is an ELF
+            let elf = match EasyElf::from_file(&path, &mut buffer) {
+                Ok(elf) => elf,
+                Err(_) => continue,
+            };
+            // a module we cannot resolve in should not prevent filtering the others
+            match resolve(&elf, range.start as GuestAddr) {
+                Ok(resolved) => {
+                    for r in resolved {
+                        ranges.push(u64::from(r.start)..u64::from(r.end));
+                    }
+                }
+                Err(err) => eprintln!("Skipping {} when resolving {}: {:?}", path, pattern, err),
+            }
+        }
+        if ranges.is_empty() {
+            Err(Error::key_not_found(format!(
+                "Nothing matching {} found in the modules loaded in the guest",
+                pattern
+            )))
+        } else {
+            Ok(ranges)
+        }
+    }
+
+    fn function_ranges(emulator: &Emulator, pattern: &str) -> Result<Vec<Range<u64>>, Error> {
+        Self::resolve_in_modules(emulator, pattern, |elf, load_addr| {
+            Ok(elf.function_ranges_matching(pattern, load_addr))
+        })
+    }
+
+    #[cfg(feature = "dwarf")]
+    fn source_file_ranges(emulator: &Emulator, pattern: &str) -> Result<Vec<Range<u64>>, Error> {
+        Self::resolve_in_modules(emulator, pattern, |elf, load_addr| {
+            elf.source_file_ranges_matching(pattern, load_addr)
+        })
+    }
+}
+
 #[must_use]
 pub fn hash_me(mut x: u64) -> u64 {
     x = (x.overflowing_shr(16).0 ^ x).overflowing_mul(0x45d9f3b).0;
@@ -120,3 +335,23 @@ pub fn hash_me(mut x: u64) -> u64 {
     x = (x.overflowing_shr(16).0 ^ x) ^ x;
     x
 }
+
+#[cfg(test)]
+mod tests {
+    use super::subtract_ranges;
+
+    #[test]
+    fn test_subtract_ranges() {
+        assert_eq!(subtract_ranges(vec![0..10], &[]), vec![0..10]);
+        assert_eq!(
+            subtract_ranges(vec![0..10], &[2..4, 6..8]),
+            vec![0..2, 4..6, 8..10]
+        );
+        // deny ranges at the borders and outside of the allowed ones
+        assert_eq!(
+            subtract_ranges(vec![0..10, 20..30], &[0..2, 8..22, 40..50]),
+            vec![2..8, 22..30]
+        );
+        assert_eq!(subtract_ranges(vec![4..6], &[0..10]), vec![]);
+    }
+}
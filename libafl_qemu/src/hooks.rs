@@ -10,13 +10,15 @@ use core::{
     ptr::{self, addr_of},
 };
 
-use libafl::{executors::inprocess::inprocess_get_state, inputs::Input};
+use libafl::{executors::inprocess::inprocess_get_state, inputs::Input, Error};
 
 pub use crate::emu::SyscallHookResult;
 use crate::{
+    elf::EasyElf,
     emu::{Emulator, SKIP_EXEC_HOOK},
     helper::{QemuHelper, QemuHelperTuple},
-    GuestAddr,
+    intercept::FunctionCall,
+    GuestAddr, Regs,
 };
 
 #[repr(C)]
@@ -118,7 +120,10 @@ where
     }
 }
 
-static mut GEN_BLOCK_HOOK: Hook = Hook::Empty;
+/// Multiple helpers can observe the generation of blocks, each generation hook returns its own id for the block
+static mut GEN_BLOCK_HOOKS: Vec<Hook> = vec![];
+/// The ids returned by each generation hook, for each generated block
+static mut BLOCK_IDS: Vec<Vec<Option<u64>>> = vec![];
 extern "C" fn gen_block_hook_wrapper<I, QT, S>(pc: u64) -> u64
 where
     I: Input,
@@ -127,32 +132,43 @@ where
     unsafe {
         let helpers = get_qemu_helpers::<QT>();
         let emulator = Emulator::new_empty();
-        match &GEN_BLOCK_HOOK {
-            Hook::Function(ptr) => {
-                let func: fn(&Emulator, &mut QT, Option<&mut S>, u64) -> Option<u64> =
-                    transmute(*ptr);
-                (func)(&emulator, helpers, inprocess_get_state::<S>(), pc)
-                    .map_or(SKIP_EXEC_HOOK, |id| id)
-            }
-            Hook::Closure(ptr) => {
-                let mut func: Box<
-                    dyn FnMut(&Emulator, &mut QT, Option<&mut S>, u64) -> Option<u64>,
-                > = transmute(*ptr);
-                let ret = (func)(&emulator, helpers, inprocess_get_state::<S>(), pc)
-                    .map_or(SKIP_EXEC_HOOK, |id| id);
+        let mut ids = Vec::with_capacity(GEN_BLOCK_HOOKS.len());
+        for hook in &GEN_BLOCK_HOOKS {
+            let id = match hook {
+                Hook::Function(ptr) => {
+                    let func: fn(&Emulator, &mut QT, Option<&mut S>, u64) -> Option<u64> =
+                        transmute(*ptr);
+                    (func)(&emulator, helpers, inprocess_get_state::<S>(), pc)
+                }
+                Hook::Closure(ptr) => {
+                    let mut func: Box<
+                        dyn FnMut(&Emulator, &mut QT, Option<&mut S>, u64) -> Option<u64>,
+                    > = transmute(*ptr);
+                    let id = (func)(&emulator, helpers, inprocess_get_state::<S>(), pc);
 
-                // Forget the closure so that drop is not called on captured variables.
-                core::mem::forget(func);
+                    // Forget the closure so that drop is not called on captured variables.
+                    core::mem::forget(func);
 
-                ret
-            }
-            _ => SKIP_EXEC_HOOK,
+                    id
+                }
+                _ => None,
+            };
+            ids.push(id);
+        }
+        if ids.iter().all(Option::is_none) {
+            SKIP_EXEC_HOOK
+        } else {
+            // The execution hooks get the index of the ids of this block
+            BLOCK_IDS.push(ids);
+            (BLOCK_IDS.len() - 1) as u64
         }
     }
 }
 
-static mut BLOCK_HOOKS: Vec<Hook> = vec![];
-extern "C" fn block_hooks_wrapper<I, QT, S>(id: u64)
+/// The execution hooks, with the index of the generation hook providing their ids.
+/// Without generation hook, the first id returned by any generation hook is used.
+static mut BLOCK_HOOKS: Vec<(Option<usize>, Hook)> = vec![];
+extern "C" fn block_hooks_wrapper<I, QT, S>(index: u64)
 where
     I: Input,
     QT: QemuHelperTuple<I, S>,
@@ -160,7 +176,22 @@ where
     unsafe {
         let helpers = get_qemu_helpers::<QT>();
         let emulator = Emulator::new_empty();
-        for hook in &BLOCK_HOOKS {
+        let ids = if GEN_BLOCK_HOOKS.is_empty() {
+            None
+        } else {
+            Some(&BLOCK_IDS[index as usize])
+        };
+        for (gen, hook) in &BLOCK_HOOKS {
+            let id = match (ids, gen) {
+                // No generation hook, QEMU passes the id itself
+                (None, _) => Some(index),
+                (Some(ids), Some(gen)) => ids[*gen],
+                (Some(ids), None) => ids.iter().find_map(|id| *id),
+            };
+            let id = match id {
+                Some(id) => id,
+                None => continue,
+            };
             match hook {
                 Hook::Function(ptr) => {
                     let func: fn(&Emulator, &mut QT, Option<&mut S>, u64) = transmute(*ptr);
@@ -1065,6 +1096,103 @@ where
     }
 }
 
+static mut FUNCTION_HOOKS: Vec<(GuestAddr, Hook)> = vec![];
+/// The return addresses of the intercepted calls that were not skipped, and the intercepted functions
+static mut PENDING_RETURNS: Vec<(GuestAddr, GuestAddr)> = vec![];
+static mut FUNCTION_HOOKS_DISPATCHER: Option<fn(&Emulator, GuestAddr) -> bool> = None;
+
+/// Runs the hooks of the function intercepted at `pc`, returns false if the emulator did not stop for an interception
+fn function_hooks_dispatcher<I, QT, S>(emulator: &Emulator, pc: GuestAddr) -> bool
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    unsafe {
+        // The entry breakpoint is removed while the function runs, put it back when it returns
+        if let Some(idx) = PENDING_RETURNS.iter().rposition(|(ret, _)| *ret == pc) {
+            let (ret_addr, addr) = PENDING_RETURNS.remove(idx);
+            if !PENDING_RETURNS.iter().any(|(ret, _)| *ret == ret_addr) {
+                emulator.remove_breakpoint(ret_addr);
+            }
+            emulator.set_breakpoint(addr);
+            return true;
+        }
+
+        if !FUNCTION_HOOKS.iter().any(|(addr, _)| *addr == pc) {
+            return false;
+        }
+        // On errors, the emulator stays stopped at the entry and the run returns to the harness
+        let mut call = match FunctionCall::new(pc) {
+            Ok(call) => call,
+            Err(err) => {
+                eprintln!("Cannot read the intercepted call at {:#x}: {:?}", pc, err);
+                return false;
+            }
+        };
+        let helpers = get_qemu_helpers::<QT>();
+        for (addr, hook) in &FUNCTION_HOOKS {
+            if *addr != pc {
+                continue;
+            }
+            match hook {
+                Hook::Function(ptr) => {
+                    let func: fn(&Emulator, &mut QT, Option<&mut S>, &mut FunctionCall) =
+                        transmute(*ptr);
+                    (func)(emulator, helpers, inprocess_get_state::<S>(), &mut call);
+                }
+                Hook::Closure(ptr) => {
+                    let mut func: Box<
+                        dyn FnMut(&Emulator, &mut QT, Option<&mut S>, &mut FunctionCall),
+                    > = transmute(*ptr);
+                    (func)(emulator, helpers, inprocess_get_state::<S>(), &mut call);
+
+                    // Forget the closure so that drop is not called on captured variables.
+                    core::mem::forget(func);
+                }
+                _ => (),
+            }
+        }
+
+        if call.skipped() {
+            if let Err(err) = call.apply_skip() {
+                eprintln!("Cannot skip the intercepted call at {:#x}: {:?}", pc, err);
+                return false;
+            }
+        } else {
+            // Run the function past the entry breakpoint
+            emulator.remove_breakpoint(pc);
+            emulator.set_breakpoint(call.return_address());
+            PENDING_RETURNS.push((call.return_address(), pc));
+        }
+        true
+    }
+}
+
+/// Runs the emulator, handling the functions intercepted with [`QemuHooks::intercept_function`],
+/// until it stops at a breakpoint that is not an interception.
+/// Recursive calls to an intercepted function are not intercepted.
+///
+/// # Safety
+/// Runs the guest, as [`Emulator::run`]
+pub unsafe fn run_intercepted(emulator: &Emulator) {
+    // A previous run may have stopped inside an intercepted function
+    for (ret_addr, addr) in PENDING_RETURNS.drain(..) {
+        emulator.remove_breakpoint(ret_addr);
+        emulator.set_breakpoint(addr);
+    }
+    loop {
+        emulator.run();
+        let pc: GuestAddr = match emulator.read_reg(Regs::Pc) {
+            Ok(pc) => pc,
+            Err(_) => break,
+        };
+        match FUNCTION_HOOKS_DISPATCHER {
+            Some(dispatcher) if dispatcher(emulator, pc) => (),
+            _ => break,
+        }
+    }
+}
+
 static mut HOOKS_IS_INITIALIZED: bool = false;
 
 pub struct QemuHooks<'a, I, QT, S>
@@ -1199,7 +1327,7 @@ where
         hook: fn(&Emulator, &mut QT, Option<&mut S>, pc: u64) -> Option<u64>,
     ) {
         unsafe {
-            GEN_BLOCK_HOOK = Hook::Function(hook as *const libc::c_void);
+            GEN_BLOCK_HOOKS.push(Hook::Function(hook as *const libc::c_void));
         }
         self.emulator
             .set_gen_block_hook(gen_block_hook_wrapper::<I, QT, S>);
@@ -1210,15 +1338,17 @@ where
         hook: Box<dyn FnMut(&Emulator, &mut QT, Option<&mut S>, u64) -> Option<u64>>,
     ) {
         unsafe {
-            GEN_BLOCK_HOOK = Hook::Closure(transmute(hook));
+            GEN_BLOCK_HOOKS.push(Hook::Closure(transmute(hook)));
         }
         self.emulator
             .set_gen_block_hook(gen_block_hook_wrapper::<I, QT, S>);
     }
 
+    /// Adds a block execution hook, getting the id returned by the first generation hook that returns one.
+    /// Use [`Self::blocks`] to get the ids of a specific generation hook.
     pub fn block_execution(&self, hook: fn(&Emulator, &mut QT, Option<&mut S>, id: u64)) {
         unsafe {
-            BLOCK_HOOKS.push(Hook::Function(hook as *const libc::c_void));
+            BLOCK_HOOKS.push((None, Hook::Function(hook as *const libc::c_void)));
         }
         self.emulator
             .set_exec_block_hook(block_hooks_wrapper::<I, QT, S>);
@@ -1229,8 +1359,28 @@ where
         hook: Box<dyn FnMut(&Emulator, &mut QT, Option<&mut S>, u64)>,
     ) {
         unsafe {
-            BLOCK_HOOKS.push(Hook::Closure(transmute(hook)));
+            BLOCK_HOOKS.push((None, Hook::Closure(transmute(hook))));
+        }
+        self.emulator
+            .set_exec_block_hook(block_hooks_wrapper::<I, QT, S>);
+    }
+
+    /// Adds a block generation hook and an execution hook getting the ids it returns,
+    /// independently of the ids returned by the other generation hooks
+    pub fn blocks(
+        &self,
+        generation: fn(&Emulator, &mut QT, Option<&mut S>, pc: u64) -> Option<u64>,
+        execution: fn(&Emulator, &mut QT, Option<&mut S>, id: u64),
+    ) {
+        unsafe {
+            GEN_BLOCK_HOOKS.push(Hook::Function(generation as *const libc::c_void));
+            BLOCK_HOOKS.push((
+                Some(GEN_BLOCK_HOOKS.len() - 1),
+                Hook::Function(execution as *const libc::c_void),
+            ));
         }
+        self.emulator
+            .set_gen_block_hook(gen_block_hook_wrapper::<I, QT, S>);
         self.emulator
             .set_exec_block_hook(block_hooks_wrapper::<I, QT, S>);
     }
@@ -1579,6 +1729,47 @@ where
             .set_on_thread_hook(on_thread_hooks_wrapper::<I, QT, S>);
     }
 
+    /// Intercepts the function at `addr`, see [`crate::intercept`]
+    pub fn intercept_function(
+        &self,
+        addr: GuestAddr,
+        hook: fn(&Emulator, &mut QT, Option<&mut S>, &mut FunctionCall),
+    ) {
+        unsafe {
+            FUNCTION_HOOKS.push((addr, Hook::Function(hook as *const libc::c_void)));
+            FUNCTION_HOOKS_DISPATCHER = Some(function_hooks_dispatcher::<I, QT, S>);
+        }
+        self.emulator.set_breakpoint(addr);
+    }
+
+    /// Intercepts the function at `addr` with a closure, see [`crate::intercept`]
+    pub fn intercept_function_closure(
+        &self,
+        addr: GuestAddr,
+        hook: Box<dyn FnMut(&Emulator, &mut QT, Option<&mut S>, &mut FunctionCall) + 'a>,
+    ) {
+        unsafe {
+            FUNCTION_HOOKS.push((addr, Hook::Closure(transmute(hook))));
+            FUNCTION_HOOKS_DISPATCHER = Some(function_hooks_dispatcher::<I, QT, S>);
+        }
+        self.emulator.set_breakpoint(addr);
+    }
+
+    /// Intercepts the function named `name` in the main binary with a closure, see [`crate::intercept`]
+    pub fn intercept_symbol_closure(
+        &self,
+        name: &str,
+        hook: Box<dyn FnMut(&Emulator, &mut QT, Option<&mut S>, &mut FunctionCall) + 'a>,
+    ) -> Result<(), Error> {
+        let mut elf_buffer = Vec::new();
+        let elf = EasyElf::from_file(self.emulator.binary_path(), &mut elf_buffer)?;
+        let addr = elf
+            .resolve_symbol(name, self.emulator.load_addr())
+            .ok_or_else(|| Error::key_not_found(format!("Symbol {} not found", name)))?;
+        self.intercept_function_closure(addr, hook);
+        Ok(())
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn syscalls(
         &self,
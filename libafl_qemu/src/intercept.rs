@@ -0,0 +1,221 @@
+//! Interception of guest functions for `libafl_qemu`.
+//!
+//! A function intercepted with [`crate::QemuHooks::intercept_function`] stops the emulator at its entry,
+//! the hook gets a [`FunctionCall`] to read and modify the arguments following the calling convention
+//! of the target (`SysV` on `x86_64`, `cdecl` on `i386`, `AAPCS` on ARM and `AArch64`), and can skip
+//! the function with a fake return value.
+//! The harness must resume the emulator with [`crate::run_intercepted`] instead of [`Emulator::run`].
+
+use core::mem::size_of;
+use libafl::Error;
+
+use crate::{
+    emu::{Emulator, GuestAddr, GuestUsize},
+    Regs,
+};
+
+/// The registers of the first arguments
+#[cfg(cpu_target = "x86_64")]
+const ARG_REGS: &[Regs] = &[
+    Regs::Rdi,
+    Regs::Rsi,
+    Regs::Rdx,
+    Regs::Rcx,
+    Regs::R8,
+    Regs::R9,
+];
+#[cfg(cpu_target = "i386")]
+const ARG_REGS: &[Regs] = &[];
+#[cfg(cpu_target = "arm")]
+const ARG_REGS: &[Regs] = &[Regs::R0, Regs::R1, Regs::R2, Regs::R3];
+#[cfg(cpu_target = "aarch64")]
+const ARG_REGS: &[Regs] = &[
+    Regs::X0,
+    Regs::X1,
+    Regs::X2,
+    Regs::X3,
+    Regs::X4,
+    Regs::X5,
+    Regs::X6,
+    Regs::X7,
+];
+
+/// The register of the return value
+#[cfg(cpu_target = "x86_64")]
+const RET_REG: Regs = Regs::Rax;
+#[cfg(cpu_target = "i386")]
+const RET_REG: Regs = Regs::Eax;
+#[cfg(cpu_target = "arm")]
+const RET_REG: Regs = Regs::R0;
+#[cfg(cpu_target = "aarch64")]
+const RET_REG: Regs = Regs::X0;
+
+/// The offset of the stack arguments from the stack pointer at the function entry,
+/// the return address is on the stack on x86
+#[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+const STACK_ARGS_OFFSET: GuestAddr = size_of::<GuestAddr>() as GuestAddr;
+#[cfg(any(cpu_target = "arm", cpu_target = "aarch64"))]
+const STACK_ARGS_OFFSET: GuestAddr = 0;
+
+/// The Thumb state bit of the CPSR
+#[cfg(cpu_target = "arm")]
+const CPSR_THUMB: u32 = 1 << 5;
+
+/// The return address of the function the guest is stopped at the entry of
+#[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+pub(crate) fn return_address(emulator: &Emulator) -> Result<GuestAddr, Error> {
+    let sp: GuestAddr = emulator.read_reg(Regs::Sp).map_err(Error::unknown)?;
+    let mut buf = [0; size_of::<GuestAddr>()];
+    unsafe { emulator.read_mem(sp, &mut buf) };
+    Ok(GuestAddr::from_le_bytes(buf))
+}
+
+/// The return address of the function the guest is stopped at the entry of
+#[cfg(any(cpu_target = "arm", cpu_target = "aarch64"))]
+pub(crate) fn return_address(emulator: &Emulator) -> Result<GuestAddr, Error> {
+    let lr: GuestAddr = emulator.read_reg(Regs::Lr).map_err(Error::unknown)?;
+    // Clear the thumb bit
+    #[cfg(cpu_target = "arm")]
+    let lr = lr & !1;
+    Ok(lr)
+}
+
+/// A call to an intercepted function, stopped at its entry
+#[derive(Debug)]
+pub struct FunctionCall {
+    emulator: Emulator,
+    addr: GuestAddr,
+    ret_addr: GuestAddr,
+    /// If the caller runs in Thumb mode, i.e., the Thumb bit of the return address is set
+    #[cfg(cpu_target = "arm")]
+    thumb: bool,
+    retval: Option<GuestUsize>,
+}
+
+impl FunctionCall {
+    pub(crate) fn new(addr: GuestAddr) -> Result<Self, Error> {
+        let emulator = Emulator::new_empty();
+        let ret_addr = return_address(&emulator)?;
+        #[cfg(cpu_target = "arm")]
+        let thumb = emulator
+            .read_reg::<_, GuestAddr>(Regs::Lr)
+            .map_err(Error::unknown)?
+            & 1
+            == 1;
+        Ok(Self {
+            emulator,
+            addr,
+            ret_addr,
+            #[cfg(cpu_target = "arm")]
+            thumb,
+            retval: None,
+        })
+    }
+
+    /// The address of the intercepted function
+    #[must_use]
+    pub fn addr(&self) -> GuestAddr {
+        self.addr
+    }
+
+    /// The address the function returns to
+    #[must_use]
+    pub fn return_address(&self) -> GuestAddr {
+        self.ret_addr
+    }
+
+    /// The address of the argument `idx` on the stack, if it is not passed in a register
+    fn stack_arg_addr(&self, idx: usize) -> Result<GuestAddr, Error> {
+        let sp: GuestAddr = self.emulator.read_reg(Regs::Sp).map_err(Error::unknown)?;
+        let slot = (idx - ARG_REGS.len()) * size_of::<GuestUsize>();
+        Ok(sp + STACK_ARGS_OFFSET + slot as GuestAddr)
+    }
+
+    /// The integer or pointer argument `idx`, starting from 0
+    pub fn arg(&self, idx: usize) -> Result<GuestUsize, Error> {
+        if let Some(reg) = ARG_REGS.get(idx) {
+            self.emulator.read_reg(*reg).map_err(Error::unknown)
+        } else {
+            let mut buf = [0; size_of::<GuestUsize>()];
+            unsafe { self.emulator.read_mem(self.stack_arg_addr(idx)?, &mut buf) };
+            Ok(GuestUsize::from_le_bytes(buf))
+        }
+    }
+
+    /// Sets the integer or pointer argument `idx`, starting from 0
+    pub fn set_arg(&mut self, idx: usize, val: GuestUsize) -> Result<(), Error> {
+        if let Some(reg) = ARG_REGS.get(idx) {
+            self.emulator.write_reg(*reg, val).map_err(Error::unknown)
+        } else {
+            let addr = self.stack_arg_addr(idx)?;
+            unsafe { self.emulator.write_mem(addr, &val.to_le_bytes()) };
+            Ok(())
+        }
+    }
+
+    /// The `len` bytes pointed by the argument `idx`
+    pub fn arg_bytes(&self, idx: usize, len: usize) -> Result<Vec<u8>, Error> {
+        let ptr = self.arg(idx)?;
+        let mut buf = vec![0; len];
+        unsafe { self.emulator.read_mem(ptr as GuestAddr, &mut buf) };
+        Ok(buf)
+    }
+
+    /// The NUL-terminated string pointed by the argument `idx`
+    pub fn arg_cstr(&self, idx: usize) -> Result<String, Error> {
+        let ptr = self.arg(idx)?;
+        if ptr == 0 {
+            return Err(Error::illegal_argument(format!(
+                "The argument {} is a null pointer",
+                idx
+            )));
+        }
+        let s = unsafe { std::ffi::CStr::from_ptr(self.emulator.g2h(ptr as GuestAddr)) };
+        Ok(s.to_string_lossy().into_owned())
+    }
+
+    /// Skips the function, returning `retval` to the caller
+    pub fn skip(&mut self, retval: GuestUsize) {
+        self.retval = Some(retval);
+    }
+
+    /// The function is skipped
+    #[must_use]
+    pub fn skipped(&self) -> bool {
+        self.retval.is_some()
+    }
+
+    /// Returns to the caller with the fake return value, if the function is skipped
+    pub(crate) fn apply_skip(&self) -> Result<(), Error> {
+        if let Some(retval) = self.retval {
+            self.emulator
+                .write_reg(RET_REG, retval)
+                .map_err(Error::unknown)?;
+            // Pop the return address
+            #[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+            {
+                let sp: GuestAddr = self.emulator.read_reg(Regs::Sp).map_err(Error::unknown)?;
+                self.emulator
+                    .write_reg(Regs::Sp, sp + size_of::<GuestAddr>() as GuestAddr)
+                    .map_err(Error::unknown)?;
+            }
+            // Return in the mode of the caller, as `bx lr` would
+            #[cfg(cpu_target = "arm")]
+            {
+                let cpsr: u32 = self.emulator.read_reg(Regs::Cpsr).map_err(Error::unknown)?;
+                let cpsr = if self.thumb {
+                    cpsr | CPSR_THUMB
+                } else {
+                    cpsr & !CPSR_THUMB
+                };
+                self.emulator
+                    .write_reg(Regs::Cpsr, cpsr)
+                    .map_err(Error::unknown)?;
+            }
+            self.emulator
+                .write_reg(Regs::Pc, self.ret_addr)
+                .map_err(Error::unknown)?;
+        }
+        Ok(())
+    }
+}
@@ -38,6 +38,8 @@ pub mod helper;
 pub use helper::*;
 pub mod hooks;
 pub use hooks::*;
+pub mod intercept;
+pub use intercept::FunctionCall;
 
 pub mod edges;
 pub use edges::QemuEdgeCoverageHelper;
@@ -47,6 +49,25 @@ pub mod snapshot;
 pub use snapshot::QemuSnapshotHelper;
 pub mod asan;
 pub use asan::{init_with_asan, QemuAsanHelper};
+pub mod drcov;
+pub use drcov::{QemuDrCovFeedback, QemuDrCovHelper};
+pub mod calls;
+pub use calls::{QemuCallStackObserver, QemuCallTracerHelper};
+pub mod fd_input;
+pub use fd_input::{QemuFdBehavior, QemuFdInputHelper, QemuFdOutputObserver};
+pub mod exit;
+pub use exit::{QemuExitHelper, QemuExitObserver, QemuExitSnapshotObserver};
+pub mod memaccess;
+pub use memaccess::{
+    QemuMemAccessCoverageHelper, QemuWatchpointFeedback, QemuWatchpointsHelper, MEM_ACCESS_MAP,
+    MEM_ACCESS_MAP_SIZE,
+};
+pub mod trace;
+pub use trace::{QemuTraceHelper, QemuTraceObserver, QemuTraceReader};
+pub mod persistent;
+pub use persistent::{
+    persistent_harness, QemuPersistentHelper, QemuPersistentHelperBuilder, QemuPersistentInput,
+};
 
 pub mod executor;
 pub use executor::{QemuExecutor, QemuForkExecutor};
@@ -0,0 +1,492 @@
+//! Guest memory access coverage and watchpoints for `libafl_qemu`.
+//!
+//! The [`QemuMemAccessCoverageHelper`] hashes the data addresses read and written by the guest into
+//! [`MEM_ACCESS_MAP`], to be used with a map observer and feedback as an additional coverage dimension.
+//! With a [`QemuAsanHelper`] in the helpers, the accesses to heap allocations are recorded as offsets
+//! in the allocation, that do not depend on where the allocator placed it.
+//!
+//! The [`QemuWatchpointsHelper`] records the writes of the guest to chosen address ranges, such as
+//! critical global variables, and the [`QemuWatchpointFeedback`] makes objectives of the runs that hit them.
+//! Only the writes of the guest code are seen, not the ones done by the kernel in syscalls.
+
+use core::{ops::Range, pin::Pin};
+use libafl::{
+    bolts::tuples::Named,
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    inputs::Input,
+    observers::ObserversTuple,
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asan::QemuAsanHelper,
+    elf::EasyElf,
+    emu::{Emulator, GuestAddr},
+    helper::{hash_me, QemuHelper, QemuHelperTuple},
+    hooks::QemuHooks,
+};
+
+/// The size of [`MEM_ACCESS_MAP`]
+pub const MEM_ACCESS_MAP_SIZE: usize = 1 << 16;
+
+/// The memory access coverage map filled by the [`QemuMemAccessCoverageHelper`]
+pub static mut MEM_ACCESS_MAP: [u8; MEM_ACCESS_MAP_SIZE] = [0; MEM_ACCESS_MAP_SIZE];
+
+/// The default granularity of the memory access coverage, as the log2 of the size of a recorded cell
+pub const MEM_ACCESS_DEFAULT_GRANULARITY: u32 = 3;
+
+/// Set in the hashed location of the heap accesses, that are offsets and not addresses
+const HEAP_TAG: u64 = 1 << 63;
+/// Set in the hashed location of the writes, to tell them from the reads
+const WRITE_TAG: u64 = 1 << 62;
+
+/// Records the data addresses accessed by the guest, see the [module-level documentation](self)
+#[derive(Debug)]
+pub struct QemuMemAccessCoverageHelper {
+    reads: bool,
+    granularity: u32,
+}
+
+impl QemuMemAccessCoverageHelper {
+    /// Creates a new [`QemuMemAccessCoverageHelper`], recording reads and writes
+    /// with a granularity of [`MEM_ACCESS_DEFAULT_GRANULARITY`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            reads: true,
+            granularity: MEM_ACCESS_DEFAULT_GRANULARITY,
+        }
+    }
+
+    /// Only record the writes
+    #[must_use]
+    pub fn writes_only(mut self) -> Self {
+        self.reads = false;
+        self
+    }
+
+    /// Record the accesses in cells of `1 << bits` bytes
+    #[must_use]
+    pub fn granularity(mut self, bits: u32) -> Self {
+        self.granularity = bits;
+        self
+    }
+
+    fn access(&self, asan: Option<&QemuAsanHelper>, addr: GuestAddr, size: usize, write: bool) {
+        let allocation = asan.and_then(|h| h.allocation(addr));
+        let addr: u64 = addr.into();
+        let (base, tag) = match allocation {
+            Some((start, _)) => (start, HEAP_TAG),
+            None => (0, 0),
+        };
+        let tag = if write { tag | WRITE_TAG } else { tag };
+        let first = (addr - base) >> self.granularity;
+        let last = (addr - base + size.max(1) as u64 - 1) >> self.granularity;
+        for cell in first..=last {
+            let idx = hash_me(cell | tag) as usize & (MEM_ACCESS_MAP_SIZE - 1);
+            unsafe {
+                MEM_ACCESS_MAP[idx] = MEM_ACCESS_MAP[idx].wrapping_add(1);
+            }
+        }
+    }
+}
+
+impl Default for QemuMemAccessCoverageHelper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuMemAccessCoverageHelper
+where
+    I: Input,
+{
+    fn init_hooks<'a, QT>(&self, hooks: Pin<&QemuHooks<'a, I, QT, S>>)
+    where
+        QT: QemuHelperTuple<I, S>,
+    {
+        if self.reads {
+            hooks.read8_execution(trace_read8_memaccess::<I, QT, S>);
+            hooks.read4_execution(trace_read4_memaccess::<I, QT, S>);
+            hooks.read2_execution(trace_read2_memaccess::<I, QT, S>);
+            hooks.read1_execution(trace_read1_memaccess::<I, QT, S>);
+            hooks.read_n_execution(trace_read_n_memaccess::<I, QT, S>);
+        }
+
+        hooks.write8_execution(trace_write8_memaccess::<I, QT, S>);
+        hooks.write4_execution(trace_write4_memaccess::<I, QT, S>);
+        hooks.write2_execution(trace_write2_memaccess::<I, QT, S>);
+        hooks.write1_execution(trace_write1_memaccess::<I, QT, S>);
+        hooks.write_n_execution(trace_write_n_memaccess::<I, QT, S>);
+    }
+}
+
+fn trace_memaccess<I, QT, S>(helpers: &mut QT, addr: GuestAddr, size: usize, write: bool)
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers
+        .match_first_type::<QemuMemAccessCoverageHelper>()
+        .unwrap();
+    h.access(
+        helpers.match_first_type::<QemuAsanHelper>(),
+        addr,
+        size,
+        write,
+    );
+}
+
+pub fn trace_read1_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, 1, false);
+}
+
+pub fn trace_read2_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, 2, false);
+}
+
+pub fn trace_read4_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, 4, false);
+}
+
+pub fn trace_read8_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, 8, false);
+}
+
+pub fn trace_read_n_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+    size: usize,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, size, false);
+}
+
+pub fn trace_write1_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, 1, true);
+}
+
+pub fn trace_write2_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, 2, true);
+}
+
+pub fn trace_write4_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, 4, true);
+}
+
+pub fn trace_write8_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, 8, true);
+}
+
+pub fn trace_write_n_memaccess<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+    size: usize,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    trace_memaccess::<I, QT, S>(helpers, addr, size, true);
+}
+
+/// A write of the guest to a watched address range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QemuWatchpointHit {
+    /// The name of the watchpoint
+    pub name: String,
+    /// The address of the write
+    pub addr: GuestAddr,
+    /// The size of the write
+    pub size: usize,
+}
+
+/// The watchpoints hit in the current run
+static mut WATCHPOINT_HITS: Vec<QemuWatchpointHit> = vec![];
+
+/// The watchpoints hit in the current run, in order
+#[must_use]
+pub fn watchpoint_hits() -> &'static [QemuWatchpointHit] {
+    unsafe { &WATCHPOINT_HITS }
+}
+
+/// Records the writes of the guest to the watched address ranges, see the [module-level documentation](self)
+#[derive(Debug, Default)]
+pub struct QemuWatchpointsHelper {
+    watchpoints: Vec<(String, Range<GuestAddr>)>,
+}
+
+impl QemuWatchpointsHelper {
+    /// Creates a new [`QemuWatchpointsHelper`], without watchpoints
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watches the writes to the `len` bytes at `addr`
+    #[must_use]
+    pub fn watch(mut self, name: &str, addr: GuestAddr, len: usize) -> Self {
+        self.watchpoints
+            .push((name.to_string(), addr..addr + len as GuestAddr));
+        self
+    }
+
+    /// Watches the writes to the global variable `name` of the main binary
+    pub fn watch_symbol(mut self, emulator: &Emulator, name: &str) -> Result<Self, Error> {
+        let mut elf_buffer = Vec::new();
+        let elf = EasyElf::from_file(emulator.binary_path(), &mut elf_buffer)?;
+        let range = elf
+            .resolve_symbol_range(name, emulator.load_addr())
+            .ok_or_else(|| Error::key_not_found(format!("Symbol {} not found", name)))?;
+        self.watchpoints.push((name.to_string(), range));
+        Ok(self)
+    }
+
+    fn access(&self, addr: GuestAddr, size: usize) {
+        let end = addr + size as GuestAddr;
+        for (name, range) in &self.watchpoints {
+            if addr < range.end && range.start < end {
+                unsafe {
+                    WATCHPOINT_HITS.push(QemuWatchpointHit {
+                        name: name.clone(),
+                        addr,
+                        size,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuWatchpointsHelper
+where
+    I: Input,
+{
+    fn init_hooks<'a, QT>(&self, hooks: Pin<&QemuHooks<'a, I, QT, S>>)
+    where
+        QT: QemuHelperTuple<I, S>,
+    {
+        hooks.write8_execution(trace_write8_watchpoints::<I, QT, S>);
+        hooks.write4_execution(trace_write4_watchpoints::<I, QT, S>);
+        hooks.write2_execution(trace_write2_watchpoints::<I, QT, S>);
+        hooks.write1_execution(trace_write1_watchpoints::<I, QT, S>);
+        hooks.write_n_execution(trace_write_n_watchpoints::<I, QT, S>);
+    }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        unsafe { WATCHPOINT_HITS.clear() };
+    }
+}
+
+pub fn trace_write1_watchpoints<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type::<QemuWatchpointsHelper>().unwrap();
+    h.access(addr, 1);
+}
+
+pub fn trace_write2_watchpoints<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type::<QemuWatchpointsHelper>().unwrap();
+    h.access(addr, 2);
+}
+
+pub fn trace_write4_watchpoints<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type::<QemuWatchpointsHelper>().unwrap();
+    h.access(addr, 4);
+}
+
+pub fn trace_write8_watchpoints<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type::<QemuWatchpointsHelper>().unwrap();
+    h.access(addr, 8);
+}
+
+pub fn trace_write_n_watchpoints<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+    size: usize,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let h = helpers.match_first_type::<QemuWatchpointsHelper>().unwrap();
+    h.access(addr, size);
+}
+
+/// The watchpoints hit by a testcase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QemuWatchpointMetadata {
+    /// The writes to the watched address ranges, in order
+    pub hits: Vec<QemuWatchpointHit>,
+}
+
+libafl::impl_serdeany!(QemuWatchpointMetadata);
+
+/// An objective feedback, interesting if the last run wrote to a watched address range.
+/// It adds the hits as a [`QemuWatchpointMetadata`] to the testcase.
+/// Requires a [`QemuWatchpointsHelper`] and the in-process [`crate::QemuExecutor`].
+#[derive(Debug, Default)]
+pub struct QemuWatchpointFeedback;
+
+impl QemuWatchpointFeedback {
+    /// Creates a new [`QemuWatchpointFeedback`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<I, S> Feedback<I, S> for QemuWatchpointFeedback
+where
+    I: Input,
+    S: HasClientPerfMonitor,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        Ok(!watchpoint_hits().is_empty())
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        testcase.add_metadata(QemuWatchpointMetadata {
+            hits: watchpoint_hits().to_vec(),
+        });
+        Ok(())
+    }
+}
+
+impl Named for QemuWatchpointFeedback {
+    #[inline]
+    fn name(&self) -> &str {
+        "QemuWatchpointFeedback"
+    }
+}
@@ -0,0 +1,291 @@
+//! Persistent mode for `libafl_qemu`.
+//!
+//! The [`QemuPersistentHelper`] runs the guest to an entry point once, saves the registers there, and
+//! stops each run at an exit point. Before each run it restores the saved registers and injects the input,
+//! so the harness only has to resume the emulator, see [`persistent_harness`].
+//! To also restore the guest memory between runs, place a [`crate::QemuSnapshotHelper`] *before*
+//! the [`QemuPersistentHelper`] in the helpers tuple, so that the memory is reset before the input is written.
+
+use libafl::{
+    bolts::AsSlice,
+    executors::ExitKind,
+    inputs::{HasTargetBytes, Input},
+    Error,
+};
+
+use crate::{
+    elf::EasyElf,
+    emu::{Emulator, GuestAddr, GuestUsize, MmapPerms},
+    helper::QemuHelper,
+    hooks::run_intercepted,
+    intercept::return_address,
+    Regs,
+};
+
+/// The default maximum length of an input written to the guest
+pub const PERSISTENT_DEFAULT_MAX_LEN: usize = 4096;
+
+/// A location in the guest, as an address or as a symbol of the main binary
+#[derive(Debug, Clone)]
+enum PersistentLocation {
+    Addr(GuestAddr),
+    Symbol(String),
+}
+
+impl PersistentLocation {
+    fn resolve(&self, emulator: &Emulator, elf: Option<&EasyElf>) -> Result<GuestAddr, Error> {
+        match self {
+            Self::Addr(addr) => Ok(*addr),
+            Self::Symbol(name) => elf
+                .unwrap()
+                .resolve_symbol(name, emulator.load_addr())
+                .ok_or_else(|| Error::key_not_found(format!("Symbol {} not found", name))),
+        }
+    }
+}
+
+/// How the input is passed to the guest in each run
+#[derive(Debug, Clone, Copy)]
+pub enum QemuPersistentInput {
+    /// Write the input to a buffer mapped by the helper, and pass its address and length in two registers,
+    /// as for `LLVMFuzzerTestOneInput(const uint8_t *data, size_t size)`.
+    Registers { buf_reg: Regs, len_reg: Regs },
+    /// Write the input at a fixed guest address, and optionally its length as a [`GuestUsize`] at `len_addr`
+    Memory {
+        addr: GuestAddr,
+        len_addr: Option<GuestAddr>,
+    },
+}
+
+/// A builder for the [`QemuPersistentHelper`]
+#[derive(Debug)]
+pub struct QemuPersistentHelperBuilder {
+    entry: Option<PersistentLocation>,
+    exit: Option<PersistentLocation>,
+    regs: Vec<Regs>,
+    input: Option<QemuPersistentInput>,
+    max_len: usize,
+}
+
+impl QemuPersistentHelperBuilder {
+    /// Creates a new builder, stopping at the return of the entry function by default
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            entry: None,
+            exit: None,
+            regs: vec![],
+            input: None,
+            max_len: PERSISTENT_DEFAULT_MAX_LEN,
+        }
+    }
+
+    /// Each run starts at this address
+    #[must_use]
+    pub fn entry(mut self, addr: GuestAddr) -> Self {
+        self.entry = Some(PersistentLocation::Addr(addr));
+        self
+    }
+
+    /// Each run starts at this symbol of the main binary
+    #[must_use]
+    pub fn entry_symbol(mut self, name: &str) -> Self {
+        self.entry = Some(PersistentLocation::Symbol(name.to_string()));
+        self
+    }
+
+    /// Each run ends when reaching this address.
+    /// If not set, the return address of the entry function is used.
+    #[must_use]
+    pub fn exit(mut self, addr: GuestAddr) -> Self {
+        self.exit = Some(PersistentLocation::Addr(addr));
+        self
+    }
+
+    /// Each run ends when reaching this symbol of the main binary
+    #[must_use]
+    pub fn exit_symbol(mut self, name: &str) -> Self {
+        self.exit = Some(PersistentLocation::Symbol(name.to_string()));
+        self
+    }
+
+    /// Restore this register to its value at the entry before each run.
+    /// The program counter and the stack pointer are always restored.
+    #[must_use]
+    pub fn save_reg(mut self, reg: Regs) -> Self {
+        self.regs.push(reg);
+        self
+    }
+
+    /// Restore these registers to their value at the entry before each run
+    #[must_use]
+    pub fn save_regs<IT>(mut self, regs: IT) -> Self
+    where
+        IT: IntoIterator<Item = Regs>,
+    {
+        self.regs.extend(regs);
+        self
+    }
+
+    /// Set how the input is passed to the guest
+    #[must_use]
+    pub fn input(mut self, input: QemuPersistentInput) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    /// Inputs are truncated to this length, [`PERSISTENT_DEFAULT_MAX_LEN`] by default
+    #[must_use]
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Runs the guest to the entry, saves the registers and sets the exit breakpoint
+    pub fn build(self, emulator: &Emulator) -> Result<QemuPersistentHelper, Error> {
+        let entry = self
+            .entry
+            .ok_or_else(|| Error::illegal_argument("No entry set for the persistent mode"))?;
+        let input = self.input.ok_or_else(|| {
+            Error::illegal_argument("No input injection set for the persistent mode")
+        })?;
+
+        let needs_elf = matches!(entry, PersistentLocation::Symbol(_))
+            || matches!(self.exit, Some(PersistentLocation::Symbol(_)));
+        let mut elf_buffer = Vec::new();
+        let elf = if needs_elf {
+            Some(EasyElf::from_file(emulator.binary_path(), &mut elf_buffer)?)
+        } else {
+            None
+        };
+
+        let entry = entry.resolve(emulator, elf.as_ref())?;
+        emulator.set_breakpoint(entry);
+        unsafe { emulator.run() };
+        emulator.remove_breakpoint(entry);
+
+        let pc: GuestAddr = emulator.read_reg(Regs::Pc).map_err(Error::unknown)?;
+        if pc != entry {
+            return Err(Error::unknown(format!(
+                "Persistent entry {:#x} not reached, stopped at {:#x}",
+                entry, pc
+            )));
+        }
+
+        let exit = match &self.exit {
+            Some(exit) => exit.resolve(emulator, elf.as_ref())?,
+            None => return_address(emulator)?,
+        };
+        emulator.set_breakpoint(exit);
+
+        let mut regs = vec![Regs::Pc, Regs::Sp];
+        for reg in self.regs {
+            let id: i32 = reg.into();
+            if !regs.iter().any(|r| i32::from(*r) == id) {
+                regs.push(reg);
+            }
+        }
+        let saved_regs = regs
+            .into_iter()
+            .map(|reg| Ok((reg, emulator.read_reg(reg).map_err(Error::unknown)?)))
+            .collect::<Result<Vec<(Regs, GuestUsize)>, Error>>()?;
+
+        let buf_addr = match input {
+            QemuPersistentInput::Registers { .. } => emulator
+                .map_private(0, self.max_len, MmapPerms::ReadWrite)
+                .map_err(Error::unknown)?,
+            QemuPersistentInput::Memory { addr, .. } => addr,
+        };
+
+        Ok(QemuPersistentHelper {
+            entry,
+            exit,
+            saved_regs,
+            input,
+            buf_addr,
+            max_len: self.max_len,
+        })
+    }
+}
+
+impl Default for QemuPersistentHelperBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Restores the registers at the persistent entry and injects the input before each run
+#[derive(Debug)]
+pub struct QemuPersistentHelper {
+    entry: GuestAddr,
+    exit: GuestAddr,
+    saved_regs: Vec<(Regs, GuestUsize)>,
+    input: QemuPersistentInput,
+    buf_addr: GuestAddr,
+    max_len: usize,
+}
+
+impl QemuPersistentHelper {
+    /// The address each run starts at
+    #[must_use]
+    pub fn entry(&self) -> GuestAddr {
+        self.entry
+    }
+
+    /// The address each run ends at
+    #[must_use]
+    pub fn exit(&self) -> GuestAddr {
+        self.exit
+    }
+
+    /// The guest address the input is written to
+    #[must_use]
+    pub fn input_addr(&self) -> GuestAddr {
+        self.buf_addr
+    }
+
+    fn restore_regs(&self, emulator: &Emulator) {
+        for (reg, val) in &self.saved_regs {
+            emulator.write_reg(*reg, *val).unwrap();
+        }
+    }
+
+    fn inject(&self, emulator: &Emulator, buf: &[u8]) {
+        let buf = &buf[..buf.len().min(self.max_len)];
+        unsafe { emulator.write_mem(self.buf_addr, buf) };
+        let len = buf.len() as GuestUsize;
+        match self.input {
+            QemuPersistentInput::Registers { buf_reg, len_reg } => {
+                emulator.write_reg(buf_reg, self.buf_addr).unwrap();
+                emulator.write_reg(len_reg, len).unwrap();
+            }
+            QemuPersistentInput::Memory {
+                len_addr: Some(len_addr),
+                ..
+            } => unsafe { emulator.write_mem(len_addr, &len.to_le_bytes()) },
+            QemuPersistentInput::Memory { len_addr: None, .. } => (),
+        }
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuPersistentHelper
+where
+    I: Input + HasTargetBytes,
+{
+    fn pre_exec(&mut self, emulator: &Emulator, input: &I) {
+        self.restore_regs(emulator);
+        self.inject(emulator, input.target_bytes().as_slice());
+    }
+}
+
+/// The harness for a [`crate::QemuExecutor`] with a [`QemuPersistentHelper`]:
+/// it resumes the guest, that runs until the exit breakpoint, handling the intercepted functions
+pub fn persistent_harness<I>(emulator: &Emulator) -> impl FnMut(&I) -> ExitKind + '_
+where
+    I: Input,
+{
+    move |_input: &I| {
+        unsafe { run_intercepted(emulator) };
+        ExitKind::Ok
+    }
+}
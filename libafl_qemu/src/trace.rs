@@ -0,0 +1,544 @@
+//! Record and replay of `libafl_qemu` executions, for crash analysis.
+//!
+//! Replaying a solution with the [`QemuTraceHelper`] added to the helpers of the fuzzer records the executed
+//! blocks, the values of chosen registers at the start of each block, and the results of the syscalls.
+//! The [`QemuTraceObserver`] writes the trace to a file at the end of the run, also when the guest crashes,
+//! as the in-process executors run the observers before exiting, and the [`QemuTraceReader`] loads it,
+//! for example to look at the last blocks executed before the crash.
+//!
+//! The trace file starts with the [`TRACE_MAGIC`], the format version and the ids of the recorded registers,
+//! followed by the events, all the integers are little endian and 64 bits wide:
+//! * a block is the tag `0`, its address and the values of the registers,
+//! * a syscall is the tag `1`, its number, its 6 arguments and its result.
+
+use core::pin::Pin;
+use libafl::{
+    bolts::tuples::Named, executors::ExitKind, inputs::Input, observers::Observer, Error,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    emu::{Emulator, GuestUsize},
+    helper::{QemuHelper, QemuHelperTuple},
+    hooks::QemuHooks,
+    Regs,
+};
+
+/// The first bytes of a trace file
+pub const TRACE_MAGIC: &[u8; 8] = b"QEMUTRC\0";
+/// The version of the trace file format
+pub const TRACE_VERSION: u64 = 1;
+/// The default amount of events kept for each run by the [`QemuTraceHelper`]
+pub const DEFAULT_TRACE_MAX_EVENTS: usize = 1 << 20;
+
+const TAG_BLOCK: u8 = 0;
+const TAG_SYSCALL: u8 = 1;
+
+/// A block executed by the guest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QemuTraceBlock {
+    /// The address of the block
+    pub pc: u64,
+    /// The values of the recorded registers at the start of the block
+    pub regs: Vec<u64>,
+}
+
+/// A syscall done by the guest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QemuTraceSyscall {
+    /// The syscall number
+    pub sys_num: i64,
+    /// The first 6 arguments
+    pub args: [u64; 6],
+    /// The value returned to the guest
+    pub result: u64,
+}
+
+/// An event of a trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QemuTraceEvent {
+    /// A block was executed
+    Block(QemuTraceBlock),
+    /// A syscall returned
+    Syscall(QemuTraceSyscall),
+}
+
+/// The ids of the recorded registers
+static mut TRACE_REGS: Vec<i32> = vec![];
+/// The events of the current run
+static mut TRACE_EVENTS: Option<VecDeque<QemuTraceEvent>> = None;
+/// Only keep this many events, if set
+static mut TRACE_MAX_EVENTS: Option<usize> = None;
+
+fn trace_events() -> &'static mut VecDeque<QemuTraceEvent> {
+    unsafe { TRACE_EVENTS.get_or_insert_with(VecDeque::new) }
+}
+
+fn push_event(event: QemuTraceEvent) {
+    let events = trace_events();
+    if let Some(max) = unsafe { TRACE_MAX_EVENTS } {
+        if events.len() >= max {
+            events.pop_front();
+        }
+    }
+    events.push_back(event);
+}
+
+/// Writes the trace of the current run to the file at `path`
+pub fn write_trace<P>(path: P) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    let regs = unsafe { &TRACE_REGS };
+    writer.write_all(TRACE_MAGIC)?;
+    writer.write_all(&TRACE_VERSION.to_le_bytes())?;
+    writer.write_all(&(regs.len() as u64).to_le_bytes())?;
+    for reg in regs {
+        writer.write_all(&i64::from(*reg).to_le_bytes())?;
+    }
+    for event in trace_events().iter() {
+        match event {
+            QemuTraceEvent::Block(block) => {
+                writer.write_all(&[TAG_BLOCK])?;
+                writer.write_all(&block.pc.to_le_bytes())?;
+                for val in &block.regs {
+                    writer.write_all(&val.to_le_bytes())?;
+                }
+            }
+            QemuTraceEvent::Syscall(syscall) => {
+                writer.write_all(&[TAG_SYSCALL])?;
+                writer.write_all(&syscall.sys_num.to_le_bytes())?;
+                for arg in &syscall.args {
+                    writer.write_all(&arg.to_le_bytes())?;
+                }
+                writer.write_all(&syscall.result.to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Records the blocks, registers and syscalls of each run, see the [module-level documentation](self)
+#[derive(Debug)]
+pub struct QemuTraceHelper {
+    regs: Vec<Regs>,
+    max_events: Option<usize>,
+}
+
+impl Default for QemuTraceHelper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QemuTraceHelper {
+    /// Creates a new [`QemuTraceHelper`], recording only the blocks and the syscalls,
+    /// and keeping the last [`DEFAULT_TRACE_MAX_EVENTS`] events of each run
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            regs: vec![],
+            max_events: Some(DEFAULT_TRACE_MAX_EVENTS),
+        }
+    }
+
+    /// Also record the value of these registers at the start of each block
+    #[must_use]
+    pub fn regs<IT>(mut self, regs: IT) -> Self
+    where
+        IT: IntoIterator<Item = Regs>,
+    {
+        self.regs.extend(regs);
+        self
+    }
+
+    /// Only keep the last `max_events` events of each run, to bound the memory used by long runs
+    #[must_use]
+    pub fn keep_last(mut self, max_events: usize) -> Self {
+        self.max_events = Some(max_events);
+        self
+    }
+
+    /// Keep all the events of each run, however long it is
+    #[must_use]
+    pub fn keep_all(mut self) -> Self {
+        self.max_events = None;
+        self
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuTraceHelper
+where
+    I: Input,
+{
+    fn init_hooks<'a, QT>(&self, hooks: Pin<&QemuHooks<'a, I, QT, S>>)
+    where
+        QT: QemuHelperTuple<I, S>,
+    {
+        unsafe {
+            TRACE_REGS = self.regs.iter().map(|reg| i32::from(*reg)).collect();
+            TRACE_MAX_EVENTS = self.max_events;
+        }
+        hooks.blocks(gen_trace_block::<I, QT, S>, trace_block::<I, QT, S>);
+        hooks.after_syscalls(trace_syscall::<I, QT, S>);
+    }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        trace_events().clear();
+    }
+}
+
+/// The id of a block is its address
+pub fn gen_trace_block<I, QT, S>(
+    _emulator: &Emulator,
+    _helpers: &mut QT,
+    _state: Option<&mut S>,
+    pc: u64,
+) -> Option<u64>
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    Some(pc)
+}
+
+pub fn trace_block<I, QT, S>(
+    emulator: &Emulator,
+    _helpers: &mut QT,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    let regs = unsafe { &TRACE_REGS }
+        .iter()
+        .map(|reg| {
+            emulator
+                .read_reg::<_, GuestUsize>(*reg)
+                .map_or(0, u64::from)
+        })
+        .collect();
+    push_event(QemuTraceEvent::Block(QemuTraceBlock { pc: id, regs }));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn trace_syscall<I, QT, S>(
+    _emulator: &Emulator,
+    _helpers: &mut QT,
+    _state: Option<&mut S>,
+    result: u64,
+    sys_num: i32,
+    a0: u64,
+    a1: u64,
+    a2: u64,
+    a3: u64,
+    a4: u64,
+    a5: u64,
+    _a6: u64,
+    _a7: u64,
+) -> u64
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    push_event(QemuTraceEvent::Syscall(QemuTraceSyscall {
+        sys_num: i64::from(sys_num),
+        args: [a0, a1, a2, a3, a4, a5],
+        result,
+    }));
+    result
+}
+
+/// Writes the trace recorded by the [`QemuTraceHelper`] to a file after each run, including the crashing ones
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QemuTraceObserver {
+    observer_name: String,
+    path: PathBuf,
+}
+
+impl QemuTraceObserver {
+    /// Creates a new [`QemuTraceObserver`], writing the trace to `path`
+    #[must_use]
+    pub fn new<P>(observer_name: &str, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            observer_name: observer_name.to_string(),
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl<I, S> Observer<I, S> for QemuTraceObserver
+where
+    I: Input,
+{
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        write_trace(&self.path)
+    }
+
+    fn post_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        write_trace(&self.path)
+    }
+}
+
+impl Named for QemuTraceObserver {
+    fn name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+/// Reads the little endian integers of a trace file
+struct TraceCursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> TraceCursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() < len {
+            return Err(Error::illegal_argument("Truncated trace file"));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// A trace written by the [`QemuTraceObserver`]
+#[derive(Debug, Clone)]
+pub struct QemuTraceReader {
+    regs: Vec<i32>,
+    events: Vec<QemuTraceEvent>,
+}
+
+impl QemuTraceReader {
+    /// Loads the trace file at `path`
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut data = vec![];
+        File::open(path)?.read_to_end(&mut data)?;
+        Self::from_bytes(&data)
+    }
+
+    /// Parses a trace
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let mut cursor = TraceCursor { data };
+        if cursor.take(TRACE_MAGIC.len())? != TRACE_MAGIC {
+            return Err(Error::illegal_argument("Not a trace file"));
+        }
+        let version = cursor.u64()?;
+        if version != TRACE_VERSION {
+            return Err(Error::illegal_argument(format!(
+                "Unsupported trace version {}",
+                version
+            )));
+        }
+        let regs_num = cursor.u64()? as usize;
+        let regs = (0..regs_num)
+            .map(|_| Ok(cursor.u64()? as i32))
+            .collect::<Result<Vec<i32>, Error>>()?;
+
+        let mut events = vec![];
+        while !cursor.data.is_empty() {
+            let event = match cursor.u8()? {
+                TAG_BLOCK => QemuTraceEvent::Block(QemuTraceBlock {
+                    pc: cursor.u64()?,
+                    regs: (0..regs_num)
+                        .map(|_| cursor.u64())
+                        .collect::<Result<Vec<u64>, Error>>()?,
+                }),
+                TAG_SYSCALL => {
+                    let sys_num = cursor.u64()? as i64;
+                    let mut args = [0; 6];
+                    for arg in &mut args {
+                        *arg = cursor.u64()?;
+                    }
+                    QemuTraceEvent::Syscall(QemuTraceSyscall {
+                        sys_num,
+                        args,
+                        result: cursor.u64()?,
+                    })
+                }
+                tag => {
+                    return Err(Error::illegal_argument(format!(
+                        "Unknown trace event tag {}",
+                        tag
+                    )))
+                }
+            };
+            events.push(event);
+        }
+        Ok(Self { regs, events })
+    }
+
+    /// The recorded registers, in the order of their values in each block
+    #[must_use]
+    pub fn regs(&self) -> Vec<Regs> {
+        self.regs
+            .iter()
+            .filter_map(|reg| Regs::try_from(*reg).ok())
+            .collect()
+    }
+
+    /// All the events, in order
+    #[must_use]
+    pub fn events(&self) -> &[QemuTraceEvent] {
+        &self.events
+    }
+
+    /// The executed blocks, in order
+    pub fn blocks(&self) -> impl Iterator<Item = &QemuTraceBlock> {
+        self.events.iter().filter_map(|event| match event {
+            QemuTraceEvent::Block(block) => Some(block),
+            QemuTraceEvent::Syscall(_) => None,
+        })
+    }
+
+    /// The syscalls, in order
+    pub fn syscalls(&self) -> impl Iterator<Item = &QemuTraceSyscall> {
+        self.events.iter().filter_map(|event| match event {
+            QemuTraceEvent::Syscall(syscall) => Some(syscall),
+            QemuTraceEvent::Block(_) => None,
+        })
+    }
+
+    /// The last `n` executed blocks, oldest first, such as the blocks before a crash
+    #[must_use]
+    pub fn last_blocks(&self, n: usize) -> Vec<&QemuTraceBlock> {
+        let blocks: Vec<&QemuTraceBlock> = self.blocks().collect();
+        blocks[blocks.len().saturating_sub(n)..].to_vec()
+    }
+
+    /// The value of the register `reg` at the start of `block`, if it was recorded
+    #[must_use]
+    pub fn reg(&self, block: &QemuTraceBlock, reg: Regs) -> Option<u64> {
+        let id = i32::from(reg);
+        let idx = self.regs.iter().position(|r| *r == id)?;
+        block.regs.get(idx).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs};
+
+    use super::{
+        push_event, trace_events, write_trace, QemuTraceBlock, QemuTraceEvent, QemuTraceReader,
+        QemuTraceSyscall, TRACE_MAGIC, TRACE_REGS, TRACE_VERSION,
+    };
+
+    fn header(regs: &[i64]) -> Vec<u8> {
+        let mut data = TRACE_MAGIC.to_vec();
+        data.extend_from_slice(&TRACE_VERSION.to_le_bytes());
+        data.extend_from_slice(&(regs.len() as u64).to_le_bytes());
+        for reg in regs {
+            data.extend_from_slice(&reg.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_trace_from_bytes() {
+        let mut data = header(&[3]);
+        data.push(0);
+        data.extend_from_slice(&0x1000_u64.to_le_bytes());
+        data.extend_from_slice(&42_u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&60_i64.to_le_bytes());
+        for arg in 1..=6_u64 {
+            data.extend_from_slice(&arg.to_le_bytes());
+        }
+        data.extend_from_slice(&7_u64.to_le_bytes());
+
+        let reader = QemuTraceReader::from_bytes(&data).unwrap();
+        assert_eq!(
+            reader.events(),
+            &[
+                QemuTraceEvent::Block(QemuTraceBlock {
+                    pc: 0x1000,
+                    regs: vec![42],
+                }),
+                QemuTraceEvent::Syscall(QemuTraceSyscall {
+                    sys_num: 60,
+                    args: [1, 2, 3, 4, 5, 6],
+                    result: 7,
+                }),
+            ]
+        );
+        assert_eq!(reader.last_blocks(4).len(), 1);
+
+        // A truncated event, an unknown tag and a wrong magic are rejected
+        assert!(QemuTraceReader::from_bytes(&data[..data.len() - 1]).is_err());
+        let mut unknown = header(&[]);
+        unknown.push(2);
+        assert!(QemuTraceReader::from_bytes(&unknown).is_err());
+        assert!(QemuTraceReader::from_bytes(b"NOTATRACE").is_err());
+    }
+
+    #[test]
+    fn test_trace_roundtrip() {
+        let events = vec![
+            QemuTraceEvent::Block(QemuTraceBlock {
+                pc: 0x4000,
+                regs: vec![1, 2],
+            }),
+            QemuTraceEvent::Syscall(QemuTraceSyscall {
+                sys_num: -1,
+                args: [0, 1, 2, 3, 4, u64::MAX],
+                result: 0,
+            }),
+            QemuTraceEvent::Block(QemuTraceBlock {
+                pc: 0x4010,
+                regs: vec![3, 4],
+            }),
+        ];
+        unsafe {
+            TRACE_REGS = vec![0, 1];
+        }
+        trace_events().clear();
+        for event in &events {
+            push_event(event.clone());
+        }
+
+        let path = temp_dir().join(format!("libafl_qemu_trace_test_{}", std::process::id()));
+        write_trace(&path).unwrap();
+        let reader = QemuTraceReader::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(reader.events(), events.as_slice());
+        let pcs: Vec<u64> = reader.blocks().map(|block| block.pc).collect();
+        assert_eq!(pcs, vec![0x4000, 0x4010]);
+        assert_eq!(reader.syscalls().count(), 1);
+    }
+}
@@ -99,8 +99,22 @@ fn main() {
         common.define("DEFAULT_SANITIZERS_OPTIONS", "1");
     }
 
+    #[cfg(feature = "sanitizer_leaks")]
+    {
+        common.define("DETECT_LEAKS", "1");
+    }
+
     common.file(src_dir.join("common.c")).compile("common");
 
+    #[cfg(feature = "sanitizer_leaks")]
+    {
+        println!("cargo:rerun-if-changed=src/leaks.c");
+
+        cc::Build::new()
+            .file(src_dir.join("leaks.c"))
+            .compile("leaks");
+    }
+
     println!("cargo:rerun-if-changed=src/coverage.c");
 
     cc::Build::new()
@@ -4,7 +4,12 @@
 // TODO MSan and LSan. however they don't support abort_on_error
 
 const char* __asan_default_options() {
+  #ifdef DETECT_LEAKS
+  // Leaks are checked after each run by the LeakObserver, not at exit
+  return "abort_on_error=1:detect_leaks=1:leak_check_at_exit=0:"
+  #else
   return "abort_on_error=1:detect_leaks=0:"
+  #endif
          "malloc_context_size=0:symbolize=0:"
          "allocator_may_return_null=1:"
          "detect_odr_violation=0:handle_segv=0:"
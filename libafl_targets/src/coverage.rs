@@ -17,6 +17,27 @@ pub use __afl_acc_memop_ptr_local as ACCOUNTING_MEMOP_MAP;
 /// The max count of edges tracked.
 pub static mut MAX_EDGES_NUM: usize = 0;
 
+/// The indexes of the edges map touched in the current run, for the `TouchedMapObserver`.
+#[cfg(feature = "touched_indexes")]
+pub static mut TOUCHED_INDEXES: [usize; EDGES_MAP_SIZE] = [0; EDGES_MAP_SIZE];
+
+/// The number of touched indexes in the current run, bigger than [`TOUCHED_INDEXES`] if it overflowed.
+#[cfg(feature = "touched_indexes")]
+pub static mut TOUCHED_INDEXES_LEN: usize = 0;
+
+/// Records that the entry at `pos` of the edges map is touched for the first time in this run.
+///
+/// # Safety
+/// Writes to the global touched list, not thread safe.
+#[cfg(feature = "touched_indexes")]
+#[inline]
+pub unsafe fn record_touched_index(pos: usize) {
+    if TOUCHED_INDEXES_LEN < TOUCHED_INDEXES.len() {
+        *TOUCHED_INDEXES.get_unchecked_mut(TOUCHED_INDEXES_LEN) = pos;
+    }
+    TOUCHED_INDEXES_LEN = TOUCHED_INDEXES_LEN.saturating_add(1);
+}
+
 extern "C" {
     /// The area pointer points to the edges map.
     pub static mut __afl_area_ptr: *mut u8;
@@ -78,7 +99,14 @@ pub unsafe fn edges_map_from_ptr<'a>() -> OwnedSliceMut<'a, u8> {
 #[must_use]
 pub fn edges_max_num() -> usize {
     unsafe {
-        if MAX_EDGES_NUM > 0 {
+        // n-gram and context sensitive indices are hashed over the whole map
+        if MAX_EDGES_NUM > 0
+            && cfg!(not(any(
+                feature = "sancov_ngram4",
+                feature = "sancov_ngram8",
+                feature = "sancov_ctx"
+            )))
+        {
             MAX_EDGES_NUM
         } else {
             #[cfg(feature = "pointer_maps")]
@@ -0,0 +1,248 @@
+//! Execution history for n-gram and calling context sensitive coverage.
+//!
+//! Instead of the edge alone, the coverage map index is derived from the edge and the
+//! last `N - 1` edges before it (n-gram coverage), or from the edge and a hash of the
+//! current call stack (calling context coverage), as in `AFL++`.
+//! The resulting indices are hashed into the usual edges map, so the ordinary map observers can be used.
+
+/// The maximum n-gram size
+pub const NGRAM_SIZE_MAX: usize = 16;
+
+/// The maximum call stack depth tracked by the [`CallContext`], deeper calls keep the deepest context
+pub const CTX_MAX_DEPTH: usize = 64;
+
+/// Mixes a location, so that neighbouring locations end up far away in the map
+#[inline]
+#[must_use]
+pub const fn hash_loc(mut x: u64) -> u64 {
+    x = (x.overflowing_shr(16).0 ^ x).overflowing_mul(0x45d9f3b).0;
+    x = (x.overflowing_shr(16).0 ^ x).overflowing_mul(0x45d9f3b).0;
+    (x.overflowing_shr(16).0 ^ x) ^ x
+}
+
+/// The last `N - 1` locations of the execution, for n-gram coverage
+#[derive(Debug, Clone, Copy)]
+pub struct NgramHistory {
+    prev: [u64; NGRAM_SIZE_MAX],
+    size: usize,
+    pos: usize,
+}
+
+impl NgramHistory {
+    /// Creates a new [`NgramHistory`] for n-grams of `size` locations.
+    /// A `size` of `1` falls back to plain locations, `2` is the usual edge coverage.
+    ///
+    /// # Panics
+    /// Panics if `size` is `0` or bigger than [`NGRAM_SIZE_MAX`].
+    #[must_use]
+    pub const fn new(size: usize) -> Self {
+        assert!(
+            size > 0 && size <= NGRAM_SIZE_MAX,
+            "The n-gram size must be between 1 and NGRAM_SIZE_MAX"
+        );
+        Self {
+            prev: [0; NGRAM_SIZE_MAX],
+            size,
+            pos: 0,
+        }
+    }
+
+    /// The n-gram size
+    #[must_use]
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Forgets the previous locations, call this before each execution
+    pub fn reset(&mut self) {
+        self.prev = [0; NGRAM_SIZE_MAX];
+        self.pos = 0;
+    }
+
+    /// The hash of the previous `N - 1` locations, sensitive to their order
+    #[must_use]
+    pub fn hash(&self) -> u64 {
+        let mut hash = 0;
+        for i in 0..(self.size - 1) {
+            let idx = (self.pos + NGRAM_SIZE_MAX - 1 - i) % NGRAM_SIZE_MAX;
+            hash ^= self.prev[idx].rotate_left(i as u32 + 1);
+        }
+        hash
+    }
+
+    /// Appends a location to the history
+    pub fn push(&mut self, loc: u64) {
+        self.prev[self.pos] = loc;
+        self.pos = (self.pos + 1) % NGRAM_SIZE_MAX;
+    }
+
+    /// Returns the map index of the n-gram ending at `loc` in a map of `map_size` entries,
+    /// and appends the hashed `loc` to the history
+    pub fn index(&mut self, loc: u64, map_size: usize) -> usize {
+        let idx = ((loc ^ self.hash()) as usize) % map_size;
+        self.push(hash_loc(loc));
+        idx
+    }
+}
+
+impl Default for NgramHistory {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+/// A hash of the current call stack, for calling context sensitive coverage
+#[derive(Debug, Clone, Copy)]
+pub struct CallContext {
+    ctx: u64,
+    stack: [u64; CTX_MAX_DEPTH],
+    depth: usize,
+}
+
+impl CallContext {
+    /// Creates a new, empty [`CallContext`]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            ctx: 0,
+            stack: [0; CTX_MAX_DEPTH],
+            depth: 0,
+        }
+    }
+
+    /// The hash of the current calling context
+    #[must_use]
+    pub const fn ctx(&self) -> u64 {
+        self.ctx
+    }
+
+    /// The current call depth
+    #[must_use]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Forgets the call stack, call this before each execution
+    pub fn reset(&mut self) {
+        self.ctx = 0;
+        self.depth = 0;
+    }
+
+    /// Enters a function called from `call_site`
+    pub fn enter(&mut self, call_site: u64) {
+        if self.depth < CTX_MAX_DEPTH {
+            self.stack[self.depth] = self.ctx;
+        }
+        self.depth += 1;
+        self.ctx = self.ctx.rotate_left(1) ^ hash_loc(call_site);
+    }
+
+    /// Returns from the current function
+    pub fn exit(&mut self) {
+        if self.depth == 0 {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth < CTX_MAX_DEPTH {
+            self.ctx = self.stack[self.depth];
+        }
+    }
+
+    /// Returns the map index of `loc` in the current context in a map of `map_size` entries
+    #[must_use]
+    pub fn index(&self, loc: u64, map_size: usize) -> usize {
+        ((loc ^ self.ctx) as usize) % map_size
+    }
+}
+
+impl Default for CallContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_loc, CallContext, NgramHistory, CTX_MAX_DEPTH, NGRAM_SIZE_MAX};
+
+    #[test]
+    fn test_ngram_history() {
+        let mut history = NgramHistory::new(3);
+        assert_eq!(history.hash(), 0);
+
+        // Only the last `size - 1` locations are part of the hash
+        history.push(1);
+        history.push(3);
+        let hash = history.hash();
+        assert_ne!(hash, 0);
+        let mut other = NgramHistory::new(3);
+        other.push(5);
+        other.push(1);
+        other.push(3);
+        assert_eq!(other.hash(), hash);
+
+        // The order matters
+        let mut swapped = NgramHistory::new(3);
+        swapped.push(3);
+        swapped.push(1);
+        assert_ne!(swapped.hash(), hash);
+
+        // The position wraps around
+        for i in 0..(2 * NGRAM_SIZE_MAX as u64) {
+            history.push(i);
+        }
+        history.push(1);
+        history.push(3);
+        assert_eq!(history.hash(), hash);
+
+        history.reset();
+        assert_eq!(history.hash(), 0);
+
+        let idx = history.index(42, 1 << 16);
+        assert_eq!(idx, 42);
+        assert_eq!(history.hash(), hash_loc(42).rotate_left(1));
+
+        // Plain locations for n-grams of size 1
+        let mut plain = NgramHistory::new(1);
+        plain.push(42);
+        assert_eq!(plain.index(7, 1 << 16), 7);
+    }
+
+    #[test]
+    fn test_call_context() {
+        let mut ctx = CallContext::new();
+        assert_eq!(ctx.ctx(), 0);
+        assert_eq!(ctx.index(7, 1 << 16), 7);
+
+        ctx.enter(0x1000);
+        let outer = ctx.ctx();
+        assert_ne!(outer, 0);
+        ctx.enter(0x2000);
+        assert_eq!(ctx.depth(), 2);
+        assert_ne!(ctx.ctx(), outer);
+        ctx.exit();
+        assert_eq!(ctx.ctx(), outer);
+        ctx.exit();
+        assert_eq!(ctx.ctx(), 0);
+
+        // Returning from the outermost function is ignored
+        ctx.exit();
+        assert_eq!(ctx.depth(), 0);
+        assert_eq!(ctx.ctx(), 0);
+
+        // Beyond the maximum depth, the returns keep the deepest context until they are back in range
+        for i in 0..=(CTX_MAX_DEPTH as u64) {
+            ctx.enter(i);
+        }
+        assert_eq!(ctx.depth(), CTX_MAX_DEPTH + 1);
+        let deepest = ctx.ctx();
+        ctx.exit();
+        assert_eq!(ctx.ctx(), deepest);
+        ctx.exit();
+        assert_ne!(ctx.ctx(), deepest);
+
+        ctx.reset();
+        assert_eq!(ctx.depth(), 0);
+        assert_eq!(ctx.ctx(), 0);
+    }
+}
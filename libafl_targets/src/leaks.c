@@ -0,0 +1,71 @@
+#include "common.h"
+#include <stddef.h>
+
+// The sanitizer interface, weak so that we still link without sanitizers
+#pragma GCC diagnostic push
+#pragma GCC diagnostic ignored "-Wunused-parameter"
+EXT_FUNC(__sanitizer_install_malloc_and_free_hooks, int,
+         (void (*malloc_hook)(const volatile void *, size_t),
+          void (*free_hook)(const volatile void *)),
+         false);
+EXT_FUNC(__lsan_enable, void, (void), false);
+EXT_FUNC(__lsan_disable, void, (void), false);
+EXT_FUNC(__lsan_do_recoverable_leak_check, int, (void), false);
+#pragma GCC diagnostic pop
+
+static size_t libafl_leaks_mallocs_cnt;
+static size_t libafl_leaks_frees_cnt;
+static int    libafl_leaks_tracing;
+
+#pragma GCC diagnostic push
+#pragma GCC diagnostic ignored "-Wunused-parameter"
+static void libafl_leaks_malloc_hook(const volatile void *ptr, size_t size) {
+  if (libafl_leaks_tracing) {
+    __atomic_add_fetch(&libafl_leaks_mallocs_cnt, 1, __ATOMIC_RELAXED);
+  }
+}
+
+static void libafl_leaks_free_hook(const volatile void *ptr) {
+  if (libafl_leaks_tracing) {
+    __atomic_add_fetch(&libafl_leaks_frees_cnt, 1, __ATOMIC_RELAXED);
+  }
+}
+#pragma GCC diagnostic pop
+
+// Installs the malloc/free hooks, returns 0 if the target lacks LeakSanitizer
+EXPORT_FN int libafl_leaks_init() {
+  if (!CHECK_WEAK_FN(__sanitizer_install_malloc_and_free_hooks) ||
+      !CHECK_WEAK_FN(__lsan_do_recoverable_leak_check)) {
+    return false;
+  }
+  __sanitizer_install_malloc_and_free_hooks(libafl_leaks_malloc_hook,
+                                            libafl_leaks_free_hook);
+  // Allocations of the fuzzer itself should never be reported
+  if (CHECK_WEAK_FN(__lsan_disable)) { __lsan_disable(); }
+  return true;
+}
+
+EXPORT_FN void libafl_leaks_start() {
+  libafl_leaks_mallocs_cnt = 0;
+  libafl_leaks_frees_cnt = 0;
+  libafl_leaks_tracing = true;
+  if (CHECK_WEAK_FN(__lsan_enable)) { __lsan_enable(); }
+}
+
+EXPORT_FN void libafl_leaks_stop() {
+  if (CHECK_WEAK_FN(__lsan_disable)) { __lsan_disable(); }
+  libafl_leaks_tracing = false;
+}
+
+EXPORT_FN size_t libafl_leaks_mallocs() {
+  return __atomic_load_n(&libafl_leaks_mallocs_cnt, __ATOMIC_RELAXED);
+}
+
+EXPORT_FN size_t libafl_leaks_frees() {
+  return __atomic_load_n(&libafl_leaks_frees_cnt, __ATOMIC_RELAXED);
+}
+
+EXPORT_FN int libafl_leaks_check() {
+  if (!CHECK_WEAK_FN(__lsan_do_recoverable_leak_check)) { return false; }
+  return __lsan_do_recoverable_leak_check();
+}
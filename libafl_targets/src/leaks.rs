@@ -0,0 +1,152 @@
+//! Memory leak detection for in-process fuzzing, similar to `libFuzzer`'s `-detect_leaks`.
+//! The target needs to be built with `LeakSanitizer`, for example as part of `AddressSanitizer`.
+//! `LeakSanitizer` usually only runs at exit, so the [`LeakObserver`] checks for leaks after every run instead.
+
+use alloc::string::{String, ToString};
+use libafl::{
+    bolts::tuples::Named,
+    executors::ExitKind,
+    observers::{Observer, ObserverWithLeakCheck},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+
+extern "C" {
+    fn libafl_leaks_init() -> i32;
+    fn libafl_leaks_start();
+    fn libafl_leaks_stop();
+    fn libafl_leaks_mallocs() -> usize;
+    fn libafl_leaks_frees() -> usize;
+    fn libafl_leaks_check() -> i32;
+}
+
+/// An observer that runs a `LeakSanitizer` leak check after each run of an in-process target.
+/// As a fast pre-filter, the (expensive) leak check only runs if the target did more `malloc`s than `free`s.
+///
+/// Each run is checked at most once, even if the executor calls the observer hooks more than once for it.
+/// Use it together with a [`libafl::feedbacks::LeakFeedback`] in the objective.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeakObserver {
+    name: String,
+    leaked: bool,
+    /// If the leak check already ran for the current run
+    reported: bool,
+    /// If the allocations are currently traced, to keep the `LeakSanitizer` enable and disable calls balanced
+    tracing: bool,
+}
+
+impl LeakObserver {
+    /// Creates a new [`LeakObserver`] with the given name and installs the `malloc`/`free` hooks.
+    /// Fails if the target was not built with `LeakSanitizer`.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        if unsafe { libafl_leaks_init() } == 0 {
+            return Err(Error::illegal_state(
+                "LeakSanitizer not found, build the target with -fsanitize=address or -fsanitize=leak",
+            ));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            leaked: false,
+            reported: false,
+            tracing: false,
+        })
+    }
+
+    /// If the allocations of the target are currently traced, i.e., between `pre_exec` and `post_exec`
+    #[must_use]
+    pub fn tracing(&self) -> bool {
+        self.tracing
+    }
+}
+
+impl ObserverWithLeakCheck for LeakObserver {
+    #[inline]
+    fn leaked(&self) -> bool {
+        self.leaked
+    }
+}
+
+impl<I, S> Observer<I, S> for LeakObserver {
+    #[inline]
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.leaked = false;
+        self.reported = false;
+        // `__lsan_enable` and `__lsan_disable` nest, so they must not be called twice in a row
+        if !self.tracing {
+            self.tracing = true;
+            unsafe {
+                libafl_leaks_start();
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        if !self.tracing {
+            return Ok(());
+        }
+        self.tracing = false;
+        unsafe {
+            libafl_leaks_stop();
+            if *exit_kind == ExitKind::Ok
+                && !self.reported
+                && libafl_leaks_mallocs() > libafl_leaks_frees()
+            {
+                self.leaked = libafl_leaks_check() != 0;
+                self.reported = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Named for LeakObserver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use libafl::{
+        executors::ExitKind,
+        inputs::BytesInput,
+        observers::{Observer, ObserverWithLeakCheck},
+    };
+
+    use crate::leaks::LeakObserver;
+
+    #[test]
+    fn test_leak_observer_state() {
+        // Without `LeakSanitizer` the hooks are no-ops, so the observer is built directly
+        let mut observer = LeakObserver {
+            name: "leaks".to_string(),
+            leaked: true,
+            reported: true,
+            tracing: false,
+        };
+        let input = BytesInput::new(vec![]);
+
+        // A second `pre_exec` before the `post_exec`, e.g., when a hang is confirmed, does not nest
+        Observer::<BytesInput, ()>::pre_exec(&mut observer, &mut (), &input).unwrap();
+        assert!(observer.tracing());
+        assert!(!observer.leaked());
+        Observer::<BytesInput, ()>::pre_exec(&mut observer, &mut (), &input).unwrap();
+        assert!(observer.tracing());
+
+        Observer::<BytesInput, ()>::post_exec(&mut observer, &mut (), &input, &ExitKind::Ok)
+            .unwrap();
+        assert!(!observer.tracing());
+        assert!(!observer.leaked());
+        Observer::<BytesInput, ()>::post_exec(&mut observer, &mut (), &input, &ExitKind::Ok)
+            .unwrap();
+        assert!(!observer.tracing());
+
+        // The next run is checked again
+        observer.reported = true;
+        Observer::<BytesInput, ()>::pre_exec(&mut observer, &mut (), &input).unwrap();
+        assert!(!observer.reported);
+    }
+}
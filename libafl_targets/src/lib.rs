@@ -91,12 +91,20 @@ pub use sancov_8bit::*;
 pub mod coverage;
 pub use coverage::*;
 
+pub mod history;
+pub use history::*;
+
 pub mod value_profile;
 pub use value_profile::*;
 
 pub mod cmplog;
 pub use cmplog::*;
 
+#[cfg(feature = "sanitizer_leaks")]
+pub mod leaks;
+#[cfg(feature = "sanitizer_leaks")]
+pub use leaks::*;
+
 #[cfg(feature = "std")]
 pub mod drcov;
 
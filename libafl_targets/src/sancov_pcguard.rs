@@ -1,8 +1,18 @@
 //! [`LLVM` `PcGuard`](https://clang.llvm.org/docs/SanitizerCoverage.html#tracing-pcs-with-guards) runtime for `LibAFL`.
 
+use alloc::string::{String, ToString};
+use libafl::{bolts::tuples::Named, observers::Observer, Error};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "touched_indexes")]
+use crate::coverage::record_touched_index;
 use crate::coverage::{EDGES_MAP, MAX_EDGES_NUM};
 #[cfg(feature = "pointer_maps")]
 use crate::coverage::{EDGES_MAP_PTR, EDGES_MAP_PTR_SIZE};
+#[cfg(feature = "sancov_ctx")]
+use crate::history::CallContext;
+#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
+use crate::history::NgramHistory;
 
 #[cfg(all(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts"))]
 #[cfg(not(any(doc, feature = "clippy")))]
@@ -10,6 +20,94 @@ compile_error!(
     "the libafl_targets `sancov_pcguard_edges` and `sancov_pcguard_hitcounts` features are mutually exclusive."
 );
 
+#[cfg(all(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
+#[cfg(not(any(doc, feature = "clippy")))]
+compile_error!(
+    "the libafl_targets `sancov_ngram4` and `sancov_ngram8` features are mutually exclusive."
+);
+
+/// The history of the last edges, for n-gram coverage
+#[cfg(all(feature = "sancov_ngram4", not(feature = "sancov_ngram8")))]
+pub static mut SANCOV_NGRAM_HISTORY: NgramHistory = NgramHistory::new(4);
+/// The history of the last edges, for n-gram coverage
+#[cfg(feature = "sancov_ngram8")]
+pub static mut SANCOV_NGRAM_HISTORY: NgramHistory = NgramHistory::new(8);
+
+/// The calling context, updated by the `-finstrument-functions` callbacks
+#[cfg(feature = "sancov_ctx")]
+pub static mut SANCOV_CALL_CONTEXT: CallContext = CallContext::new();
+
+/// Resets the n-gram history and the calling context, call this before each execution
+/// so that the coverage of an input does not depend on the previous one.
+/// The [`SancovHistoryObserver`] does it for in-process executions.
+///
+/// # Safety
+/// Writes to the global history, not thread safe.
+pub unsafe fn sancov_reset_history() {
+    #[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
+    SANCOV_NGRAM_HISTORY.reset();
+    #[cfg(feature = "sancov_ctx")]
+    SANCOV_CALL_CONTEXT.reset();
+}
+
+/// An observer resetting the n-gram history and the calling context of the sancov runtime before each execution,
+/// see [`sancov_reset_history`]. Add it to the observers of the executor with the `sancov_ngram4`,
+/// `sancov_ngram8` or `sancov_ctx` features, it does nothing otherwise.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SancovHistoryObserver {
+    name: String,
+}
+
+impl SancovHistoryObserver {
+    /// Creates a new [`SancovHistoryObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl<I, S> Observer<I, S> for SancovHistoryObserver {
+    #[inline]
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        unsafe { sancov_reset_history() };
+        Ok(())
+    }
+}
+
+impl Named for SancovHistoryObserver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Callback of `-finstrument-functions`, called on each function entry.
+///
+/// # Safety
+/// Writes to the global calling context, not thread safe.
+#[cfg(feature = "sancov_ctx")]
+#[no_mangle]
+pub unsafe extern "C" fn __cyg_profile_func_enter(
+    _this_fn: *const core::ffi::c_void,
+    call_site: *const core::ffi::c_void,
+) {
+    SANCOV_CALL_CONTEXT.enter(call_site as u64);
+}
+
+/// Callback of `-finstrument-functions`, called on each function exit.
+///
+/// # Safety
+/// Writes to the global calling context, not thread safe.
+#[cfg(feature = "sancov_ctx")]
+#[no_mangle]
+pub unsafe extern "C" fn __cyg_profile_func_exit(
+    _this_fn: *const core::ffi::c_void,
+    _call_site: *const core::ffi::c_void,
+) {
+    SANCOV_CALL_CONTEXT.exit();
+}
+
 /// Callback for sancov `pc_guard` - usually called by `llvm` on each block or edge.
 ///
 /// # Safety
@@ -17,8 +115,49 @@ compile_error!(
 /// Should usually not be called directly.
 #[no_mangle]
 pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard(guard: *mut u32) {
-    let pos = *guard as usize;
-    #[cfg(feature = "pointer_maps")]
+    #[allow(unused_mut)]
+    let mut pos = *guard as usize;
+    #[cfg(any(
+        feature = "sancov_ngram4",
+        feature = "sancov_ngram8",
+        feature = "sancov_ctx"
+    ))]
+    {
+        #[cfg(feature = "pointer_maps")]
+        let map_size = EDGES_MAP_PTR_SIZE;
+        #[cfg(not(feature = "pointer_maps"))]
+        let map_size = EDGES_MAP.len();
+        #[cfg(feature = "sancov_ctx")]
+        {
+            pos = SANCOV_CALL_CONTEXT.index(pos as u64, map_size);
+        }
+        #[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
+        {
+            pos = SANCOV_NGRAM_HISTORY.index(pos as u64, map_size);
+        }
+    }
+    #[cfg(feature = "touched_indexes")]
+    {
+        #[cfg(feature = "pointer_maps")]
+        let addr = (EDGES_MAP_PTR as *mut u8).add(pos);
+        #[cfg(not(feature = "pointer_maps"))]
+        let addr = EDGES_MAP.as_mut_ptr().add(pos);
+        let old = addr.read();
+        if old == 0 {
+            record_touched_index(pos);
+        }
+        #[cfg(feature = "sancov_pcguard_edges")]
+        {
+            addr.write(1);
+        }
+        #[cfg(feature = "sancov_pcguard_hitcounts")]
+        {
+            // Never-zero counters, so that each index is recorded only once
+            let (val, overflow) = old.overflowing_add(1);
+            addr.write(val + u8::from(overflow));
+        }
+    }
+    #[cfg(all(feature = "pointer_maps", not(feature = "touched_indexes")))]
     {
         #[cfg(feature = "sancov_pcguard_edges")]
         {
@@ -31,7 +170,7 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard(guard: *mut u32) {
             addr.write(val);
         }
     }
-    #[cfg(not(feature = "pointer_maps"))]
+    #[cfg(not(any(feature = "pointer_maps", feature = "touched_indexes")))]
     {
         #[cfg(feature = "sancov_pcguard_edges")]
         {
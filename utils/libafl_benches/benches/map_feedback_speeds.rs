@@ -0,0 +1,114 @@
+//! Compare the speed of the map feedback scanning the whole map with the one scanning only the touched indexes
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use libafl::{
+    bolts::{
+        rands::{Rand, StdRand},
+        tuples::tuple_list,
+    },
+    corpus::InMemoryCorpus,
+    events::NopEventManager,
+    executors::ExitKind,
+    feedbacks::{Feedback, MaxMapFeedback},
+    inputs::BytesInput,
+    observers::{HitcountsMapObserver, ObserversTuple, StdMapObserver, TouchedMapObserver},
+    state::StdState,
+};
+
+const MAP_SIZE: usize = 65536;
+const TOUCHED_NUM: usize = 512;
+
+static mut MAP: [u8; MAP_SIZE] = [0; MAP_SIZE];
+static mut TOUCHED: [usize; MAP_SIZE] = [0; MAP_SIZE];
+static mut TOUCHED_LEN: usize = 0;
+
+/// Simulates the instrumentation of a run hitting the given edges
+fn run_target(edges: &[usize]) {
+    unsafe {
+        for &pos in edges {
+            if MAP[pos] == 0 {
+                TOUCHED[TOUCHED_LEN] = pos;
+                TOUCHED_LEN += 1;
+            }
+            let (val, overflow) = MAP[pos].overflowing_add(1);
+            MAP[pos] = val + u8::from(overflow);
+        }
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut rand = StdRand::with_seed(0);
+    let edges: Vec<usize> = (0..TOUCHED_NUM)
+        .map(|_| rand.below(MAP_SIZE as u64) as usize)
+        .collect();
+    let input = BytesInput::new(vec![0; 4]);
+    let mut mgr = NopEventManager {};
+
+    {
+        let observer =
+            HitcountsMapObserver::new(StdMapObserver::new("edges", unsafe { &mut MAP[..] }));
+        let mut feedback = MaxMapFeedback::<BytesInput, _, _, u8>::new(&observer);
+        let mut observers = tuple_list!(observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut (),
+        )
+        .unwrap();
+
+        c.bench_function("map_feedback_full_scan", |b| {
+            b.iter(|| {
+                observers.pre_exec_all(&mut state, &input).unwrap();
+                run_target(black_box(&edges));
+                observers
+                    .post_exec_all(&mut state, &input, &ExitKind::Ok)
+                    .unwrap();
+                feedback
+                    .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                    .unwrap()
+            })
+        });
+    }
+
+    unsafe {
+        MAP = [0; MAP_SIZE];
+        TOUCHED_LEN = 0;
+    }
+
+    {
+        let observer = HitcountsMapObserver::new(TouchedMapObserver::new(
+            "edges",
+            unsafe { &mut MAP[..] },
+            unsafe { &mut TOUCHED[..] },
+            unsafe { &mut TOUCHED_LEN },
+        ));
+        let mut feedback = MaxMapFeedback::<BytesInput, _, _, u8>::new(&observer);
+        let mut observers = tuple_list!(observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut (),
+        )
+        .unwrap();
+
+        c.bench_function("map_feedback_touched", |b| {
+            b.iter(|| {
+                observers.pre_exec_all(&mut state, &input).unwrap();
+                run_target(black_box(&edges));
+                observers
+                    .post_exec_all(&mut state, &input, &ExitKind::Ok)
+                    .unwrap();
+                feedback
+                    .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                    .unwrap()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
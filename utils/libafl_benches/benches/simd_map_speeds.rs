@@ -0,0 +1,106 @@
+//! Compare the speed of the scalar and the vectorized coverage map operations
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use libafl::bolts::{
+    rands::{Rand, StdRand},
+    simd::{
+        any_novel_max_with, classify_counts_with, merge_max_with, simd_level, SimdLevel,
+        COUNT_CLASS_LOOKUP,
+    },
+};
+
+const MAP_SIZE: usize = 65536;
+
+/// The classification with a 16 bit lookup table, as `HitcountsMapObserver` did before
+fn classify_counts_lookup16(map: &mut [u8], lookup16: &[u16]) {
+    let (pairs, tail) = map.split_at_mut(map.len() & !1);
+    for pair in pairs.chunks_exact_mut(2) {
+        let item = u16::from_ne_bytes([pair[0], pair[1]]);
+        pair.copy_from_slice(&lookup16[item as usize].to_ne_bytes());
+    }
+    for item in tail {
+        *item = COUNT_CLASS_LOOKUP[*item as usize];
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut rand = StdRand::with_seed(0);
+    // A sparse map, as after a typical run
+    let mut map = vec![0_u8; MAP_SIZE];
+    for _ in 0..1024 {
+        map[rand.below(MAP_SIZE as u64) as usize] = rand.below(256) as u8;
+    }
+    let mut history = map.clone();
+    history.iter_mut().for_each(|x| *x = x.saturating_sub(1));
+
+    let mut lookup16 = vec![0_u16; 65536];
+    for (i, class) in lookup16.iter_mut().enumerate() {
+        let [lo, hi] = (i as u16).to_ne_bytes();
+        *class = u16::from_ne_bytes([
+            COUNT_CLASS_LOOKUP[lo as usize],
+            COUNT_CLASS_LOOKUP[hi as usize],
+        ]);
+    }
+
+    let level = simd_level();
+    println!("Using {:?}", level);
+
+    c.bench_function("classify_lookup16", |b| {
+        b.iter(|| {
+            let mut map = map.clone();
+            classify_counts_lookup16(black_box(&mut map), &lookup16);
+            map
+        })
+    });
+    c.bench_function("classify_scalar", |b| {
+        b.iter(|| {
+            let mut map = map.clone();
+            classify_counts_with(SimdLevel::Scalar, black_box(&mut map));
+            map
+        })
+    });
+    c.bench_function("classify_simd", |b| {
+        b.iter(|| {
+            let mut map = map.clone();
+            classify_counts_with(level, black_box(&mut map));
+            map
+        })
+    });
+
+    c.bench_function("any_novel_scalar", |b| {
+        b.iter(|| any_novel_max_with(SimdLevel::Scalar, black_box(&map), black_box(&map)))
+    });
+    c.bench_function("any_novel_simd", |b| {
+        b.iter(|| any_novel_max_with(level, black_box(&map), black_box(&map)))
+    });
+
+    c.bench_function("merge_scalar", |b| {
+        b.iter(|| {
+            let mut history = history.clone();
+            let mut novelties = vec![];
+            merge_max_with(
+                SimdLevel::Scalar,
+                black_box(&mut history),
+                black_box(&map),
+                Some(&mut novelties),
+            );
+            novelties
+        })
+    });
+    c.bench_function("merge_simd", |b| {
+        b.iter(|| {
+            let mut history = history.clone();
+            let mut novelties = vec![];
+            merge_max_with(
+                level,
+                black_box(&mut history),
+                black_box(&map),
+                Some(&mut novelties),
+            );
+            novelties
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);